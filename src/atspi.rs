@@ -0,0 +1,155 @@
+use anyhow::{Context, Result};
+use std::time::Duration;
+use zbus::zvariant::OwnedObjectPath;
+
+const REGISTRY_DEST: &str = "org.a11y.atspi.Registry";
+const ROOT_PATH: &str = "/org/a11y/atspi/accessible/root";
+const ACCESSIBLE_IFACE: &str = "org.a11y.atspi.Accessible";
+const TEXT_IFACE: &str = "org.a11y.atspi.Text";
+const EDITABLE_TEXT_IFACE: &str = "org.a11y.atspi.EditableText";
+
+/// Bits of an AT-SPI state set (AtspiStateType)
+const STATE_ACTIVE: u32 = 1;
+const STATE_EDITABLE: u32 = 7;
+const STATE_FOCUSED: u32 = 12;
+const STATE_SHOWING: u32 = 25;
+
+/// Accessible objects looked at before giving up on finding the focus, so
+/// a huge document can't stall typing
+const MAX_OBJECTS: usize = 10_000;
+
+/// How long finding the focused field and inserting into it may take, as
+/// an application that hangs never answers
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An accessible object: the bus name of its application and its path
+type Object = (String, OwnedObjectPath);
+
+/// Insert `text` at the caret of the focused editable field through the
+/// AT-SPI accessibility interface, without any key events. Works wherever
+/// the toolkit exposes editable text (GTK, Qt, Firefox, LibreOffice, and
+/// Chromium with accessibility turned on), under Wayland and X11 alike.
+pub async fn insert(text: &str) -> Result<()> {
+    tokio::time::timeout(TIMEOUT, async {
+        let conn = connect().await?;
+        let field = focused_field(&conn).await?;
+        let caret: i32 = proxy(&conn, &field, TEXT_IFACE)
+            .await?
+            .get_property("CaretOffset")
+            .await
+            .context("The focused field doesn't report its caret")?;
+        let length = text.chars().count() as i32;
+        let inserted: bool = proxy(&conn, &field, EDITABLE_TEXT_IFACE)
+            .await?
+            .call("InsertText", &(caret, text, length))
+            .await
+            .context("Failed to insert text through AT-SPI")?;
+        if !inserted {
+            anyhow::bail!("The focused field refused the text");
+        }
+        // Not every toolkit moves the caret past inserted text by itself
+        let _: zbus::Result<bool> = proxy(&conn, &field, TEXT_IFACE)
+            .await?
+            .call("SetCaretOffset", &(caret + length,))
+            .await;
+        Ok(())
+    })
+    .await
+    .context("The application didn't answer AT-SPI requests in time")?
+}
+
+/// The accessibility bus, whose address the session bus hands out
+async fn connect() -> Result<zbus::Connection> {
+    let session = zbus::Connection::session()
+        .await
+        .context("No D-Bus session bus")?;
+    let bus = zbus::Proxy::new(&session, "org.a11y.Bus", "/org/a11y/bus", "org.a11y.Bus").await?;
+    let address: String = bus
+        .call("GetAddress", &())
+        .await
+        .context("The accessibility bus isn't running (is at-spi2-core installed?)")?;
+    zbus::connection::Builder::address(address.as_str())?
+        .build()
+        .await
+        .context("Failed to connect to the accessibility bus")
+}
+
+/// A proxy for `interface` of `object`. Properties aren't cached, which
+/// would cost a subscription per object while searching.
+async fn proxy<'a>(
+    conn: &zbus::Connection,
+    (dest, path): &'a Object,
+    interface: &'a str,
+) -> Result<zbus::Proxy<'a>> {
+    Ok(zbus::proxy::Builder::new(conn)
+        .destination(dest.as_str())?
+        .path(path.as_str())?
+        .interface(interface)?
+        .cache_properties(zbus::proxy::CacheProperties::No)
+        .build()
+        .await?)
+}
+
+async fn children(conn: &zbus::Connection, object: &Object) -> Result<Vec<Object>> {
+    Ok(proxy(conn, object, ACCESSIBLE_IFACE)
+        .await?
+        .call("GetChildren", &())
+        .await?)
+}
+
+/// The state set of `object`, as bits in 32-bit words; empty when it
+/// doesn't answer
+async fn states(conn: &zbus::Connection, object: &Object) -> Vec<u32> {
+    let Ok(proxy) = proxy(conn, object, ACCESSIBLE_IFACE).await else {
+        return Vec::new();
+    };
+    proxy.call("GetState", &()).await.unwrap_or_default()
+}
+
+/// Whether `set` has all of `states`
+fn has(set: &[u32], states: &[u32]) -> bool {
+    states.iter().all(|&state| {
+        set.get((state / 32) as usize)
+            .is_some_and(|word| word & (1 << (state % 32)) != 0)
+    })
+}
+
+/// The focused editable object in the active window. AT-SPI has no call for
+/// the focus, so the visible part of the active window is searched for it.
+async fn focused_field(conn: &zbus::Connection) -> Result<Object> {
+    let root = (
+        REGISTRY_DEST.to_string(),
+        OwnedObjectPath::try_from(ROOT_PATH)?,
+    );
+    let mut windows = Vec::new();
+    for app in children(conn, &root).await? {
+        // An application that doesn't answer can't be the one in front
+        for window in children(conn, &app).await.unwrap_or_default() {
+            if has(&states(conn, &window).await, &[STATE_ACTIVE]) {
+                windows.push(window);
+            }
+        }
+    }
+    if windows.is_empty() {
+        anyhow::bail!(
+            "No active window found through AT-SPI (is accessibility turned on for the application?)"
+        );
+    }
+
+    let mut pending = windows;
+    let mut seen = 0;
+    while let Some(object) = pending.pop() {
+        seen += 1;
+        if seen > MAX_OBJECTS {
+            break;
+        }
+        let set = states(conn, &object).await;
+        if has(&set, &[STATE_FOCUSED, STATE_EDITABLE]) {
+            return Ok(object);
+        }
+        if has(&set, &[STATE_SHOWING]) {
+            pending.extend(children(conn, &object).await.unwrap_or_default());
+        }
+    }
+    anyhow::bail!("The focused window has no editable field with the focus that AT-SPI can reach")
+}
@@ -1,6 +1,184 @@
 use anyhow::{Context, Result};
-use flacenc::component::BitRepr;
-use flacenc::error::Verify;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+
+use crate::error::Error;
+
+/// Microphone capture from an input device (the default one unless named).
+///
+/// The input stream stays open for the recorder's whole lifetime, but samples
+/// are only kept between [`Recorder::start`] and [`Recorder::stop`], so a
+/// long-lived recorder pays the device setup cost once.
+pub struct Recorder {
+    /// `None` when replaying a recording instead of capturing
+    _stream: Option<cpal::Stream>,
+    /// Chunks from the capture callback, which must never wait for a lock
+    /// or it drops audio
+    incoming: Mutex<mpsc::Receiver<Vec<i16>>>,
+    /// What was captured since `start`, moved over from `incoming` whenever
+    /// it's asked for
+    samples: Mutex<Vec<i16>>,
+    capturing: Arc<AtomicBool>,
+    /// Audio handed out as if captured live, see [`Recorder::replay`]
+    replay: Option<Vec<i16>>,
+}
+
+impl Recorder {
+    pub fn open(device_name: Option<&str>) -> Result<Self> {
+        // Initialize audio host and device
+        let host = cpal::default_host();
+        let device = match device_name {
+            Some(name) => find_input_device(&host, name)?,
+            None => host
+                .default_input_device()
+                .ok_or_else(|| Error::NoInputDevice("no default input device".to_string()))?,
+        };
+
+        // Configure input stream
+        let config = cpal::StreamConfig {
+            channels: crate::CHANNELS,
+            sample_rate: cpal::SampleRate(crate::SAMPLE_RATE),
+            buffer_size: cpal::BufferSize::Default,
+        };
+
+        // Collect raw samples in memory
+        let (sender, incoming) = mpsc::channel();
+        let capturing = Arc::new(AtomicBool::new(false));
+
+        let stream = device
+            .build_input_stream(
+                &config,
+                {
+                    let capturing = Arc::clone(&capturing);
+                    move |data: &[f32], _: &_| {
+                        if !capturing.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        let chunk = data.iter().map(|&s| (s * i16::MAX as f32) as i16);
+                        let _ = sender.send(chunk.collect());
+                    }
+                },
+                move |err| eprintln!("An error occurred on stream: {}", err),
+                None,
+            )
+            .map_err(|e| Error::NoInputDevice(e.to_string()))?;
+
+        stream.play()?;
+
+        Ok(Self {
+            _stream: Some(stream),
+            incoming: Mutex::new(incoming),
+            samples: Mutex::new(Vec::new()),
+            capturing,
+            replay: None,
+        })
+    }
+
+    /// A recorder that doesn't touch any audio device: every `start`
+    /// "captures" `samples` at once, so the rest of the pipeline can be
+    /// driven from a file.
+    pub fn replay(samples: Vec<i16>) -> Self {
+        Self {
+            _stream: None,
+            incoming: Mutex::new(mpsc::channel().1),
+            samples: Mutex::new(Vec::new()),
+            capturing: Arc::new(AtomicBool::new(false)),
+            replay: Some(samples),
+        }
+    }
+
+    /// Start keeping samples, discarding anything captured before.
+    pub fn start(&self) {
+        let mut samples = self.collect();
+        samples.clear();
+        if let Some(ref replay) = self.replay {
+            samples.extend_from_slice(replay);
+        }
+        self.capturing.store(true, Ordering::Relaxed);
+    }
+
+    /// A copy of what was captured since `start` or the last `drain`, for a
+    /// look at the recording while it goes on.
+    pub fn snapshot(&self) -> Vec<i16> {
+        self.collect().clone()
+    }
+
+    /// Loudness of the last `window` samples captured, as their RMS relative
+    /// to full scale (0.0–1.0), for a level meter.
+    pub fn level(&self, window: usize) -> f32 {
+        let samples = self.collect();
+        let tail = &samples[samples.len().saturating_sub(window)..];
+        if tail.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = tail.iter().map(|&s| f64::from(s).powi(2)).sum();
+        ((sum / tail.len() as f64).sqrt() / f64::from(i16::MAX)) as f32
+    }
+
+    /// Hand over what was captured since `start` or the last `drain`, and
+    /// keep recording.
+    pub fn drain(&self) -> Vec<i16> {
+        std::mem::take(&mut *self.collect())
+    }
+
+    /// Stop keeping samples and hand over what was captured since `start`
+    /// (or the last `drain`).
+    pub fn stop(&self) -> Vec<i16> {
+        self.capturing.store(false, Ordering::Relaxed);
+        std::mem::take(&mut *self.collect())
+    }
+
+    /// The samples kept so far, with everything the callback sent since the
+    /// last call added
+    fn collect(&self) -> MutexGuard<'_, Vec<i16>> {
+        let mut samples = self.samples.lock().unwrap();
+        for chunk in self.incoming.lock().unwrap().try_iter() {
+            samples.extend(chunk);
+        }
+        samples
+    }
+}
+
+/// Names of the input devices, for picking one with `--device`.
+pub fn input_devices() -> Result<Vec<String>> {
+    let host = cpal::default_host();
+    Ok(host
+        .input_devices()
+        .map_err(|e| Error::NoInputDevice(format!("failed to list input devices: {}", e)))?
+        .filter_map(|d| d.name().ok())
+        .collect())
+}
+
+/// Name of the input device recorded from without `--device`, if any
+pub fn default_input_device() -> Option<String> {
+    cpal::default_host()
+        .default_input_device()
+        .and_then(|d| d.name().ok())
+}
+
+/// Find an input device by exact name.
+fn find_input_device(host: &cpal::Host, name: &str) -> Result<cpal::Device> {
+    let devices: Vec<cpal::Device> = host
+        .input_devices()
+        .map_err(|e| Error::NoInputDevice(format!("failed to list input devices: {}", e)))?
+        .collect();
+    let mut names = Vec::new();
+    for device in devices {
+        let device_name = device.name().unwrap_or_default();
+        if device_name == name {
+            return Ok(device);
+        }
+        names.push(device_name);
+    }
+    Err(Error::NoInputDevice(format!(
+        "'{}' not found (see rpdictation devices). Available: {}",
+        name,
+        names.join(", ")
+    ))
+    .into())
+}
 
 pub fn samples_to_wav(samples: &[i16], sample_rate: u32) -> Result<Vec<u8>> {
     let spec = hound::WavSpec {
@@ -21,7 +199,19 @@ pub fn samples_to_wav(samples: &[i16], sample_rate: u32) -> Result<Vec<u8>> {
     Ok(cursor.into_inner())
 }
 
+/// How long WAV-encoded audio plays, in seconds (0 if it can't be parsed)
+pub fn wav_seconds(wav_data: &[u8]) -> f64 {
+    match hound::WavReader::new(std::io::Cursor::new(wav_data)) {
+        Ok(reader) => reader.duration() as f64 / reader.spec().sample_rate as f64,
+        Err(_) => 0.0,
+    }
+}
+
+#[cfg(feature = "google")]
 pub fn wav_to_flac(wav_data: &[u8], sample_rate: u32) -> Result<Vec<u8>> {
+    use flacenc::component::BitRepr;
+    use flacenc::error::Verify;
+
     // Parse WAV file to get PCM samples
     let mut cursor = std::io::Cursor::new(wav_data);
     let reader = hound::WavReader::new(&mut cursor).context("Failed to parse WAV data")?;
@@ -55,3 +245,48 @@ pub fn wav_to_flac(wav_data: &[u8], sample_rate: u32) -> Result<Vec<u8>> {
 
     Ok(sink.as_slice().to_vec())
 }
+
+/// Decode an audio file to 16 kHz mono samples. WAV files already in that
+/// format are read directly; everything else goes through ffmpeg.
+pub async fn decode_file(path: &Path) -> Result<Vec<i16>> {
+    if let Ok(mut reader) = hound::WavReader::open(path) {
+        let spec = reader.spec();
+        if spec.sample_rate == crate::SAMPLE_RATE
+            && spec.channels == 1
+            && spec.bits_per_sample == 16
+            && spec.sample_format == hound::SampleFormat::Int
+        {
+            return reader
+                .samples::<i16>()
+                .collect::<Result<_, _>>()
+                .context("Failed to read WAV file");
+        }
+    }
+
+    let output = tokio::process::Command::new("ffmpeg")
+        .args(["-nostdin", "-loglevel", "error", "-i"])
+        .arg(path)
+        .args([
+            "-f",
+            "s16le",
+            "-ac",
+            "1",
+            "-ar",
+            &crate::SAMPLE_RATE.to_string(),
+            "-",
+        ])
+        .output()
+        .await
+        .context("Failed to run ffmpeg")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output
+        .stdout
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .collect())
+}
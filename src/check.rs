@@ -0,0 +1,30 @@
+use anyhow::Result;
+
+use crate::Args;
+
+/// Try every provider in the chain with a cheap request, so a rejected key
+/// or missing model shows up before anything is recorded. Prints one line
+/// per provider; fails with the first provider's error if any of them
+/// failed, so the exit code says why.
+pub async fn run(args: &Args) -> Result<()> {
+    let providers = rpdictation::setup::build_providers(&args.options())?;
+    let mut failures = Vec::new();
+    for provider in &providers {
+        match provider.check().await {
+            Ok(()) => say!("{}: OK", provider.name()),
+            Err(e) => {
+                say!("{}: {:#}", provider.name(), e);
+                failures.push(e);
+            }
+        }
+    }
+    let failed = failures.len();
+    match failures.into_iter().next() {
+        None => Ok(()),
+        Some(first) => Err(first.context(format!(
+            "{} of {} providers failed the check",
+            failed,
+            providers.len()
+        ))),
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::{Context, Result};
+use tokio::io::AsyncWriteExt;
+
+/// Clipboard tools tried by [`copy`], in order, with their arguments
+const COPY_TOOLS: &[(&str, &[&str])] = &[
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+];
+
+/// The same for [`copy_primary`]
+const PRIMARY_TOOLS: &[(&str, &[&str])] = &[
+    ("wl-copy", &["--primary"]),
+    ("xclip", &["-selection", "primary"]),
+    ("xsel", &["--primary", "--input"]),
+];
+
+/// Put `text` on the clipboard with the first available tool: wl-copy on
+/// Wayland, xclip or xsel on X11.
+pub async fn copy(text: &str) -> Result<()> {
+    copy_with(COPY_TOOLS, text).await
+}
+
+/// Put `text` in the primary selection, which middle-click pastes, with
+/// the same tools as [`copy`]
+pub async fn copy_primary(text: &str) -> Result<()> {
+    copy_with(PRIMARY_TOOLS, text).await
+}
+
+async fn copy_with(tools: &[(&str, &[&str])], text: &str) -> Result<()> {
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    for (tool, args) in tools {
+        if (*tool == "wl-copy") != wayland {
+            continue;
+        }
+        let mut child = match tokio::process::Command::new(tool)
+            .args(*args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).with_context(|| format!("Failed to spawn {}", tool)),
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes()).await?;
+        }
+        let status = child.wait().await?;
+        if !status.success() {
+            anyhow::bail!("{} failed with {}", tool, status);
+        }
+        return Ok(());
+    }
+    anyhow::bail!(
+        "No clipboard tool found (install {})",
+        if wayland {
+            "wl-clipboard"
+        } else {
+            "xclip or xsel"
+        }
+    )
+}
+
+pub struct ClipboardSnapshot {
+    mime: String,
+    data: Vec<u8>,
+}
+
+pub async fn save_selection(primary: bool) -> Option<ClipboardSnapshot> {
+    let mut list_args: Vec<&str> = vec!["--list-types"];
+    if primary {
+        list_args.push("--primary");
+    }
+    let list_out = tokio::process::Command::new("wl-paste")
+        .args(&list_args)
+        .output()
+        .await
+        .ok()?;
+    if !list_out.status.success() {
+        return None;
+    }
+    let mime = String::from_utf8_lossy(&list_out.stdout)
+        .lines()
+        .next()?
+        .trim()
+        .to_string();
+    if mime.is_empty() {
+        return None;
+    }
+
+    let mut read_args: Vec<&str> = vec!["--no-newline", "--type", &mime];
+    if primary {
+        read_args.push("--primary");
+    }
+    let data_out = tokio::process::Command::new("wl-paste")
+        .args(&read_args)
+        .output()
+        .await
+        .ok()?;
+    if !data_out.status.success() {
+        return None;
+    }
+
+    Some(ClipboardSnapshot {
+        mime,
+        data: data_out.stdout,
+    })
+}
+
+pub async fn restore_selection(primary: bool, snap: Option<ClipboardSnapshot>) -> Result<()> {
+    match snap {
+        Some(s) => {
+            let mut cmd = tokio::process::Command::new("wl-copy");
+            cmd.arg("--type").arg(&s.mime);
+            if primary {
+                cmd.arg("--primary");
+            }
+            cmd.stdin(std::process::Stdio::piped());
+            let mut child = cmd.spawn().context("Failed to spawn wl-copy for restore")?;
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(&s.data).await.ok();
+            }
+            child.wait().await.context("wl-copy restore failed")?;
+        }
+        None => {
+            let mut cmd = tokio::process::Command::new("wl-copy");
+            cmd.arg("--clear");
+            if primary {
+                cmd.arg("--primary");
+            }
+            cmd.status().await.context("wl-copy --clear failed")?;
+        }
+    }
+    Ok(())
+}
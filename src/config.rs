@@ -0,0 +1,422 @@
+use anyhow::Result;
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::Args;
+use rpdictation::notifier;
+use rpdictation::numbers::NumberStyle;
+use rpdictation::profanity::Profanity;
+use rpdictation::punctuation::Case;
+use rpdictation::rewrite::SummaryMode;
+use rpdictation::setup::Route;
+use rpdictation::typer::{AppStyle, PasteKey};
+
+/// Settings from `config.toml`. Every field is optional; command-line flags
+/// win over the file, and for API keys the environment does too.
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub provider: Option<String>,
+    pub language: Option<String>,
+    pub openai_api_key: Option<String>,
+    pub mistral_api_key: Option<String>,
+    pub groq_api_key: Option<String>,
+    pub google_api_key: Option<String>,
+    /// Shell command printing the OpenAI key, e.g. "pass show openai"
+    pub openai_key_command: Option<String>,
+    pub mistral_key_command: Option<String>,
+    pub groq_key_command: Option<String>,
+    pub token_endpoint: Option<String>,
+    pub wyoming_server: Option<String>,
+    /// As for --keyring
+    pub keyring: Option<bool>,
+    /// As for --preflight
+    pub preflight: Option<bool>,
+    pub typer: Option<String>,
+    /// Comma-separated output sinks, as for --output
+    pub output: Option<String>,
+    pub paste: Option<bool>,
+    pub paste_key: Option<PasteKey>,
+    pub type_delay: Option<u64>,
+    /// Seconds, as for --delay
+    pub delay: Option<u64>,
+    pub enter: Option<bool>,
+    pub append: Option<bool>,
+    pub track_window: Option<bool>,
+    pub strict_focus: Option<bool>,
+    pub history: Option<bool>,
+    pub clipboard: Option<bool>,
+    pub primary: Option<bool>,
+    pub osc52: Option<bool>,
+    pub device: Option<String>,
+    pub notifications: Option<bool>,
+    pub notifier: Option<notifier::Kind>,
+    pub runtime_dir: Option<PathBuf>,
+    pub session: Option<String>,
+    pub global_shortcut: Option<String>,
+    pub push_to_talk: Option<String>,
+    pub wake_word: Option<String>,
+    pub websocket: Option<String>,
+    pub metrics: Option<String>,
+    pub otlp_endpoint: Option<String>,
+    pub webhook: Option<String>,
+    pub mqtt: Option<String>,
+    pub exec: Option<String>,
+    pub append_file: Option<PathBuf>,
+    pub append_header: Option<String>,
+    pub journal: Option<String>,
+    pub journal_heading: Option<String>,
+    pub journal_template: Option<PathBuf>,
+    pub org: Option<PathBuf>,
+    pub org_headline: Option<String>,
+    pub nvim: Option<String>,
+    pub tmux: Option<String>,
+    pub confirm: Option<bool>,
+    pub edit: Option<bool>,
+    pub replacements: Option<PathBuf>,
+    /// As for --spell
+    pub spell: Option<bool>,
+    pub voice_commands: Option<bool>,
+    pub timings: Option<bool>,
+    pub live: Option<bool>,
+    pub live_type: Option<bool>,
+    pub overlay: Option<bool>,
+    pub numbers: Option<NumberStyle>,
+    pub date_format: Option<String>,
+    pub case: Option<Case>,
+    pub profanity: Option<Profanity>,
+    pub summarize: Option<SummaryMode>,
+    pub translate_to: Option<String>,
+    pub llm: Option<String>,
+    pub llm_model: Option<String>,
+    pub strip_period: Option<bool>,
+    pub collapse_whitespace: Option<bool>,
+    /// Terms the provider should expect, as for --vocabulary
+    pub vocabulary: Option<Vec<String>>,
+    /// Stored with each dictation in the history, as for --tag
+    pub tags: Option<Vec<String>>,
+    /// How long the history keeps dictations, e.g. "90d", as for
+    /// --keep-transcripts
+    #[serde(default, deserialize_with = "duration")]
+    pub keep_transcripts: Option<Duration>,
+    /// How long the history keeps their audio, as for --keep-audio
+    #[serde(default, deserialize_with = "duration")]
+    pub keep_audio: Option<Duration>,
+    pub vad_threshold: Option<f64>,
+    /// Seconds, as for --api-timeout
+    pub api_timeout: Option<u64>,
+    /// Dollars, as for --max-cost
+    pub max_cost: Option<f64>,
+    pub proxy: Option<String>,
+    pub ca_cert: Option<PathBuf>,
+    /// How text is typed into particular applications, by app-id or class
+    #[serde(default)]
+    pub app: BTreeMap<String, AppStyle>,
+    /// How dictations detected to be in a language are handled, by language
+    /// code, with `language = "auto"`
+    #[serde(default)]
+    pub route: BTreeMap<String, Route>,
+    /// Named sets of overrides, selected with --profile
+    #[serde(default)]
+    pub profile: BTreeMap<String, Config>,
+}
+
+impl Config {
+    /// The base settings with those of profile `name` laid over them.
+    pub fn with_profile(mut self, name: &str) -> Result<Config> {
+        let Some(p) = self.profile.remove(name) else {
+            let names: Vec<&str> = self.profile.keys().map(String::as_str).collect();
+            anyhow::bail!(
+                "Profile '{}' not found in config file (available: {})",
+                name,
+                if names.is_empty() {
+                    "none".to_string()
+                } else {
+                    names.join(", ")
+                }
+            );
+        };
+        if !p.profile.is_empty() {
+            anyhow::bail!("Profile '{}' contains nested profiles", name);
+        }
+        Ok(Config {
+            provider: p.provider.or(self.provider),
+            language: p.language.or(self.language),
+            openai_api_key: p.openai_api_key.or(self.openai_api_key),
+            mistral_api_key: p.mistral_api_key.or(self.mistral_api_key),
+            groq_api_key: p.groq_api_key.or(self.groq_api_key),
+            google_api_key: p.google_api_key.or(self.google_api_key),
+            openai_key_command: p.openai_key_command.or(self.openai_key_command),
+            mistral_key_command: p.mistral_key_command.or(self.mistral_key_command),
+            groq_key_command: p.groq_key_command.or(self.groq_key_command),
+            token_endpoint: p.token_endpoint.or(self.token_endpoint),
+            wyoming_server: p.wyoming_server.or(self.wyoming_server),
+            keyring: p.keyring.or(self.keyring),
+            preflight: p.preflight.or(self.preflight),
+            typer: p.typer.or(self.typer),
+            output: p.output.or(self.output),
+            paste: p.paste.or(self.paste),
+            paste_key: p.paste_key.or(self.paste_key),
+            type_delay: p.type_delay.or(self.type_delay),
+            delay: p.delay.or(self.delay),
+            enter: p.enter.or(self.enter),
+            append: p.append.or(self.append),
+            track_window: p.track_window.or(self.track_window),
+            strict_focus: p.strict_focus.or(self.strict_focus),
+            history: p.history.or(self.history),
+            clipboard: p.clipboard.or(self.clipboard),
+            primary: p.primary.or(self.primary),
+            osc52: p.osc52.or(self.osc52),
+            device: p.device.or(self.device),
+            notifications: p.notifications.or(self.notifications),
+            notifier: p.notifier.or(self.notifier),
+            runtime_dir: p.runtime_dir.or(self.runtime_dir),
+            session: p.session.or(self.session),
+            global_shortcut: p.global_shortcut.or(self.global_shortcut),
+            push_to_talk: p.push_to_talk.or(self.push_to_talk),
+            wake_word: p.wake_word.or(self.wake_word),
+            websocket: p.websocket.or(self.websocket),
+            metrics: p.metrics.or(self.metrics),
+            otlp_endpoint: p.otlp_endpoint.or(self.otlp_endpoint),
+            webhook: p.webhook.or(self.webhook),
+            mqtt: p.mqtt.or(self.mqtt),
+            exec: p.exec.or(self.exec),
+            append_file: p.append_file.or(self.append_file),
+            append_header: p.append_header.or(self.append_header),
+            journal: p.journal.or(self.journal),
+            journal_heading: p.journal_heading.or(self.journal_heading),
+            journal_template: p.journal_template.or(self.journal_template),
+            org: p.org.or(self.org),
+            org_headline: p.org_headline.or(self.org_headline),
+            nvim: p.nvim.or(self.nvim),
+            tmux: p.tmux.or(self.tmux),
+            confirm: p.confirm.or(self.confirm),
+            edit: p.edit.or(self.edit),
+            replacements: p.replacements.or(self.replacements),
+            spell: p.spell.or(self.spell),
+            voice_commands: p.voice_commands.or(self.voice_commands),
+            timings: p.timings.or(self.timings),
+            live: p.live.or(self.live),
+            live_type: p.live_type.or(self.live_type),
+            overlay: p.overlay.or(self.overlay),
+            numbers: p.numbers.or(self.numbers),
+            date_format: p.date_format.or(self.date_format),
+            case: p.case.or(self.case),
+            profanity: p.profanity.or(self.profanity),
+            summarize: p.summarize.or(self.summarize),
+            translate_to: p.translate_to.or(self.translate_to),
+            llm: p.llm.or(self.llm),
+            llm_model: p.llm_model.or(self.llm_model),
+            strip_period: p.strip_period.or(self.strip_period),
+            collapse_whitespace: p.collapse_whitespace.or(self.collapse_whitespace),
+            vocabulary: p.vocabulary.or(self.vocabulary),
+            tags: p.tags.or(self.tags),
+            keep_transcripts: p.keep_transcripts.or(self.keep_transcripts),
+            keep_audio: p.keep_audio.or(self.keep_audio),
+            vad_threshold: p.vad_threshold.or(self.vad_threshold),
+            api_timeout: p.api_timeout.or(self.api_timeout),
+            max_cost: p.max_cost.or(self.max_cost),
+            proxy: p.proxy.or(self.proxy),
+            ca_cert: p.ca_cert.or(self.ca_cert),
+            // A profile's [app] tables add to the top-level ones
+            app: self.app.into_iter().chain(p.app).collect(),
+            route: self.route.into_iter().chain(p.route).collect(),
+            profile: BTreeMap::new(),
+        })
+    }
+
+    /// The key command configured for `provider`, if any.
+    pub fn key_command(&self, provider: &str) -> Option<&str> {
+        match provider {
+            "openai" => self.openai_key_command.as_deref(),
+            "mistral" => self.mistral_key_command.as_deref(),
+            "groq" => self.groq_key_command.as_deref(),
+            _ => None,
+        }
+        .filter(|c| !c.is_empty())
+    }
+
+    /// Fill in everything `args` didn't get from the command line.
+    pub fn apply(&self, args: &mut Args, matches: &ArgMatches) {
+        let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        fill(&mut args.provider, &self.provider);
+        fill(&mut args.typer, &self.typer);
+        fill(&mut args.output, &self.output);
+        fill(&mut args.google_api_key, &self.google_api_key);
+        fill(&mut args.token_endpoint, &self.token_endpoint);
+        fill(&mut args.wyoming_server, &self.wyoming_server);
+        fill(&mut args.device, &self.device);
+        fill(&mut args.session, &self.session);
+        fill(&mut args.global_shortcut, &self.global_shortcut);
+        fill(&mut args.push_to_talk, &self.push_to_talk);
+        fill(&mut args.wake_word, &self.wake_word);
+        fill(&mut args.websocket, &self.websocket);
+        fill(&mut args.metrics, &self.metrics);
+        fill(&mut args.otlp_endpoint, &self.otlp_endpoint);
+        fill(&mut args.webhook, &self.webhook);
+        fill(&mut args.mqtt, &self.mqtt);
+        fill(&mut args.exec, &self.exec);
+        fill(&mut args.append_header, &self.append_header);
+        fill(&mut args.journal, &self.journal);
+        fill(&mut args.journal_heading, &self.journal_heading);
+        fill(&mut args.org_headline, &self.org_headline);
+        fill(&mut args.nvim, &self.nvim);
+        fill(&mut args.tmux, &self.tmux);
+        fill(&mut args.proxy, &self.proxy);
+        fill(&mut args.date_format, &self.date_format);
+        fill(&mut args.translate_to, &self.translate_to);
+        fill(&mut args.llm, &self.llm);
+        fill(&mut args.llm_model, &self.llm_model);
+        args.summarize = args.summarize.or(self.summarize);
+        if args.runtime_dir.is_none() {
+            args.runtime_dir.clone_from(&self.runtime_dir);
+        }
+        if args.append_file.is_none() {
+            args.append_file.clone_from(&self.append_file);
+        }
+        if args.journal_template.is_none() {
+            args.journal_template.clone_from(&self.journal_template);
+        }
+        if args.org.is_none() {
+            args.org.clone_from(&self.org);
+        }
+        if args.vocabulary.is_empty() {
+            args.vocabulary = self.vocabulary.clone().unwrap_or_default();
+        }
+        if args.tag.is_empty() {
+            args.tag = self.tags.clone().unwrap_or_default();
+        }
+        if args.replacements.is_none() {
+            args.replacements.clone_from(&self.replacements);
+        }
+        if args.ca_cert.is_none() {
+            args.ca_cert.clone_from(&self.ca_cert);
+        }
+        if !from_cli("language") {
+            if let Some(ref language) = self.language {
+                args.language = language.clone();
+            }
+        }
+        args.type_delay = args.type_delay.or(self.type_delay);
+        args.max_cost = args.max_cost.or(self.max_cost);
+        args.delay = args.delay.or(self.delay);
+        args.keep_transcripts = args.keep_transcripts.or(self.keep_transcripts);
+        args.keep_audio = args.keep_audio.or(self.keep_audio);
+        if !from_cli("paste_key") {
+            if let Some(key) = self.paste_key {
+                args.paste_key = key;
+            }
+        }
+        if !from_cli("numbers") {
+            if let Some(style) = self.numbers {
+                args.numbers = style;
+            }
+        }
+        if !from_cli("case") {
+            if let Some(case) = self.case {
+                args.case = case;
+            }
+        }
+        if !from_cli("profanity") {
+            if let Some(profanity) = self.profanity {
+                args.profanity = profanity;
+            }
+        }
+        if !from_cli("notifier") {
+            if let Some(kind) = self.notifier {
+                args.notifier = kind;
+            }
+        }
+        if !from_cli("vad_threshold") {
+            if let Some(threshold) = self.vad_threshold {
+                args.vad_threshold = threshold;
+            }
+        }
+        if !from_cli("api_timeout") {
+            if let Some(timeout) = self.api_timeout {
+                args.api_timeout = timeout;
+            }
+        }
+        for (flag, value) in [
+            (&mut args.paste, self.paste),
+            (&mut args.enter, self.enter),
+            (&mut args.append, self.append),
+            (&mut args.track_window, self.track_window),
+            (&mut args.strict_focus, self.strict_focus),
+            (&mut args.history, self.history),
+            (&mut args.clipboard, self.clipboard),
+            (&mut args.primary, self.primary),
+            (&mut args.osc52, self.osc52),
+            (&mut args.confirm, self.confirm),
+            (&mut args.edit, self.edit),
+            (&mut args.keyring, self.keyring),
+            (&mut args.preflight, self.preflight),
+            (&mut args.spell, self.spell),
+            (&mut args.voice_commands, self.voice_commands),
+            (&mut args.timings, self.timings),
+            (&mut args.live, self.live),
+            (&mut args.live_type, self.live_type),
+            (&mut args.overlay, self.overlay),
+            (&mut args.strip_period, self.strip_period),
+            (&mut args.collapse_whitespace, self.collapse_whitespace),
+        ] {
+            if !*flag {
+                *flag = value.unwrap_or(false);
+            }
+        }
+        if self.notifications == Some(false) {
+            args.no_notifications = true;
+        }
+    }
+}
+
+fn fill(arg: &mut Option<String>, value: &Option<String>) {
+    if arg.is_none() {
+        arg.clone_from(value);
+    }
+}
+
+/// A length of time written as for --duration, e.g. "7d" or "12h"
+fn duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Duration>, D::Error> {
+    let Some(text) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    crate::parse_duration(&text)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// $XDG_CONFIG_HOME/rpdictation/config.toml, or ~/.config/rpdictation/config.toml
+pub fn default_path() -> PathBuf {
+    let config = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME").unwrap_or_default()).join(".config"),
+    };
+    config.join("rpdictation").join("config.toml")
+}
+
+/// Load `path`, or the default location when `None`. A missing default file
+/// is not an error; a missing explicit one is.
+pub fn load(path: Option<&Path>) -> Result<Config> {
+    let (path, explicit) = match path {
+        Some(p) => (p.to_path_buf(), true),
+        None => (default_path(), false),
+    };
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && !explicit => {
+            return Ok(Config::default())
+        }
+        Err(e) => anyhow::bail!("Failed to read {}: {}", path.display(), e),
+    };
+    toml::from_str(&text)
+        .map_err(|e| anyhow::anyhow!("Invalid config file {}: {}", path.display(), e))
+}
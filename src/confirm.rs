@@ -0,0 +1,215 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::io::BufRead;
+use tokio_util::sync::CancellationToken;
+
+use crate::{notifier, output};
+
+/// Where --confirm asks whether to output a transcript, and --max-cost
+/// whether to send an expensive recording
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prompt {
+    /// A question on the terminal rpdictation runs in
+    Terminal,
+    /// A notification with Accept and Reject buttons, and Edit when $VISUAL
+    /// names an editor that doesn't need a terminal
+    Notification,
+}
+
+/// Shows each finished transcript and waits for the user to accept it,
+/// reject it, or fix it in an editor before anything is output.
+pub struct Confirm {
+    prompt: Prompt,
+    /// Open the text in the editor right away (--edit)
+    edit_first: bool,
+    /// Ask whether to output it (--confirm); otherwise whatever the editor
+    /// saved goes out
+    ask: bool,
+}
+
+/// Questions from transcriptions finishing together are asked one at a
+/// time
+static TURN: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+impl Confirm {
+    pub fn new(prompt: Prompt, edit_first: bool, ask: bool) -> Self {
+        Self {
+            prompt,
+            edit_first,
+            ask,
+        }
+    }
+
+    /// The text to output, edited or not, or `None` when the user rejected
+    /// it. Emptying the text in the editor rejects it too.
+    pub async fn ask(&self, text: &str) -> Result<Option<String>> {
+        let _turn = TURN.lock().await;
+        // The session has just printed it, unless progress output is off
+        let mut shown = output::mode() == output::Mode::Human;
+        let mut text = text.to_string();
+        if self.edit_first {
+            text = edit(&text).await?;
+            if text.is_empty() {
+                return Ok(None);
+            }
+            shown = false;
+        }
+        if !self.ask {
+            return Ok(Some(text));
+        }
+        match self.prompt {
+            Prompt::Terminal => ask_terminal(text, shown).await,
+            Prompt::Notification => ask_notification(text).await,
+        }
+    }
+}
+
+/// --max-cost: recordings whose transcription is estimated to cost more
+/// than `max` dollars are only sent once the user agrees
+pub struct CostLimit {
+    pub max: f64,
+    /// Where to ask; with nowhere to ask, such recordings are dropped
+    pub prompt: Option<Prompt>,
+}
+
+impl CostLimit {
+    /// Whether to go ahead with a transcription estimated at `cost` dollars
+    pub async fn approve(&self, cost: f64, minutes: f64) -> Result<bool> {
+        if cost <= self.max {
+            return Ok(true);
+        }
+        let question = format!(
+            "{:.1} minutes of audio, about ${:.2} to transcribe (limit ${:.2})",
+            minutes, cost, self.max
+        );
+        let Some(prompt) = self.prompt else {
+            eprintln!("{}; nowhere to ask, dropping it", question);
+            return Ok(false);
+        };
+        let _turn = TURN.lock().await;
+        match prompt {
+            Prompt::Terminal => loop {
+                eprint!("\n{}. Transcribe it? [y]es, [n]o: ", question);
+                let Some(answer) = read_line().await? else {
+                    return Ok(false);
+                };
+                match answer.trim().to_lowercase().as_str() {
+                    "y" | "yes" => return Ok(true),
+                    "n" | "no" => return Ok(false),
+                    _ => {}
+                }
+            },
+            Prompt::Notification => {
+                let picked = notifier::get()
+                    .ask(
+                        "Transcribe this recording?",
+                        Some(&question),
+                        &[("transcribe", "Transcribe"), ("discard", "Discard")],
+                        CancellationToken::new(),
+                    )
+                    .await;
+                Ok(picked.as_deref() == Some("transcribe"))
+            }
+        }
+    }
+}
+
+async fn ask_terminal(mut text: String, mut shown: bool) -> Result<Option<String>> {
+    loop {
+        // On stderr, so the question reaches the terminal even when stdout
+        // is piped
+        if !shown {
+            eprintln!("\n{}", text);
+            shown = true;
+        }
+        eprint!("\nOutput this transcription? [y]es, [n]o, [e]dit: ");
+        let Some(answer) = read_line().await? else {
+            // Nobody left to answer
+            return Ok(None);
+        };
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => return Ok(Some(text)),
+            "n" | "no" => return Ok(None),
+            "e" | "edit" => {
+                text = edit(&text).await?;
+                shown = false;
+            }
+            _ => {}
+        }
+    }
+}
+
+async fn ask_notification(mut text: String) -> Result<Option<String>> {
+    let mut actions = vec![("accept", "Accept"), ("reject", "Reject")];
+    // Without a terminal, only an editor that opens its own window will do
+    if env::var_os("VISUAL").is_some_and(|v| !v.is_empty()) {
+        actions.push(("edit", "Edit"));
+    }
+    loop {
+        let picked = notifier::get()
+            .ask(
+                "Output this transcription?",
+                Some(&text),
+                &actions,
+                CancellationToken::new(),
+            )
+            .await;
+        // Dismissing the notification counts as rejecting
+        match picked.as_deref() {
+            Some("accept") => return Ok(Some(text)),
+            Some("edit") => text = edit(&text).await?,
+            _ => return Ok(None),
+        }
+    }
+}
+
+/// Open `text` in the user's editor ($VISUAL or $EDITOR) and return what
+/// was saved, without the line break editors add at the end. Without an
+/// editor, the text is shown on the terminal to be typed over.
+pub async fn edit(text: &str) -> Result<String> {
+    let Some(editor) = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|e| !e.is_empty())
+    else {
+        eprintln!("\n{}", text);
+        eprint!("\nCorrected text (Enter keeps it as is): ");
+        return Ok(match read_line().await? {
+            Some(line) if !line.trim().is_empty() => line.trim().to_string(),
+            _ => text.to_string(),
+        });
+    };
+    let path = crate::get_runtime_dir().join(format!("edit-{}.txt", uuid::Uuid::new_v4()));
+    tokio::fs::write(&path, format!("{}\n", text))
+        .await
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    // Through the shell, as the variable may carry arguments ("code --wait")
+    let status = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&path)
+        .status()
+        .await;
+    let edited = tokio::fs::read_to_string(&path).await;
+    let _ = tokio::fs::remove_file(&path).await;
+    let status = status.with_context(|| format!("Failed to run {}", editor))?;
+    if !status.success() {
+        anyhow::bail!("{} exited with {}", editor, status);
+    }
+    Ok(edited?.trim_end().to_string())
+}
+
+/// A line typed on the terminal, or `None` at EOF
+async fn read_line() -> Result<Option<String>> {
+    let (n, line) = tokio::task::spawn_blocking(|| {
+        let mut line = String::new();
+        std::io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .map(|n| (n, line))
+    })
+    .await
+    .context("Reading from the terminal panicked")??;
+    Ok((n > 0).then_some(line))
+}
@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use std::time::SystemTime;
+
+/// A way to send control commands (`start`, `stop`, `toggle`, `cancel`,
+/// `status`) to whatever is recording, so the subcommands behave the same
+/// whether that is the daemon or a one-shot recording process.
+///
+/// Replies use the daemon's vocabulary: `recording`, `stopped`, `cancelled`,
+/// and for `status` either `recording <seconds>` or `idle`.
+#[async_trait]
+pub trait ControlChannel: Send + Sync {
+    fn name(&self) -> &str;
+    async fn send(&self, command: &str) -> Result<String>;
+}
+
+/// The daemon's Unix socket
+pub struct DaemonSocket;
+
+#[async_trait]
+impl ControlChannel for DaemonSocket {
+    fn name(&self) -> &str {
+        "Daemon"
+    }
+
+    async fn send(&self, command: &str) -> Result<String> {
+        crate::daemon::request(command)
+            .await
+            .context("Daemon is no longer running")?
+    }
+}
+
+/// A one-shot recording process, reached through its PID file: SIGUSR1
+/// stops it and SIGUSR2 cancels it.
+pub struct RecordingProcess {
+    pid: i32,
+}
+
+impl RecordingProcess {
+    fn signal(&self, sig: Signal) -> Result<()> {
+        kill(Pid::from_raw(self.pid), sig).context("Failed to send signal")
+    }
+}
+
+#[async_trait]
+impl ControlChannel for RecordingProcess {
+    fn name(&self) -> &str {
+        "Recording process"
+    }
+
+    async fn send(&self, command: &str) -> Result<String> {
+        match command {
+            "stop" | "toggle" => {
+                self.signal(Signal::SIGUSR1)?;
+                Ok("stopped".to_string())
+            }
+            "cancel" => {
+                self.signal(Signal::SIGUSR2)?;
+                Ok("cancelled".to_string())
+            }
+            "start" => anyhow::bail!("Already running (pid {})", self.pid),
+            // The PID file is written when recording starts, so its age is
+            // the elapsed time
+            "status" => {
+                let elapsed = tokio::fs::metadata(rpdictation::get_pid_path())
+                    .await
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|t| SystemTime::now().duration_since(t).ok())
+                    .unwrap_or_default();
+                Ok(format!("recording {}", elapsed.as_secs()))
+            }
+            other => anyhow::bail!("Unknown command '{}'", other),
+        }
+    }
+}
+
+/// The channel to whatever is recording, preferring a running daemon, or
+/// `None` when nothing is.
+pub async fn connect() -> Option<Box<dyn ControlChannel>> {
+    if tokio::net::UnixStream::connect(crate::daemon::socket_path())
+        .await
+        .is_ok()
+    {
+        return Some(Box::new(DaemonSocket));
+    }
+    let pid = crate::is_instance_running().await?;
+    Some(Box::new(RecordingProcess { pid }))
+}
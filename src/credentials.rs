@@ -0,0 +1,138 @@
+use anyhow::{Context, Result};
+
+#[cfg(feature = "http")]
+const TOKEN_ENDPOINT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Where a provider's API key comes from.
+#[derive(Clone)]
+pub enum ApiKey {
+    /// Long-lived key from the command line, environment, or config file
+    Static(String),
+    /// Printed by a shell command (e.g. a password manager) right before each
+    /// request
+    Command(String),
+    /// Stored in the Secret Service keyring (GNOME Keyring, KWallet,
+    /// KeePassXC), looked up right before each request
+    Keyring { provider: String },
+    /// Short-lived key fetched from a token endpoint right before each request
+    TokenEndpoint { url: String, provider: String },
+}
+
+impl ApiKey {
+    /// Return the key to put in the request, fetching it if necessary.
+    pub async fn resolve(&self) -> Result<String> {
+        match self {
+            ApiKey::Static(key) => Ok(key.clone()),
+            ApiKey::Command(command) => run_key_command(command).await,
+            ApiKey::Keyring { provider } => keyring_lookup(provider).await,
+            #[cfg(feature = "http")]
+            ApiKey::TokenEndpoint { url, provider } => fetch_token(url, provider).await,
+            #[cfg(not(feature = "http"))]
+            ApiKey::TokenEndpoint { .. } => {
+                anyhow::bail!("Token endpoints need rpdictation built with the http feature")
+            }
+        }
+    }
+}
+
+/// Run `command` through the shell and use the first line of its output.
+async fn run_key_command(command: &str) -> Result<String> {
+    let output = tokio::process::Command::new("sh")
+        .args(["-c", command])
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .await
+        .with_context(|| format!("Failed to run key command '{}'", command))?;
+    if !output.status.success() {
+        anyhow::bail!("Key command '{}' failed ({})", command, output.status);
+    }
+    let key = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    if key.is_empty() {
+        anyhow::bail!("Key command '{}' printed nothing", command);
+    }
+    Ok(key)
+}
+
+/// secret-tool attributes of `provider`'s keyring entry
+fn keyring_attributes(provider: &str) -> [&str; 4] {
+    ["service", "rpdictation", "provider", provider]
+}
+
+/// Whether the keyring holds a key for `provider`. Blocks for as long as
+/// secret-tool takes, which is meant for startup only.
+pub fn keyring_has(provider: &str) -> bool {
+    std::process::Command::new("secret-tool")
+        .arg("lookup")
+        .args(keyring_attributes(provider))
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// Look `provider`'s key up in the keyring through secret-tool (libsecret).
+async fn keyring_lookup(provider: &str) -> Result<String> {
+    let output = tokio::process::Command::new("secret-tool")
+        .arg("lookup")
+        .args(keyring_attributes(provider))
+        .output()
+        .await
+        .context("Failed to run secret-tool")?;
+    let key = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || key.is_empty() {
+        anyhow::bail!(
+            "No {} key in the keyring; store one with: secret-tool store --label='rpdictation {}' {}",
+            provider,
+            provider,
+            keyring_attributes(provider).join(" ")
+        );
+    }
+    Ok(key)
+}
+
+/// Ask the token endpoint for a short-lived key for `provider`.
+///
+/// The endpoint gets `provider=<name>` as a query parameter and may answer
+/// either with a JSON object carrying an `api_key` (or `token`) field, or
+/// with the bare key as plain text.
+#[cfg(feature = "http")]
+async fn fetch_token(url: &str, provider: &str) -> Result<String> {
+    let client = crate::providers::http_client();
+    let response = client
+        .get(url)
+        .query(&[("provider", provider)])
+        .timeout(TOKEN_ENDPOINT_TIMEOUT)
+        .send()
+        .await
+        .context("Failed to reach token endpoint")?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        anyhow::bail!("Token endpoint error ({}): {}", status, error_text);
+    }
+
+    let body = response
+        .text()
+        .await
+        .context("Failed to read token endpoint response")?;
+
+    let key = match serde_json::from_str::<serde_json::Value>(&body) {
+        Ok(json) if json.is_object() => json["api_key"]
+            .as_str()
+            .or_else(|| json["token"].as_str())
+            .context("Token endpoint response has no api_key or token field")?
+            .to_string(),
+        _ => body.trim().to_string(),
+    };
+
+    if key.is_empty() {
+        anyhow::bail!("Token endpoint returned an empty key for {}", provider);
+    }
+    Ok(key)
+}
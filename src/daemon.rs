@@ -0,0 +1,432 @@
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::Args;
+use rpdictation::session::State;
+use rpdictation::typing_queue::Ticket;
+use rpdictation::{record, setup};
+
+/// A control command and where to send the one-line reply
+pub type Request = (String, oneshot::Sender<std::result::Result<String, String>>);
+
+/// What the recording currently in progress holds on to; the session
+/// tracks the rest
+struct Dictation {
+    ticket: Option<Ticket>,
+    timer: CancellationToken,
+    /// The --live preview being transcribed, if any
+    preview: Option<JoinHandle<()>>,
+}
+
+impl Dictation {
+    /// Stop the timer and any preview, which would only be outdated now
+    fn end(&mut self) {
+        self.timer.cancel();
+        if let Some(preview) = self.preview.take() {
+            preview.abort();
+        }
+    }
+}
+
+pub fn socket_path() -> PathBuf {
+    rpdictation::get_runtime_dir().join("daemon.sock")
+}
+
+/// Send `command` to a running daemon and return its reply without the
+/// `ok ` prefix. Returns `None` when no daemon is listening.
+pub async fn request(command: &str) -> Option<Result<String>> {
+    let stream = UnixStream::connect(socket_path()).await.ok()?;
+    Some(
+        async move {
+            let (read, mut write) = stream.into_split();
+            write
+                .write_all(format!("{}\n", command).as_bytes())
+                .await
+                .context("Failed to send command to daemon")?;
+            let mut reply = String::new();
+            BufReader::new(read)
+                .read_line(&mut reply)
+                .await
+                .context("Failed to read reply from daemon")?;
+            let reply = reply.trim();
+            if let Some(msg) = reply.strip_prefix("error ") {
+                if msg == rpdictation::Error::NotRecording.to_string() {
+                    return Err(rpdictation::Error::NotRecording.into());
+                }
+                anyhow::bail!("{}", msg);
+            }
+            Ok(reply.strip_prefix("ok ").unwrap_or(reply).to_string())
+        }
+        .await,
+    )
+}
+
+/// Run the daemon: keep the input stream open and the provider clients warm,
+/// and turn start/stop/toggle/cancel commands from the socket into
+/// dictations.
+pub async fn run(args: Args) -> Result<()> {
+    let path = socket_path();
+    if UnixStream::connect(&path).await.is_ok() {
+        anyhow::bail!("Daemon already running ({})", path.display());
+    }
+    // Nobody is listening, so whatever is left there is stale
+    let _ = tokio::fs::remove_file(&path).await;
+
+    if args.preflight {
+        crate::check::run(&args).await?;
+    }
+    let options = args.options();
+    let session = Arc::new(setup::build_session(&options)?);
+    setup::spawn_overlay(&options, &session);
+    crate::spawn_prune(&args);
+    let mut typing_queue = setup::build_typing_queue(&options).await?;
+    let recorder = rpdictation::audio::Recorder::open(args.device.as_deref())?;
+    let args = Arc::new(args);
+
+    let listener =
+        UnixListener::bind(&path).with_context(|| format!("Failed to bind {}", path.display()))?;
+    let (request_tx, mut request_rx) = mpsc::channel::<Request>(8);
+    tokio::spawn(accept_loop(listener, request_tx.clone()));
+
+    #[cfg(feature = "dbus")]
+    serve_dbus(&args, request_tx.clone(), &session).await;
+    #[cfg(not(feature = "dbus"))]
+    if args.global_shortcut.is_some() {
+        eprintln!("Warning: Global shortcut needs rpdictation built with the dbus feature");
+    }
+
+    #[cfg(feature = "push-to-talk")]
+    if let Some(key) = args.push_to_talk.clone() {
+        let request_tx = request_tx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = crate::push_to_talk::run(key, request_tx).await {
+                eprintln!("Warning: Push-to-talk unavailable: {:#}", e);
+            }
+        });
+    }
+
+    #[cfg(not(feature = "push-to-talk"))]
+    if args.push_to_talk.is_some() {
+        eprintln!("Warning: Push-to-talk needs rpdictation built with the push-to-talk feature");
+    }
+
+    #[cfg(feature = "websocket")]
+    if let Some(ref addr) = args.websocket {
+        let listener = crate::websocket::bind(addr).await?;
+        tokio::spawn(crate::websocket::serve(listener, Arc::clone(&session)));
+        say!("WebSocket events on ws://{}", addr);
+    }
+
+    #[cfg(not(feature = "websocket"))]
+    if args.websocket.is_some() {
+        eprintln!("Warning: --websocket needs rpdictation built with the websocket feature");
+    }
+
+    if let Some(ref addr) = args.metrics {
+        let listener = crate::prometheus::bind(addr).await?;
+        tokio::spawn(crate::prometheus::serve(listener));
+        say!("Metrics on http://{}/metrics", addr);
+    }
+
+    if let Some(dir) = args.watch_dir.clone() {
+        tokio::spawn(crate::watch::run(dir, Arc::clone(&session)));
+    }
+
+    // Listening for the wake word means capturing while idle, too
+    let mut wake = crate::wake::WakeWord::open(&args)?;
+    if let Some(ref phrase) = args.wake_word {
+        recorder.start();
+        say!("Listening for \"{}\"", phrase);
+    }
+    let mut wake_tick = tokio::time::interval(record::VAD_INTERVAL);
+
+    let mut sig_stop =
+        signal(SignalKind::user_defined1()).context("Failed to create signal handler")?;
+    let mut sig_cancel =
+        signal(SignalKind::user_defined2()).context("Failed to create signal handler")?;
+    let mut sig_term =
+        signal(SignalKind::terminate()).context("Failed to create signal handler")?;
+    let mut sig_int = signal(SignalKind::interrupt()).context("Failed to create signal handler")?;
+
+    say!("Daemon listening on {}", path.display());
+
+    // With --live, what has been said so far is transcribed every few
+    // seconds and reported as Event::Partial; one request at a time
+    let mut live_tick = tokio::time::interval(record::LIVE_INTERVAL);
+
+    let mut current: Option<Dictation> = None;
+    loop {
+        let (command, reply) = tokio::select! {
+            Some((command, reply)) = request_rx.recv() => (command, Some(reply)),
+            _ = live_tick.tick(), if args.live && current.is_some() => {
+                if let (Some(d), State::Recording { id, .. }) = (current.as_mut(), session.state()) {
+                    if d.preview.as_ref().is_none_or(|p| p.is_finished()) {
+                        let session = Arc::clone(&session);
+                        let samples = recorder.snapshot();
+                        d.preview = Some(tokio::spawn(async move {
+                            let preview = session.preview(id, samples);
+                            if let Err(e) = rpdictation::output::silenced(preview).await {
+                                eprintln!("[{}] Live preview failed: {:#}", id, e);
+                            }
+                        }));
+                    }
+                }
+                continue;
+            }
+            _ = wake_tick.tick(), if wake.is_some() && current.is_none() => {
+                if let Some(ref mut wake) = wake {
+                    wake.push(&recorder.drain(), &request_tx);
+                }
+                continue;
+            }
+            _ = sig_stop.recv() => ("stop".to_string(), None),
+            _ = sig_cancel.recv() => ("cancel".to_string(), None),
+            _ = sig_term.recv() => break,
+            _ = sig_int.recv() => break,
+        };
+
+        // Only `lang` takes an argument
+        let (command, argument) = match command.split_once(' ') {
+            Some((command, argument)) => (command, Some(argument.trim())),
+            None => (command.as_str(), None),
+        };
+        let command = match command {
+            "toggle" if matches!(session.state(), State::Recording { .. }) => "stop",
+            "toggle" => "start",
+            other => other,
+        };
+
+        let result = match command {
+            "start" => {
+                // Commands arriving meanwhile wait for it
+                if !matches!(session.state(), State::Recording { .. }) {
+                    record::countdown(&options).await;
+                }
+                let utterance_id = uuid::Uuid::new_v4();
+                if let Err(e) = session.start(utterance_id) {
+                    Err(e.to_string())
+                } else {
+                    let focus = setup::capture_focus(&options).await;
+                    let style = setup::app_style(&options).await;
+                    let ticket = typing_queue.as_mut().map(|q| q.reserve(focus, style));
+                    eprintln!("Utterance ID: {}", utterance_id);
+                    if let Some(ref mut wake) = wake {
+                        wake.reset();
+                    }
+                    recorder.start();
+                    let timer = CancellationToken::new();
+                    // No live preview here; the sender is gone at once
+                    record::spawn_timer(
+                        timer.clone(),
+                        tokio::sync::watch::channel(String::new()).1,
+                    );
+                    // Buttons on the notification come back as commands
+                    match record::spawn_recording_notification(timer.clone()) {
+                        Ok((_, action)) => {
+                            let request_tx = request_tx.clone();
+                            tokio::spawn(async move {
+                                let Ok(action) = action.await else {
+                                    return;
+                                };
+                                let command = match action {
+                                    record::StopAction::Stop => "stop",
+                                    record::StopAction::Cancel => "cancel",
+                                };
+                                let (reply_tx, _) = oneshot::channel();
+                                let _ = request_tx.send((command.to_string(), reply_tx)).await;
+                            });
+                        }
+                        Err(e) => eprintln!("Warning: {:#}", e),
+                    }
+                    current = Some(Dictation {
+                        ticket,
+                        timer,
+                        preview: None,
+                    });
+                    Ok("recording".to_string())
+                }
+            }
+            "stop" => match (session.stop(), current.take()) {
+                (Ok(utterance_id), Some(mut d)) => {
+                    d.end();
+                    let samples = recorder.stop();
+                    if wake.is_some() {
+                        recorder.start();
+                    }
+                    eprintln!("[{}] Stop", utterance_id);
+                    let session = Arc::clone(&session);
+                    tokio::spawn(async move {
+                        if let Err(e) = session.process(samples, d.ticket, utterance_id).await {
+                            eprintln!("Error: {}", e);
+                        }
+                    });
+                    Ok("stopped".to_string())
+                }
+                _ => Err("No recording in progress".to_string()),
+            },
+            "cancel" => match (session.cancel(), current.take()) {
+                (Ok(utterance_id), Some(mut d)) => {
+                    d.end();
+                    recorder.stop();
+                    if wake.is_some() {
+                        recorder.start();
+                    }
+                    eprintln!("[{}] Cancel", utterance_id);
+                    say!();
+                    say!("Recording cancelled, discarding audio.");
+                    rpdictation::notifier::send_notification("Recording cancelled", true).await;
+                    Ok("cancelled".to_string())
+                }
+                // Not recording: stop what is still being transcribed or
+                // typed instead
+                _ => match session.cancel_processing() {
+                    Some(_) => Ok("cancelled".to_string()),
+                    None => Err("No recording in progress".to_string()),
+                },
+            },
+            "status" => Ok(match session.state() {
+                State::Recording { since, .. } => {
+                    format!("recording {}", since.elapsed().as_secs())
+                }
+                State::Idle => "idle".to_string(),
+            }),
+            "lang" => match argument {
+                Some(language) if !language.is_empty() => {
+                    session.set_language(language);
+                    say!("Language: {}", language);
+                    rpdictation::notifier::send_notification(
+                        &format!("Language: {}", language),
+                        true,
+                    )
+                    .await;
+                    Ok(language.to_string())
+                }
+                _ => Ok(session.language()),
+            },
+            other => Err(format!("Unknown command '{}'", other)),
+        };
+
+        match reply {
+            Some(reply) => {
+                let _ = reply.send(result);
+            }
+            None => {
+                if let Err(e) = result {
+                    eprintln!("{}", e);
+                }
+            }
+        }
+    }
+
+    eprintln!("daemon exit");
+    let _ = tokio::fs::remove_file(&path).await;
+    Ok(())
+}
+
+/// Register the D-Bus service, bind the global shortcut, and send each
+/// finished dictation out as a D-Bus signal.
+#[cfg(feature = "dbus")]
+async fn serve_dbus(
+    args: &Args,
+    request_tx: mpsc::Sender<Request>,
+    session: &rpdictation::DictationSession,
+) {
+    // The D-Bus service is a convenience; without a session bus the socket
+    // still works.
+    let name = crate::dbus::bus_name(args.session.as_deref());
+    let dbus = match crate::dbus::serve(request_tx.clone(), &name).await {
+        Ok(conn) => {
+            say!("D-Bus service {} registered", name);
+            Some(conn)
+        }
+        Err(e) => {
+            eprintln!("Warning: D-Bus service unavailable: {:#}", e);
+            None
+        }
+    };
+
+    if let Some(trigger) = args.global_shortcut.clone() {
+        match dbus.clone() {
+            Some(conn) => {
+                let request_tx = request_tx.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = crate::shortcut::run(conn, trigger, request_tx).await {
+                        eprintln!("Warning: Global shortcut unavailable: {:#}", e);
+                    }
+                });
+            }
+            None => eprintln!("Warning: Global shortcut needs the D-Bus session bus"),
+        }
+    }
+
+    // Every finished dictation, recorded or from the watch folder, goes out
+    // as a D-Bus signal
+    if let Some(conn) = dbus {
+        tokio::spawn(emit_transcripts(conn, session.subscribe()));
+    }
+}
+
+#[cfg(feature = "dbus")]
+async fn emit_transcripts(
+    conn: zbus::Connection,
+    mut events: tokio::sync::broadcast::Receiver<rpdictation::session::Event>,
+) {
+    use rpdictation::session::Event;
+    use tokio::sync::broadcast;
+
+    loop {
+        match events.recv().await {
+            Ok(Event::Completed { id, text, .. }) => {
+                if let Err(e) = crate::dbus::emit_transcript(&conn, id, &text).await {
+                    eprintln!("Warning: Failed to emit D-Bus signal: {}", e);
+                }
+            }
+            Ok(_) => {}
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                eprintln!("Warning: {} session events dropped", n);
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+async fn accept_loop(listener: UnixListener, request_tx: mpsc::Sender<Request>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                eprintln!("Failed to accept control connection: {}", e);
+                continue;
+            }
+        };
+        let request_tx = request_tx.clone();
+        tokio::spawn(async move {
+            let (read, mut write) = stream.into_split();
+            let mut line = String::new();
+            if BufReader::new(read).read_line(&mut line).await.is_err() {
+                return;
+            }
+            let (reply_tx, reply_rx) = oneshot::channel();
+            if request_tx
+                .send((line.trim().to_string(), reply_tx))
+                .await
+                .is_err()
+            {
+                return;
+            }
+            let reply = match reply_rx.await {
+                Ok(Ok(msg)) => format!("ok {}\n", msg),
+                Ok(Err(msg)) => format!("error {}\n", msg),
+                Err(_) => return,
+            };
+            let _ = write.write_all(reply.as_bytes()).await;
+        });
+    }
+}
@@ -0,0 +1,101 @@
+use anyhow::{Context, Result};
+use tokio::sync::{mpsc, oneshot};
+use zbus::object_server::SignalEmitter;
+
+use crate::daemon::Request;
+
+pub const BUS_NAME: &str = "org.rpdictation.Dictation";
+pub const OBJECT_PATH: &str = "/org/rpdictation/Dictation";
+
+/// `org.rpdictation.Dictation` on the session bus. Method calls are handed to
+/// the daemon loop exactly like commands arriving on the control socket.
+struct DictationService {
+    requests: mpsc::Sender<Request>,
+}
+
+impl DictationService {
+    async fn request(&self, command: &str) -> zbus::fdo::Result<String> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.requests
+            .send((command.to_string(), reply_tx))
+            .await
+            .map_err(|_| zbus::fdo::Error::Failed("Daemon is shutting down".to_string()))?;
+        reply_rx
+            .await
+            .map_err(|_| zbus::fdo::Error::Failed("Daemon is shutting down".to_string()))?
+            .map_err(zbus::fdo::Error::Failed)
+    }
+}
+
+#[zbus::interface(name = "org.rpdictation.Dictation")]
+impl DictationService {
+    async fn start(&self) -> zbus::fdo::Result<String> {
+        self.request("start").await
+    }
+
+    async fn stop(&self) -> zbus::fdo::Result<String> {
+        self.request("stop").await
+    }
+
+    async fn toggle(&self) -> zbus::fdo::Result<String> {
+        self.request("toggle").await
+    }
+
+    async fn cancel(&self) -> zbus::fdo::Result<String> {
+        self.request("cancel").await
+    }
+
+    /// Transcribe `language` (e.g. "cs") from now on
+    async fn set_language(&self, language: &str) -> zbus::fdo::Result<String> {
+        self.request(&format!("lang {}", language)).await
+    }
+
+    async fn language(&self) -> zbus::fdo::Result<String> {
+        self.request("lang").await
+    }
+
+    /// Emitted with the final text of every successful dictation
+    #[zbus(signal)]
+    async fn transcript(
+        emitter: &SignalEmitter<'_>,
+        utterance_id: &str,
+        text: &str,
+    ) -> zbus::Result<()>;
+}
+
+/// The bus name of the daemon of `session`: [`BUS_NAME`], with the session
+/// name as the last element for a named one, e.g.
+/// `org.rpdictation.Dictation.work`
+pub fn bus_name(session: Option<&str>) -> String {
+    let Some(name) = session else {
+        return BUS_NAME.to_string();
+    };
+    // Bus name elements can't start with a digit, and '-' is discouraged
+    let prefix = if name.starts_with(|c: char| c.is_ascii_digit()) {
+        "_"
+    } else {
+        ""
+    };
+    format!("{}.{}{}", BUS_NAME, prefix, name.replace('-', "_"))
+}
+
+/// Claim `name` on the session bus and serve the interface.
+pub async fn serve(requests: mpsc::Sender<Request>, name: &str) -> Result<zbus::Connection> {
+    zbus::connection::Builder::session()?
+        .name(name)?
+        .serve_at(OBJECT_PATH, DictationService { requests })?
+        .build()
+        .await
+        .context("Failed to register D-Bus service")
+}
+
+/// Broadcast the final transcript of a dictation.
+pub async fn emit_transcript(
+    conn: &zbus::Connection,
+    utterance_id: uuid::Uuid,
+    text: &str,
+) -> Result<()> {
+    let emitter = SignalEmitter::new(conn, OBJECT_PATH)?;
+    DictationService::transcript(&emitter, &utterance_id.to_string(), text).await?;
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use thiserror::Error;
+
+/// Failures a script wrapping rpdictation may want to tell apart. Each maps
+/// to its own process exit code; anything else exits with 1.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("No input device: {0}")]
+    NoInputDevice(String),
+    #[error("{provider} rejected the API key: {message}")]
+    ApiAuth { provider: String, message: String },
+    #[error("{provider} quota or rate limit exceeded: {message}")]
+    ApiQuota { provider: String, message: String },
+    #[cfg(feature = "http")]
+    #[error("Failed to send request to {provider} API")]
+    Network {
+        provider: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("Output {sink} failed: {error:#}")]
+    OutputFailed { sink: String, error: anyhow::Error },
+    #[error("No recording in progress")]
+    NotRecording,
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::NoInputDevice(_) => 3,
+            Error::ApiAuth { .. } => 4,
+            Error::ApiQuota { .. } => 5,
+            #[cfg(feature = "http")]
+            Error::Network { .. } => 6,
+            Error::OutputFailed { .. } => 7,
+            Error::NotRecording => 8,
+        }
+    }
+}
+
+/// Exit code for `err`: that of the [`Error`] anywhere in its chain, or 1
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    err.downcast_ref::<Error>().map_or(1, Error::exit_code)
+}
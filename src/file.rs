@@ -0,0 +1,54 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::path::{Path, PathBuf};
+
+use crate::Args;
+use rpdictation::subtitles::{self, Format};
+
+/// How `rpdictation file` gives the transcript
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileFormat {
+    /// The text, handed to the outputs like a dictation
+    Text,
+    /// Paragraphs marked with the time they start, `[MM:SS]`, on stdout
+    Timestamped,
+}
+
+/// Transcribe the audio file at `path`. With `srt` or `vtt`, write
+/// subtitles there; with the timestamped format, print the paragraphs.
+/// Either replaces handing the text to the outputs.
+pub async fn run(
+    mut args: Args,
+    path: &Path,
+    format: FileFormat,
+    srt: Option<PathBuf>,
+    vtt: Option<PathBuf>,
+) -> Result<()> {
+    // Typing the transcript into whatever window has the focus is hardly
+    // what anyone transcribing a file wants
+    if args.output.is_none() {
+        args.output = Some(String::new());
+    }
+    let session = rpdictation::setup::build_session(&args.options())?;
+    let samples = rpdictation::audio::decode_file(path)
+        .await
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    if format == FileFormat::Text && srt.is_none() && vtt.is_none() {
+        session.process(samples, None, uuid::Uuid::new_v4()).await?;
+        return Ok(());
+    }
+
+    let segments = session.segments(samples).await?;
+    for (out, format) in [(srt, Format::Srt), (vtt, Format::Vtt)] {
+        let Some(out) = out else { continue };
+        tokio::fs::write(&out, subtitles::render(&segments, format))
+            .await
+            .with_context(|| format!("Failed to write {}", out.display()))?;
+        say!("Subtitles written to {}", out.display());
+    }
+    if format == FileFormat::Timestamped {
+        print!("{}", subtitles::timestamped(&segments));
+    }
+    Ok(())
+}
@@ -10,6 +10,10 @@ pub trait FocusProvider: Send + Sync {
     /// Get the currently focused window ID
     async fn get_focused_window(&self) -> Result<Option<WindowId>>;
 
+    /// Get the application ID (Wayland app-id, or X11 class under
+    /// Xwayland) of the currently focused window
+    async fn get_focused_app(&self) -> Result<Option<String>>;
+
     /// Set focus to a specific window
     async fn set_focused_window(&self, window_id: &WindowId) -> Result<bool>;
 
@@ -23,11 +23,9 @@ impl NiriFocusProvider {
             None
         }
     }
-}
 
-#[async_trait]
-impl FocusProvider for NiriFocusProvider {
-    async fn get_focused_window(&self) -> Result<Option<WindowId>> {
+    /// The focused window as niri describes it, if there is one
+    async fn focused_window(&self) -> Result<Option<serde_json::Value>> {
         let output = tokio::process::Command::new("niri")
             .args(["msg", "-j", "focused-window"])
             .output()
@@ -42,6 +40,16 @@ impl FocusProvider for NiriFocusProvider {
         let stdout = String::from_utf8_lossy(&output.stdout);
         let json: serde_json::Value =
             serde_json::from_str(&stdout).context("Failed to parse niri msg output")?;
+        Ok(Some(json))
+    }
+}
+
+#[async_trait]
+impl FocusProvider for NiriFocusProvider {
+    async fn get_focused_window(&self) -> Result<Option<WindowId>> {
+        let Some(json) = self.focused_window().await? else {
+            return Ok(None);
+        };
 
         // Extract window ID from the JSON response
         if let Some(id) = json.get("id").and_then(|v| v.as_u64()) {
@@ -51,6 +59,16 @@ impl FocusProvider for NiriFocusProvider {
         }
     }
 
+    async fn get_focused_app(&self) -> Result<Option<String>> {
+        let Some(json) = self.focused_window().await? else {
+            return Ok(None);
+        };
+        Ok(json
+            .get("app_id")
+            .and_then(|v| v.as_str())
+            .map(String::from))
+    }
+
     async fn set_focused_window(&self, window_id: &WindowId) -> Result<bool> {
         let output = tokio::process::Command::new("niri")
             .args(["msg", "action", "focus-window", "--id", &window_id.0])
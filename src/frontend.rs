@@ -0,0 +1,205 @@
+use anyhow::{Context, Result};
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::task::JoinHandle;
+
+use crate::Args;
+use rpdictation::session::{Event, State};
+use rpdictation::{audio, output, DictationSession, SAMPLE_RATE};
+
+/// Samples the level meter looks at (a tenth of a second)
+const LEVEL_WINDOW: usize = SAMPLE_RATE as usize / 10;
+
+/// Quietest level the meter shows, in dB relative to full scale
+pub const LEVEL_FLOOR_DB: f32 = -60.0;
+
+/// Finished dictations kept in the history
+const HISTORY_LEN: usize = 100;
+
+/// A finished dictation in the history
+pub struct Entry {
+    pub time: chrono::DateTime<chrono::Local>,
+    /// The transcript, or why there is none
+    pub result: std::result::Result<String, String>,
+}
+
+/// What the interactive front-ends (`rpdictation tui` and `gui`) do with
+/// their keys and buttons, and what they show apart from what the session
+/// reports directly. Nothing is typed, since the front-end has the focus;
+/// the other outputs run as configured.
+pub struct Controller {
+    pub session: Arc<DictationSession>,
+    pub recorder: audio::Recorder,
+    /// For the session's work, which the GUI runs outside its own thread
+    runtime: tokio::runtime::Handle,
+    /// Preview the recording in progress (--live)
+    live: bool,
+    /// Text recognized so far in the recording in progress (with --live)
+    pub partial: String,
+    /// The --live preview being transcribed, and when it was started
+    preview: Option<(JoinHandle<()>, Instant)>,
+    /// Dictations handed to the providers and not finished yet
+    pub transcribing: HashSet<uuid::Uuid>,
+    /// The last recording, kept for retrying
+    pub last: Option<Vec<i16>>,
+    /// Newest first
+    pub history: VecDeque<Entry>,
+    /// Why the last key press or button did nothing, until the next one
+    pub message: Option<String>,
+}
+
+impl Controller {
+    /// The session and recorder configured by `args`
+    pub async fn open(args: &Args) -> Result<Self> {
+        let session = Arc::new(rpdictation::setup::build_session(&args.options())?);
+        let recorder = match args.replay {
+            Some(ref path) => audio::Recorder::replay(
+                audio::decode_file(path)
+                    .await
+                    .with_context(|| format!("Failed to read {}", path.display()))?,
+            ),
+            None => audio::Recorder::open(args.device.as_deref())?,
+        };
+        Ok(Self {
+            session,
+            recorder,
+            runtime: tokio::runtime::Handle::current(),
+            live: args.live,
+            partial: String::new(),
+            preview: None,
+            transcribing: HashSet::new(),
+            last: None,
+            history: VecDeque::new(),
+            message: None,
+        })
+    }
+
+    pub fn start(&mut self) {
+        match self.session.start(uuid::Uuid::new_v4()) {
+            Ok(()) => self.recorder.start(),
+            Err(e) => self.message = Some(e.to_string()),
+        }
+    }
+
+    pub fn stop(&mut self) {
+        let id = match self.session.stop() {
+            Ok(id) => id,
+            Err(e) => {
+                self.message = Some(e.to_string());
+                return;
+            }
+        };
+        self.end_preview();
+        let samples = self.recorder.stop();
+        self.last = Some(samples.clone());
+        self.transcribe(samples, id);
+    }
+
+    pub fn toggle(&mut self) {
+        match self.session.state() {
+            State::Idle => self.start(),
+            State::Recording { .. } => self.stop(),
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        match self.session.cancel() {
+            Ok(_) => {
+                self.end_preview();
+                self.recorder.stop();
+            }
+            // Not recording: stop what is still being transcribed
+            Err(e) => {
+                if self.session.cancel_processing().is_none() {
+                    self.message = Some(e.to_string());
+                }
+            }
+        }
+    }
+
+    /// Transcribe the last recording again, as a new dictation
+    pub fn retry(&mut self) {
+        match self.last.clone() {
+            Some(samples) => self.transcribe(samples, uuid::Uuid::new_v4()),
+            None => self.message = Some("Nothing recorded yet".to_string()),
+        }
+    }
+
+    fn transcribe(&mut self, samples: Vec<i16>, id: uuid::Uuid) {
+        self.transcribing.insert(id);
+        let session = Arc::clone(&self.session);
+        // The outcome comes back as an event
+        self.runtime.spawn(output::silenced(async move {
+            let _ = session.process(samples, None, id).await;
+        }));
+    }
+
+    /// With --live, transcribe what has been said so far every few seconds,
+    /// one request at a time
+    pub fn update_preview(&mut self) {
+        let State::Recording { id, .. } = self.session.state() else {
+            return;
+        };
+        if !self.live {
+            return;
+        }
+        if let Some((ref handle, started)) = self.preview {
+            if !handle.is_finished() || started.elapsed() < rpdictation::record::LIVE_INTERVAL {
+                return;
+            }
+        }
+        let session = Arc::clone(&self.session);
+        let samples = self.recorder.snapshot();
+        let handle = self.runtime.spawn(output::silenced(async move {
+            if let Err(e) = session.preview(id, samples).await {
+                eprintln!("[{}] Live preview failed: {:#}", id, e);
+            }
+        }));
+        self.preview = Some((handle, Instant::now()));
+    }
+
+    /// A preview finishing after the recording would only be outdated
+    fn end_preview(&mut self) {
+        if let Some((handle, _)) = self.preview.take() {
+            handle.abort();
+        }
+    }
+
+    /// The input level while recording, in dB down to [`LEVEL_FLOOR_DB`],
+    /// and as a fraction of the meter
+    pub fn level(&self) -> (f32, f32) {
+        let level = match self.session.state() {
+            State::Recording { .. } => self.recorder.level(LEVEL_WINDOW),
+            State::Idle => 0.0,
+        };
+        let db = (20.0 * level.max(f32::MIN_POSITIVE).log10()).max(LEVEL_FLOOR_DB);
+        (db, (1.0 - db / LEVEL_FLOOR_DB).clamp(0.0, 1.0))
+    }
+
+    pub fn follow(&mut self, event: Event) {
+        match event {
+            Event::Started { .. } => self.partial.clear(),
+            // A recording cancelled before it was transcribed isn't listed
+            Event::Cancelled { id } => {
+                self.partial.clear();
+                if self.transcribing.contains(&id) {
+                    self.finish(id, Err("Cancelled".to_string()));
+                }
+            }
+            Event::Partial { text, .. } => self.partial = text,
+            Event::Stopped { .. } => {}
+            Event::Completed { id, text, .. } => self.finish(id, Ok(text)),
+            Event::Failed { id, error } => self.finish(id, Err(error)),
+        }
+    }
+
+    fn finish(&mut self, id: uuid::Uuid, result: std::result::Result<String, String>) {
+        self.transcribing.remove(&id);
+        self.history.push_front(Entry {
+            time: chrono::Local::now(),
+            result,
+        });
+        self.history.truncate(HISTORY_LEN);
+    }
+}
@@ -0,0 +1,193 @@
+use anyhow::Result;
+use eframe::egui;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::frontend::Controller;
+use crate::{status, Args};
+use rpdictation::audio;
+use rpdictation::session::{Event, State};
+
+/// How often the window is redrawn while something changes on its own (the
+/// clock and the level meter)
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Shown in the device picker for the system's default input
+const DEFAULT_DEVICE: &str = "System default";
+
+struct Window {
+    args: Args,
+    app: Controller,
+    events: broadcast::Receiver<Event>,
+    /// Input devices for the picker, and the one picked (`None` for the
+    /// default)
+    devices: Vec<String>,
+    device: Option<String>,
+}
+
+/// Dictate in a small window: a big record button, a device picker, and
+/// the transcripts, for people who don't live in a terminal.
+pub async fn run(args: Args) -> Result<()> {
+    let app = Controller::open(&args).await?;
+    let window = Window {
+        events: app.session.subscribe(),
+        device: args.device.clone(),
+        devices: audio::input_devices().unwrap_or_default(),
+        args,
+        app,
+    };
+
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("rpdictation")
+            .with_app_id("rpdictation")
+            .with_inner_size([480.0, 560.0]),
+        ..Default::default()
+    };
+    // The GUI toolkit wants the main thread for its event loop
+    tokio::task::block_in_place(|| {
+        eframe::run_native("rpdictation", options, Box::new(|_| Ok(Box::new(window))))
+    })
+    .map_err(|e| anyhow::anyhow!("Failed to open the window: {}", e))
+}
+
+impl Window {
+    /// Record from `device` from now on
+    fn switch_device(&mut self, device: Option<String>) {
+        match audio::Recorder::open(device.as_deref()) {
+            Ok(recorder) => {
+                self.app.recorder = recorder;
+                self.device = device;
+            }
+            Err(e) => self.app.message = Some(format!("{:#}", e)),
+        }
+    }
+
+    fn follow_events(&mut self) {
+        loop {
+            match self.events.try_recv() {
+                Ok(event) => self.app.follow(event),
+                Err(broadcast::error::TryRecvError::Lagged(_)) => {}
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+impl eframe::App for Window {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.follow_events();
+        self.app.update_preview();
+        let state = self.app.session.state();
+        let recording = matches!(state, State::Recording { .. });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Microphone:");
+                let selected = self.device.as_deref().unwrap_or(DEFAULT_DEVICE).to_string();
+                let mut picked = None;
+                ui.add_enabled_ui(!recording && self.args.replay.is_none(), |ui| {
+                    egui::ComboBox::from_id_salt("device")
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            if ui
+                                .selectable_label(self.device.is_none(), DEFAULT_DEVICE)
+                                .clicked()
+                            {
+                                picked = Some(None);
+                            }
+                            for name in &self.devices {
+                                let current = self.device.as_deref() == Some(name.as_str());
+                                if ui.selectable_label(current, name).clicked() {
+                                    picked = Some(Some(name.clone()));
+                                }
+                            }
+                        });
+                });
+                if let Some(device) = picked.filter(|d| *d != self.device) {
+                    self.switch_device(device);
+                }
+            });
+            ui.add_space(12.0);
+
+            ui.vertical_centered(|ui| {
+                let (label, color) = if recording {
+                    ("■  Stop", egui::Color32::from_rgb(90, 90, 90))
+                } else {
+                    ("●  Record", egui::Color32::from_rgb(200, 40, 40))
+                };
+                let button = egui::Button::new(
+                    egui::RichText::new(label)
+                        .size(32.0)
+                        .color(egui::Color32::WHITE),
+                )
+                .fill(color)
+                .min_size(egui::vec2(240.0, 80.0));
+                if ui.add(button).clicked() {
+                    self.app.message = None;
+                    self.app.toggle();
+                }
+
+                ui.add_space(8.0);
+                match state {
+                    State::Recording { since, .. } => {
+                        ui.label(
+                            egui::RichText::new(status::format_elapsed(since.elapsed())).size(20.0),
+                        );
+                        let (_, ratio) = self.app.level();
+                        ui.add(egui::ProgressBar::new(ratio).desired_width(240.0));
+                        if ui.button("Cancel").clicked() {
+                            self.app.cancel();
+                        }
+                    }
+                    State::Idle => {
+                        ui.add_enabled_ui(self.app.last.is_some(), |ui| {
+                            if ui.button("Transcribe the last recording again").clicked() {
+                                self.app.retry();
+                            }
+                        });
+                    }
+                }
+                if !self.app.transcribing.is_empty() {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(format!("Transcribing ({})…", self.app.transcribing.len()));
+                    });
+                }
+                if let Some(ref message) = self.app.message {
+                    ui.colored_label(ui.visuals().error_fg_color, message);
+                }
+            });
+
+            if recording && !self.app.partial.is_empty() {
+                ui.add_space(8.0);
+                ui.label(egui::RichText::new(&self.app.partial).italics());
+            }
+
+            ui.add_space(12.0);
+            ui.separator();
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for entry in &self.app.history {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.weak(entry.time.format("%H:%M:%S").to_string());
+                        match entry.result {
+                            Ok(ref text) => {
+                                if ui.small_button("Copy").clicked() {
+                                    ctx.copy_text(text.clone());
+                                }
+                                ui.label(text);
+                            }
+                            Err(ref error) => {
+                                ui.colored_label(ui.visuals().error_fg_color, error);
+                            }
+                        }
+                    });
+                }
+            });
+        });
+
+        if recording || !self.app.transcribing.is_empty() {
+            ctx.request_repaint_after(FRAME_INTERVAL);
+        }
+    }
+}
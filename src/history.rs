@@ -0,0 +1,366 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+use crate::metrics::Timings;
+use crate::output;
+
+/// What is recorded about a finished dictation, stored as `<id>.json` next to
+/// the audio (`<id>.wav`).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entry {
+    pub id: uuid::Uuid,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub duration_seconds: f64,
+    pub provider: String,
+    pub language: String,
+    /// Transcript as returned by the provider
+    pub text: String,
+    pub cost: Option<f64>,
+    /// What the dictation was for, given with --name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Given with --tag, to find dictations again with `history --tag`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Where the time between stopping the recording and archiving it went
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timings: Option<Timings>,
+}
+
+/// $XDG_DATA_HOME/rpdictation/history, or ~/.local/share/rpdictation/history
+pub fn history_dir() -> PathBuf {
+    let data = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME").unwrap_or_default()).join(".local/share"),
+    };
+    data.join("rpdictation").join("history")
+}
+
+fn entry_path(dir: &Path, id: uuid::Uuid) -> PathBuf {
+    dir.join(format!("{}.json", id))
+}
+
+fn audio_path(dir: &Path, id: uuid::Uuid) -> PathBuf {
+    dir.join(format!("{}.wav", id))
+}
+
+/// Hand-corrected transcript; takes precedence over the provider's text.
+fn correction_path(dir: &Path, id: uuid::Uuid) -> PathBuf {
+    dir.join(format!("{}.txt", id))
+}
+
+/// The hand-corrected transcript of `id`, if there is one
+async fn correction(dir: &Path, id: uuid::Uuid) -> Option<String> {
+    tokio::fs::read_to_string(correction_path(dir, id))
+        .await
+        .ok()
+        .map(|t| t.trim().to_string())
+}
+
+pub fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Archive a dictation: its WAV audio and metadata. The directory is
+/// created readable by the current user only, as what was said may be
+/// private.
+pub async fn save(entry: &Entry, wav: &[u8]) -> Result<()> {
+    let dir = history_dir();
+    tokio::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)
+        .await
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    tokio::fs::write(audio_path(&dir, entry.id), wav).await?;
+    tokio::fs::write(
+        entry_path(&dir, entry.id),
+        serde_json::to_string_pretty(entry)?,
+    )
+    .await?;
+    Ok(())
+}
+
+/// All archived entries, oldest first. Unreadable files are skipped with a
+/// warning.
+pub async fn load_all() -> Result<Vec<Entry>> {
+    let dir = history_dir();
+    let mut entries = Vec::new();
+    let mut read_dir = match tokio::fs::read_dir(&dir).await {
+        Ok(r) => r,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(entries),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", dir.display())),
+    };
+    while let Some(file) = read_dir.next_entry().await? {
+        let path = file.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let parsed = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|s| serde_json::from_str::<Entry>(&s).map_err(anyhow::Error::from));
+        match parsed {
+            Ok(entry) => entries.push(entry),
+            Err(e) => eprintln!("Warning: Skipping {}: {}", path.display(), e),
+        }
+    }
+    entries.sort_by_key(|e| e.timestamp);
+    Ok(entries)
+}
+
+/// How many dictations [`prune`] deleted altogether, and from how many more
+/// it deleted just the audio
+#[derive(Debug, Default)]
+pub struct Pruned {
+    pub entries: usize,
+    pub audio: usize,
+}
+
+/// Delete the dictations older than `keep_entries` (audio, transcript,
+/// and correction), and the audio of those older than `keep_audio`.
+pub async fn prune(keep_entries: Option<Duration>, keep_audio: Option<Duration>) -> Result<Pruned> {
+    let dir = history_dir();
+    let now = now();
+    let older = |keep: Option<Duration>, entry: &Entry| {
+        keep.is_some_and(|keep| now.saturating_sub(entry.timestamp) > keep.as_secs())
+    };
+    let mut pruned = Pruned::default();
+    for entry in load_all().await? {
+        if older(keep_entries, &entry) {
+            remove(&audio_path(&dir, entry.id)).await?;
+            remove(&correction_path(&dir, entry.id)).await?;
+            remove(&entry_path(&dir, entry.id)).await?;
+            pruned.entries += 1;
+        } else if older(keep_audio, &entry) && remove(&audio_path(&dir, entry.id)).await? {
+            pruned.audio += 1;
+        }
+    }
+    Ok(pruned)
+}
+
+/// Delete the file at `path`; whether there was one
+async fn remove(path: &Path) -> Result<bool> {
+    match tokio::fs::remove_file(path).await {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e).with_context(|| format!("Failed to delete {}", path.display())),
+    }
+}
+
+/// Print the archived dictations carrying every one of `tags` and, if
+/// given, called `name`, oldest first: one line each with the time, the
+/// name and tags, and the final text, or the entries as JSON lines with
+/// --json.
+pub async fn list(tags: &[String], name: Option<&str>) -> Result<()> {
+    let dir = history_dir();
+    let mut stdout = tokio::io::stdout();
+    for mut entry in load_all().await? {
+        if !tags.iter().all(|tag| entry.tags.contains(tag))
+            || name.is_some_and(|name| entry.name.as_deref() != Some(name))
+        {
+            continue;
+        }
+        if let Some(corrected) = correction(&dir, entry.id).await {
+            entry.text = corrected;
+        }
+        let line = if output::mode() == output::Mode::Json {
+            serde_json::to_string(&entry)?
+        } else {
+            let time = local_time(entry.timestamp)
+                .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
+            let mut labels: Vec<&str> = entry.name.iter().map(String::as_str).collect();
+            labels.extend(entry.tags.iter().map(|tag| tag.as_str()));
+            if labels.is_empty() {
+                format!("{}  {}", time, entry.text)
+            } else {
+                format!("{}  [{}]  {}", time, labels.join(", "), entry.text)
+            }
+        };
+        stdout.write_all(line.as_bytes()).await?;
+        stdout.write_all(b"\n").await?;
+    }
+    stdout.flush().await?;
+    Ok(())
+}
+
+/// Write a JSONL dataset pairing each archived recording with its final text
+/// (the correction if there is one), one `{"audio", "text", ...}` object per
+/// line, for fine-tuning a speech model. Entries whose audio is gone are
+/// skipped.
+pub async fn export_training(output: Option<&Path>) -> Result<()> {
+    let dir = history_dir();
+    let mut lines = String::new();
+    let mut count = 0;
+    for entry in load_all().await? {
+        let audio = audio_path(&dir, entry.id);
+        if !audio.exists() {
+            continue;
+        }
+        let corrected = correction(&dir, entry.id).await;
+        let line = serde_json::json!({
+            "id": entry.id,
+            "audio": audio,
+            "text": corrected.as_deref().unwrap_or(&entry.text),
+            "corrected": corrected.is_some(),
+            "language": entry.language,
+            "duration": entry.duration_seconds,
+        });
+        lines.push_str(&line.to_string());
+        lines.push('\n');
+        count += 1;
+    }
+
+    write_export(output, &lines, count).await
+}
+
+/// Document format of `history export`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Markdown, a section per day with the time, duration, and cost of
+    /// each dictation, and the totals at the end
+    Md,
+    /// One row per dictation, for spreadsheets
+    Csv,
+    /// An array of the entries, with their final text
+    Json,
+}
+
+/// Write the archived dictations from `since` (local midnight) on as a
+/// document, with the corrected text where there is one, for archiving or
+/// reporting.
+pub async fn export(
+    format: ExportFormat,
+    since: Option<NaiveDate>,
+    output: Option<&Path>,
+) -> Result<()> {
+    let dir = history_dir();
+    let since = since
+        .and_then(|day| day.and_hms_opt(0, 0, 0))
+        .and_then(|midnight| midnight.and_local_timezone(Local).earliest())
+        .map_or(0, |t| t.timestamp().max(0) as u64);
+    let mut entries = Vec::new();
+    for mut entry in load_all().await? {
+        if entry.timestamp < since {
+            continue;
+        }
+        if let Some(corrected) = correction(&dir, entry.id).await {
+            entry.text = corrected;
+        }
+        entries.push(entry);
+    }
+    let document = match format {
+        ExportFormat::Md => markdown(&entries),
+        ExportFormat::Csv => csv(&entries),
+        ExportFormat::Json => serde_json::to_string_pretty(&entries)? + "\n",
+    };
+    write_export(output, &document, entries.len()).await
+}
+
+fn markdown(entries: &[Entry]) -> String {
+    let mut doc = String::from("# Dictation history\n");
+    let mut day = String::new();
+    for entry in entries {
+        let time = local_time(entry.timestamp);
+        let this_day = time.map(|t| t.format("%Y-%m-%d").to_string());
+        if this_day.as_ref() != Some(&day) {
+            day = this_day.unwrap_or_default();
+            let _ = write!(doc, "\n## {}\n\n", day);
+        }
+        let _ = write!(
+            doc,
+            "- **{}** ({:.1} s",
+            time.map(|t| t.format("%H:%M").to_string())
+                .unwrap_or_default(),
+            entry.duration_seconds
+        );
+        if let Some(cost) = entry.cost {
+            let _ = write!(doc, ", ${:.4}", cost);
+        }
+        if let Some(ref name) = entry.name {
+            let _ = write!(doc, ", {}", name);
+        }
+        for tag in &entry.tags {
+            let _ = write!(doc, ", #{}", tag);
+        }
+        let _ = writeln!(doc, ") {}", entry.text.replace('\n', " "));
+    }
+    let seconds: f64 = entries.iter().map(|e| e.duration_seconds).sum();
+    let cost: f64 = entries.iter().filter_map(|e| e.cost).sum();
+    let _ = write!(
+        doc,
+        "\n{} dictations, {:.1} minutes, ${:.4}\n",
+        entries.len(),
+        seconds / 60.0,
+        cost
+    );
+    doc
+}
+
+fn csv(entries: &[Entry]) -> String {
+    let mut doc = String::from("id,time,duration_seconds,provider,language,cost,name,tags,text\n");
+    for entry in entries {
+        let time = local_time(entry.timestamp)
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_default();
+        let fields = [
+            entry.id.to_string(),
+            time,
+            entry.duration_seconds.to_string(),
+            entry.provider.clone(),
+            entry.language.clone(),
+            entry.cost.map(|c| c.to_string()).unwrap_or_default(),
+            entry.name.clone().unwrap_or_default(),
+            entry.tags.join(";"),
+            entry.text.clone(),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        doc.push_str(&row.join(","));
+        doc.push('\n');
+    }
+    doc
+}
+
+/// `field` quoted as CSV needs it: in double quotes, doubling the quotes
+/// inside, when it holds a comma, quote, or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn local_time(timestamp: u64) -> Option<DateTime<Local>> {
+    Local.timestamp_opt(timestamp as i64, 0).single()
+}
+
+/// Write an export to `output`, or to stdout
+async fn write_export(output: Option<&Path>, contents: &str, count: usize) -> Result<()> {
+    match output {
+        Some(path) => {
+            tokio::fs::write(path, contents)
+                .await
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!("Exported {} entries to {}", count, path.display());
+        }
+        None => {
+            let mut stdout = tokio::io::stdout();
+            stdout.write_all(contents.as_bytes()).await?;
+            stdout.flush().await?;
+        }
+    }
+    Ok(())
+}
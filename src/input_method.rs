@@ -0,0 +1,101 @@
+use anyhow::{Context, Result};
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::{wl_registry, wl_seat::WlSeat};
+use wayland_client::{delegate_noop, Connection, Dispatch, QueueHandle};
+use wayland_protocols_misc::zwp_input_method_v2::client::{
+    zwp_input_method_manager_v2::ZwpInputMethodManagerV2,
+    zwp_input_method_v2::{self, ZwpInputMethodV2},
+};
+
+/// Roundtrips to wait for the compositor to activate the input method
+/// before concluding no text field has the focus
+const ACTIVATE_ROUNDTRIPS: usize = 5;
+
+#[derive(Default)]
+struct State {
+    /// A text field has the focus; applied with the next `done`
+    activating: bool,
+    active: bool,
+    /// `done` events so far, the serial a commit refers to
+    serial: u32,
+    unavailable: bool,
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwpInputMethodV2, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwpInputMethodV2,
+        event: zwp_input_method_v2::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_input_method_v2::Event::Activate => state.activating = true,
+            zwp_input_method_v2::Event::Deactivate => state.activating = false,
+            zwp_input_method_v2::Event::Done => {
+                state.serial += 1;
+                state.active = state.activating;
+            }
+            zwp_input_method_v2::Event::Unavailable => state.unavailable = true,
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(State: ignore WlSeat);
+delegate_noop!(State: ZwpInputMethodManagerV2);
+
+/// Commit `text` to the focused text field as an input method, through the
+/// compositor's input-method-unstable-v2 protocol. The text arrives as a
+/// whole string, the way IBus or Fcitx5 hand over a finished conversion,
+/// so no keymap or key events are involved and any script works. Needs an
+/// application supporting text-input-unstable-v3 and no other input method
+/// holding the seat. Blocking.
+pub fn commit(text: &str) -> Result<()> {
+    let conn = Connection::connect_to_env().context("Not running under a Wayland compositor")?;
+    let (globals, mut queue) = registry_queue_init::<State>(&conn)?;
+    let qh = queue.handle();
+    let seat: WlSeat = globals.bind(&qh, 1..=1, ()).context("No Wayland seat")?;
+    let manager: ZwpInputMethodManagerV2 = globals
+        .bind(&qh, 1..=1, ())
+        .context("Compositor doesn't support input-method-unstable-v2")?;
+    let input_method = manager.get_input_method(&seat, &qh, ());
+
+    let mut state = State::default();
+    for _ in 0..ACTIVATE_ROUNDTRIPS {
+        queue.roundtrip(&mut state)?;
+        if state.active || state.unavailable {
+            break;
+        }
+    }
+    let result = if state.unavailable {
+        Err(anyhow::anyhow!(
+            "Another input method (IBus, Fcitx5, ...) is running; use another typer while it is"
+        ))
+    } else if !state.active {
+        Err(anyhow::anyhow!(
+            "The focused window has no text field taking input method text (text-input-unstable-v3)"
+        ))
+    } else {
+        input_method.commit_string(text.to_string());
+        input_method.commit(state.serial);
+        queue.roundtrip(&mut state).map(|_| ()).map_err(Into::into)
+    };
+
+    input_method.destroy();
+    queue.roundtrip(&mut state)?;
+    result
+}
@@ -0,0 +1,129 @@
+//! The dictation pipeline behind the `rpdictation` command: record from the
+//! microphone ([`Recorder`]), transcribe with a chain of
+//! [`TranscriptionProvider`]s, and hand the text to [`OutputSink`]s, all
+//! driven by a [`DictationSession`].
+
+use anyhow::{Context, Result};
+use std::env;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+#[macro_use]
+pub mod output;
+
+#[cfg(feature = "dbus")]
+pub mod atspi;
+pub mod audio;
+pub mod clipboard;
+pub mod confirm;
+pub mod credentials;
+pub mod error;
+pub mod focus;
+pub mod history;
+#[cfg(feature = "native-typer")]
+pub mod input_method;
+#[cfg(any(feature = "openai", feature = "groq", feature = "mistral"))]
+pub mod llm;
+pub mod metrics;
+pub mod notifier;
+pub mod numbers;
+#[cfg(feature = "overlay")]
+pub mod overlay;
+pub mod postprocess;
+pub mod profanity;
+pub mod providers;
+pub mod punctuation;
+pub mod record;
+pub mod replacements;
+pub mod rewrite;
+pub mod session;
+pub mod setup;
+pub mod sinks;
+pub mod spelling;
+pub mod subtitles;
+pub mod typer;
+pub mod typing_queue;
+pub mod vad;
+#[cfg(feature = "native-typer")]
+pub mod virtual_keyboard;
+pub mod voice_commands;
+
+pub use audio::Recorder;
+pub use error::Error;
+pub use providers::TranscriptionProvider;
+pub use session::DictationSession;
+pub use sinks::{OutputSink, Transcript};
+
+pub const SAMPLE_RATE: u32 = 16000;
+pub const CHANNELS: u16 = 1;
+pub const BITS_PER_SAMPLE: u16 = 16;
+pub const BYTES_PER_SAMPLE: usize = (BITS_PER_SAMPLE / 8) as usize;
+pub const MIN_RECORDING_DURATION_SECONDS: f64 = 1.0;
+
+/// Punctuation a provider puts around words, ignored when matching them
+/// (spelled letters, voice commands)
+pub(crate) const PUNCTUATION: &[char] = &[',', '.', '!', '?', ';', ':', '"', '\'', '“', '”'];
+
+/// Set from --runtime-dir at startup
+static RUNTIME_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Per-user runtime directory: $XDG_RUNTIME_DIR, or `/run/user/<uid>` when unset
+pub fn get_user_runtime_dir() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(format!("/run/user/{}", nix::unistd::getuid())),
+    }
+}
+
+/// Use `dir` instead of the default runtime directory. Only the first call
+/// has an effect.
+pub fn set_runtime_dir(dir: PathBuf) {
+    let _ = RUNTIME_DIR.set(dir);
+}
+
+/// Where the stop FIFO, PID file, daemon socket, and other runtime files
+/// live: --runtime-dir, or $XDG_RUNTIME_DIR/rpdictation
+pub fn get_runtime_dir() -> PathBuf {
+    RUNTIME_DIR
+        .get()
+        .cloned()
+        .unwrap_or_else(|| get_user_runtime_dir().join("rpdictation"))
+}
+
+/// Create the runtime directory, readable by the current user only.
+pub fn create_runtime_dir() -> Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    let dir = get_runtime_dir();
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)
+        .with_context(|| format!("Failed to create {}", dir.display()))
+}
+
+pub fn get_pid_path() -> PathBuf {
+    get_runtime_dir().join("recording.pid")
+}
+
+pub fn get_fifo_path() -> PathBuf {
+    get_runtime_dir().join("stop")
+}
+
+/// `path` with a leading `~` replaced by the home directory, since config
+/// files can't rely on the shell expanding it
+pub(crate) fn expand_home(path: PathBuf) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => PathBuf::from(env::var_os("HOME").unwrap_or_default()).join(rest),
+        Err(_) => path,
+    }
+}
+
+pub async fn command_exists(name: &str) -> bool {
+    tokio::process::Command::new("which")
+        .arg(name)
+        .stdout(std::process::Stdio::null())
+        .status()
+        .await
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
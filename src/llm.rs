@@ -0,0 +1,146 @@
+//! Chat models behind the OpenAI-compatible APIs of OpenAI, Groq, and
+//! Mistral, rewriting transcripts into summaries and translations.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+
+use crate::credentials::ApiKey;
+use crate::rewrite::{Rewriter, SummaryMode};
+
+pub struct Llm {
+    name: &'static str,
+    endpoint: &'static str,
+    model: String,
+    api_key: ApiKey,
+    client: reqwest::Client,
+}
+
+impl Llm {
+    /// `provider` is "openai", "groq", or "mistral"; `model` replaces its
+    /// default model.
+    pub fn new(provider: &str, api_key: ApiKey, model: Option<String>) -> Result<Self> {
+        let (name, endpoint, default_model) = match provider {
+            "openai" => (
+                "OpenAI",
+                "https://api.openai.com/v1/chat/completions",
+                "gpt-4o-mini",
+            ),
+            "groq" => (
+                "Groq",
+                "https://api.groq.com/openai/v1/chat/completions",
+                "llama-3.3-70b-versatile",
+            ),
+            "mistral" => (
+                "Mistral",
+                "https://api.mistral.ai/v1/chat/completions",
+                "mistral-small-latest",
+            ),
+            _ => anyhow::bail!(
+                "No language model for '{}'; use openai, groq, or mistral",
+                provider
+            ),
+        };
+        Ok(Self {
+            name,
+            endpoint,
+            model: model.unwrap_or_else(|| default_model.to_string()),
+            api_key,
+            client: crate::providers::http_client(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    /// The model's answer to `text`, following `instructions`
+    pub async fn complete(&self, instructions: &str, text: &str) -> Result<String> {
+        let api_key = self.api_key.resolve().await?;
+        let body = serde_json::json!({
+            "model": self.model,
+            "messages": [
+                { "role": "system", "content": instructions },
+                { "role": "user", "content": text },
+            ],
+            "temperature": 0.2,
+        });
+        let response = crate::providers::query(
+            self.name,
+            self.client
+                .post(self.endpoint)
+                .header("Authorization", format!("Bearer {}", api_key))
+                .json(&body),
+        )
+        .await?;
+
+        let result: serde_json::Value = response
+            .json()
+            .await
+            .context("Failed to parse API response as JSON")?;
+        let Some(answer) = result["choices"][0]["message"]["content"].as_str() else {
+            anyhow::bail!("Failed to get an answer from response");
+        };
+        Ok(answer.trim().to_string())
+    }
+}
+
+/// Turns a transcript into a bulleted list of its key points
+pub struct Summarizer {
+    llm: Llm,
+    mode: SummaryMode,
+}
+
+impl Summarizer {
+    const INSTRUCTIONS: &str = "Summarize the dictated text you are given as a short \
+        bulleted list of its key points, one \"- \" bullet per line, in the language \
+        of the text. Reply with the list only.";
+
+    pub fn new(llm: Llm, mode: SummaryMode) -> Self {
+        Self { llm, mode }
+    }
+}
+
+#[async_trait]
+impl Rewriter for Summarizer {
+    fn describe(&self) -> String {
+        format!("Summarizing ({})", self.llm.name())
+    }
+
+    async fn rewrite(&self, text: &str) -> Result<String> {
+        let summary = self.llm.complete(Self::INSTRUCTIONS, text).await?;
+        Ok(match self.mode {
+            SummaryMode::Replace => summary,
+            SummaryMode::Append => format!("{}\n\n{}", summary, text),
+        })
+    }
+}
+
+/// Translates a transcript into another language
+pub struct Translator {
+    llm: Llm,
+    language: String,
+}
+
+impl Translator {
+    /// `language` is a name or code the model will understand, e.g. "de" or
+    /// "German"
+    pub fn new(llm: Llm, language: String) -> Self {
+        Self { llm, language }
+    }
+}
+
+#[async_trait]
+impl Rewriter for Translator {
+    fn describe(&self) -> String {
+        format!("Translating to {} ({})", self.language, self.llm.name())
+    }
+
+    async fn rewrite(&self, text: &str) -> Result<String> {
+        let instructions = format!(
+            "Translate the dictated text you are given into the language {}. Keep its \
+             meaning, tone, and formatting. Reply with the translation only.",
+            self.language
+        );
+        self.llm.complete(&instructions, text).await
+    }
+}
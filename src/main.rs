@@ -1,151 +1,61 @@
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use nix::sys::signal::{kill, Signal};
-use nix::unistd::Pid;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use std::env;
-use std::io::IsTerminal;
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
-use tokio::signal::unix::{signal, SignalKind};
-use tokio_util::sync::CancellationToken;
-
-mod audio;
-mod focus;
-mod providers;
-use focus::FocusProvider;
-use providers::{
-    google::GoogleProvider, groq::GroqProvider, mistral::MistralProvider, openai::OpenAIProvider,
-    TranscriptionProvider,
-};
-
-const SAMPLE_RATE: u32 = 16000;
-const CHANNELS: u16 = 1;
-const BITS_PER_SAMPLE: u16 = 16;
-const BYTES_PER_SAMPLE: usize = (BITS_PER_SAMPLE / 8) as usize;
-const MIN_RECORDING_DURATION_SECONDS: f64 = 1.0;
-
-const FIFO_PATH: &str = "/tmp/rpdictation_stop";
-
-async fn send_notification(message: &str, expire: bool) {
-    let expire_time = if expire { "3000" } else { "0" };
-    let _ = tokio::process::Command::new("notify-send")
-        .args([
-            "--hint=string:x-canonical-private-synchronous:rpdictation",
-            &format!("--expire-time={}", expire_time),
-        ])
-        .arg(message)
-        .status()
-        .await;
-}
-
-struct ClipboardSnapshot {
-    mime: String,
-    data: Vec<u8>,
-}
-
-async fn save_selection(primary: bool) -> Option<ClipboardSnapshot> {
-    let mut list_args: Vec<&str> = vec!["--list-types"];
-    if primary {
-        list_args.push("--primary");
-    }
-    let list_out = tokio::process::Command::new("wl-paste")
-        .args(&list_args)
-        .output()
-        .await
-        .ok()?;
-    if !list_out.status.success() {
-        return None;
-    }
-    let mime = String::from_utf8_lossy(&list_out.stdout)
-        .lines()
-        .next()?
-        .trim()
-        .to_string();
-    if mime.is_empty() {
-        return None;
-    }
 
-    let mut read_args: Vec<&str> = vec!["--no-newline", "--type", &mime];
-    if primary {
-        read_args.push("--primary");
-    }
-    let data_out = tokio::process::Command::new("wl-paste")
-        .args(&read_args)
-        .output()
-        .await
-        .ok()?;
-    if !data_out.status.success() {
-        return None;
-    }
-
-    Some(ClipboardSnapshot {
-        mime,
-        data: data_out.stdout,
-    })
-}
+#[macro_use]
+extern crate rpdictation;
+
+mod check;
+mod config;
+mod control;
+mod daemon;
+#[cfg(feature = "dbus")]
+mod dbus;
+mod file;
+#[cfg(any(feature = "tui", feature = "gui"))]
+mod frontend;
+#[cfg(feature = "gui")]
+mod gui;
+mod prometheus;
+#[cfg(feature = "push-to-talk")]
+mod push_to_talk;
+#[cfg(feature = "dbus")]
+mod shortcut;
+mod status;
+#[cfg(feature = "tray")]
+mod tray;
+#[cfg(feature = "tui")]
+mod tui;
+mod wake;
+mod watch;
+#[cfg(feature = "websocket")]
+mod websocket;
+use rpdictation::notifier;
+use rpdictation::numbers::NumberStyle;
+use rpdictation::profanity::Profanity;
+use rpdictation::providers;
+use rpdictation::punctuation::Case;
+use rpdictation::rewrite::SummaryMode;
+use rpdictation::setup::Options;
+use rpdictation::typer::PasteKey;
+use rpdictation::{
+    audio, create_runtime_dir, get_pid_path, history, output, record, set_runtime_dir,
+};
 
-async fn restore_selection(primary: bool, snap: Option<ClipboardSnapshot>) -> Result<()> {
-    match snap {
-        Some(s) => {
-            let mut cmd = tokio::process::Command::new("wl-copy");
-            cmd.arg("--type").arg(&s.mime);
-            if primary {
-                cmd.arg("--primary");
-            }
-            cmd.stdin(std::process::Stdio::piped());
-            let mut child = cmd.spawn().context("Failed to spawn wl-copy for restore")?;
-            if let Some(mut stdin) = child.stdin.take() {
-                stdin.write_all(&s.data).await.ok();
-            }
-            child.wait().await.context("wl-copy restore failed")?;
-        }
-        None => {
-            let mut cmd = tokio::process::Command::new("wl-copy");
-            cmd.arg("--clear");
-            if primary {
-                cmd.arg("--primary");
-            }
-            cmd.status().await.context("wl-copy --clear failed")?;
-        }
-    }
+/// Send `command` through `channel` and print the reply.
+async fn send_control(channel: &dyn control::ControlChannel, command: &str) -> Result<()> {
+    let reply = channel.send(command).await?;
+    say!("{}: {}", channel.name(), reply);
     Ok(())
 }
 
-fn get_pid_path() -> PathBuf {
-    let uid = nix::unistd::getuid();
-    PathBuf::from(format!("/run/user/{}/rpdictation.pid", uid))
-}
-
-async fn stop_recording() -> Result<()> {
-    let pid_path = get_pid_path();
-
-    // Check PID file exists
-    let pid_str = tokio::fs::read_to_string(&pid_path)
-        .await
-        .context("No recording in progress (PID file not found)")?;
-    let pid = pid_str.trim().parse::<i32>().context("Invalid PID file")?;
-
-    // Check process exists and is rpdictation by reading /proc/<pid>/comm
-    let comm_path = format!("/proc/{}/comm", pid);
-    let comm = tokio::fs::read_to_string(&comm_path)
-        .await
-        .context("No recording in progress (process not running)")?;
-
-    if comm.trim() != "rpdictation" {
-        // PID was reused by another process, clean up stale file
-        tokio::fs::remove_file(&pid_path).await?;
-        anyhow::bail!(
-            "No recording in progress (stale PID, was reused by '{}')",
-            comm.trim()
-        );
+/// Have a running daemon transcribe `language` from now on. A one-shot
+/// recording keeps the language it was started with.
+async fn switch_language(language: &str) -> Result<()> {
+    if let Some(reply) = daemon::request(&format!("lang {}", language)).await {
+        reply?;
     }
-
-    // Send signal
-    kill(Pid::from_raw(pid), Signal::SIGUSR1).context("Failed to send stop signal")?;
-
-    println!("Stop signal sent to recording process");
     Ok(())
 }
 
@@ -166,17 +76,36 @@ async fn is_instance_running() -> Option<i32> {
     }
 }
 
-#[derive(Parser)]
+#[derive(Parser, Clone)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     #[command(subcommand)]
     command: Option<Command>,
 
-    /// Typing backend to use (e.g., wtype, ydotool)
+    /// Config file (default: $XDG_CONFIG_HOME/rpdictation/config.toml)
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Use the settings of [profile.NAME] from the config file on top of the
+    /// top-level ones
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Settings loaded from the config file
+    #[arg(skip)]
+    settings: config::Config,
+
+    /// Typing backend to use: wtype, ydotool, native (the compositor's
+    /// virtual keyboard protocol, no external tool needed), input-method
+    /// (committing text as an input method, for any script), or atspi
+    /// (inserting into the focused field through the accessibility
+    /// interface)
     #[arg(long, value_name = "TOOL")]
     typer: Option<String>,
 
-    /// Transcription provider(s): "openai", "mistral", "groq", or "google".
+    /// Transcription provider(s): "openai", "mistral", "groq", "google", or
+    /// "wyoming" (a self-hosted service, see --wyoming-server). "mock" answers
+    /// with $RPDICTATION_MOCK_TEXT without any network.
     /// Accepts a comma-separated list to retry in order on failure,
     /// e.g. "google,google,groq,mistral". Auto-detects a single provider
     /// based on API key availability if not specified.
@@ -195,28 +124,420 @@ struct Args {
     #[arg(long)]
     groq_api_key: Option<String>,
 
+    /// Token endpoint handing out short-lived API keys for providers without a
+    /// static key (overrides RPDICTATION_TOKEN_ENDPOINT environment variable).
+    /// Queried with ?provider=<name> right before each transcription request.
+    #[arg(long, value_name = "URL")]
+    token_endpoint: Option<String>,
+
+    /// Wyoming speech-to-text service for the wyoming provider, e.g.
+    /// homeassistant.local:10300. Once set, auto-detection tries it first.
+    #[arg(long, value_name = "HOST:PORT")]
+    wyoming_server: Option<String>,
+
     /// Google API key (optional, uses default Chromium key if not provided)
     #[arg(long)]
     google_api_key: Option<String>,
 
-    /// Language code for Google provider (e.g., en-us, cs-CZ)
-    #[arg(long, default_value = "en-us")]
+    /// Language to transcribe (e.g. en-us, cs-CZ), for the Google and
+    /// Wyoming providers and the voice commands; the others detect it. `auto`
+    /// uses the language each dictation is detected to be in, see
+    /// [route.LANG] in the config file. Given with start or toggle, a
+    /// running daemon switches to it
+    #[arg(long, visible_alias = "lang", default_value = "en-us")]
     language: String,
 
+    /// Count down this many seconds before recording starts, to leave time
+    /// to switch to the window the text is for
+    #[arg(long, value_name = "SECONDS")]
+    delay: Option<u64>,
+
     /// Track window focus and restore it before typing
     #[arg(long)]
     track_window: bool,
 
-    /// Press Enter after typing the transcription (requires --typer)
+    /// Like --track-window, but if focus has moved to another window by the
+    /// time the text is ready, copy it to the clipboard instead of typing
     #[arg(long)]
+    strict_focus: bool,
+
+    /// Press Enter after typing the transcription (with --typer) or sending
+    /// it to --tmux, e.g. to send a chat message or run a command
+    /// hands-free
+    #[arg(long, visible_alias = "press-enter")]
     enter: bool,
 
+    /// Treat each dictation typed into the same window as a continuation of
+    /// the previous one: insert a separating space and fix the capitalization
+    /// at the join instead of typing every transcript as a new sentence.
+    /// Mostly useful with the daemon.
+    #[arg(long)]
+    append: bool,
+
     /// Insert text via clipboard paste (wl-copy + Shift+Insert) instead of
     /// direct typing. Useful when the typer's direct-type path is broken
     /// (e.g. wtype on Niri) or strips diacritics (ydotool). Implicitly
     /// enabled for non-English languages.
     #[arg(long)]
     paste: bool,
+
+    /// Pause between typed characters, for applications that drop keys
+    /// when text arrives all at once (remote desktops, Java apps, games)
+    #[arg(long, value_name = "MS")]
+    type_delay: Option<u64>,
+
+    /// Key combination that pastes in paste mode. Shift+Insert works almost
+    /// everywhere; pick another one for applications that ignore it
+    #[arg(long, value_enum, default_value = "shift-insert")]
+    paste_key: PasteKey,
+
+    /// Where the transcription goes, in order: a comma-separated list of
+    /// type, paste, clipboard, primary, osc52, history, stdout, exec, file,
+    /// journal, org, nvim, tmux, webhook, and mqtt. Defaults to type when
+    /// --typer is set; --clipboard, --primary, --osc52, --history, --exec,
+    /// --append-file, --journal, --org, --nvim, --tmux, --webhook, --mqtt
+    /// and --quiet add their outputs.
+    #[arg(long, value_name = "LIST")]
+    output: Option<String>,
+
+    /// Copy the transcription to the clipboard (wl-copy, xclip, or xsel),
+    /// in addition to typing it when --typer is set
+    #[arg(long)]
+    clipboard: bool,
+
+    /// Put the transcription in the primary selection, for pasting with the
+    /// middle mouse button
+    #[arg(long)]
+    primary: bool,
+
+    /// Put the transcription on the clipboard of the terminal rpdictation
+    /// runs in with an OSC 52 escape sequence, e.g. the local clipboard
+    /// when running over SSH
+    #[arg(long)]
+    osc52: bool,
+
+    /// Run a shell command with each transcription: `{text}` is replaced
+    /// with the quoted text, and RPD_TEXT, RPD_ID, RPD_DURATION,
+    /// RPD_PROVIDER, RPD_LANGUAGE, and RPD_COST are set in its environment
+    #[arg(long, value_name = "COMMAND")]
+    exec: Option<String>,
+
+    /// POST each transcription as JSON (id, text, duration_seconds,
+    /// timestamp, provider, language, cost) to URL
+    #[arg(long, value_name = "URL")]
+    webhook: Option<String>,
+
+    /// Publish each transcription to <prefix>/transcript on an MQTT broker,
+    /// and the recording state and session events to <prefix>/state and
+    /// <prefix>/event: mqtt://[user[:password]@]host[:port][/prefix]
+    #[arg(long, value_name = "URL")]
+    mqtt: Option<String>,
+
+    /// Append each transcription to FILE
+    #[arg(long, value_name = "FILE")]
+    append_file: Option<PathBuf>,
+
+    /// Line written above each entry in --append-file, with strftime-style
+    /// date and time fields (e.g. "## %Y-%m-%d %H:%M")
+    #[arg(long, value_name = "FORMAT")]
+    append_header: Option<String>,
+
+    /// Add each transcription as a timestamped item under today's heading
+    /// in a daily note, e.g. "~/Notes/%Y-%m-%d.md" (strftime-style fields)
+    #[arg(long, value_name = "PATH")]
+    journal: Option<String>,
+
+    /// Heading in the --journal note that entries go under, with
+    /// strftime-style fields [default: "## %Y-%m-%d"]
+    #[arg(long, value_name = "FORMAT")]
+    journal_heading: Option<String>,
+
+    /// File a missing --journal note is started from; {{date}} and
+    /// {{title}} are filled in
+    #[arg(long, value_name = "FILE")]
+    journal_template: Option<PathBuf>,
+
+    /// Capture each transcription as an entry at the end of an org file: a
+    /// headline, an inactive timestamp, and the text
+    #[arg(long, value_name = "FILE")]
+    org: Option<PathBuf>,
+
+    /// Headline of --org entries, e.g. "TODO Voice note" [default: the
+    /// first sentence of the transcription]
+    #[arg(long, value_name = "TEXT")]
+    org_headline: Option<String>,
+
+    /// Insert each transcription at the cursor of the Neovim listening on
+    /// ADDR (a socket path or host:port, as in $NVIM). The nvim output
+    /// alone uses $NVIM.
+    #[arg(long, value_name = "ADDR")]
+    nvim: Option<String>,
+
+    /// Send each transcription as keystrokes to a tmux pane (e.g. "%3" or
+    /// "work:1.0"), whichever window has the focus
+    #[arg(long, value_name = "PANE")]
+    tmux: Option<String>,
+
+    /// Show each transcription and ask before outputting it: accept, reject,
+    /// or fix it in $EDITOR first. Asks on the terminal, or in a
+    /// notification with the daemon
+    #[arg(long)]
+    confirm: bool,
+
+    /// Open each transcription in $EDITOR before outputting it, to fix a
+    /// misheard word; saving it empty drops it. Without $VISUAL or $EDITOR
+    /// set, it is shown on the terminal to be typed over
+    #[arg(long)]
+    edit: bool,
+
+    /// Read the whole dictation as spelled in the NATO alphabet ("alpha
+    /// bravo seven" types "ab7"), for identifiers and passwords. Without
+    /// it, only "spell … stop" sequences are
+    #[arg(long)]
+    spell: bool,
+
+    /// How to write numbers: as the provider did, in digits ("twenty
+    /// three" → "23"), or in digits from 10 up and words below
+    #[arg(long, value_name = "STYLE", default_value = "keep")]
+    numbers: NumberStyle,
+
+    /// Rewrite dates ("March fifth") in this strftime-style format, e.g.
+    /// "%Y-%m-%d"
+    #[arg(long, value_name = "FORMAT")]
+    date_format: Option<String>,
+
+    /// Act on a command said at the end of a dictation instead of typing
+    /// it: "scratch that" drops the dictation, "send it" presses Enter
+    /// after it, "new paragraph" and "new line" end it with a line break.
+    /// Said in the --language: English, Czech, or German
+    #[arg(long)]
+    voice_commands: bool,
+
+    /// After each dictation, print how long capturing, encoding, uploading,
+    /// the provider, rewriting, and the outputs took (always in --json
+    /// output and the history)
+    #[arg(long)]
+    timings: bool,
+
+    /// Letter case of the text: as the provider wrote it, all lowercase
+    /// (e.g. for terminal commands), or each sentence capitalized
+    #[arg(long, value_name = "CASE", default_value = "preserve")]
+    case: Case,
+
+    /// Drop the full stop at the end of the text
+    #[arg(long)]
+    strip_period: bool,
+
+    /// Turn line breaks and runs of spaces into single spaces
+    #[arg(long)]
+    collapse_whitespace: bool,
+
+    /// Mask swear words ("f***") or leave them out, so an outburst doesn't
+    /// end up in a work chat; Google is also asked to filter them
+    #[arg(long, value_name = "MODE", default_value = "keep")]
+    profanity: Profanity,
+
+    /// Output a bulleted summary of the transcription, made by a language
+    /// model, instead of it (replace, the default) or above it (append)
+    #[arg(
+        long,
+        value_name = "MODE",
+        num_args = 0..=1,
+        default_missing_value = "replace"
+    )]
+    summarize: Option<SummaryMode>,
+
+    /// Translate the transcription into LANGUAGE (e.g. "de" or "German")
+    /// with a language model before it is output
+    #[arg(long, value_name = "LANGUAGE")]
+    translate_to: Option<String>,
+
+    /// Provider of the language model for --summarize and --translate-to:
+    /// openai, groq, or mistral [default: the first one with a key]
+    #[arg(long, value_name = "PROVIDER")]
+    llm: Option<String>,
+
+    /// Language model to use instead of the provider's default
+    #[arg(long, value_name = "MODEL")]
+    llm_model: Option<String>,
+
+    /// Show what has been recognized so far while recording, by sending the
+    /// audio to the first provider every few seconds (each time a full,
+    /// billed request)
+    #[arg(long)]
+    live: bool,
+
+    /// Type the words --live recognizes into the focused window as soon as
+    /// they stop changing, so the text appears while you're still talking;
+    /// the rest is typed when recording stops. Typed words are never
+    /// corrected. Implies --live
+    #[arg(long, conflicts_with_all = ["continuous", "enter"])]
+    live_type: bool,
+
+    /// Show captions of the dictation in a strip at the bottom of the screen
+    /// (Wayland compositors with the layer shell); with --live they follow
+    /// along while you speak
+    #[arg(long)]
+    overlay: bool,
+
+    /// Comma-separated names and terms the provider should expect, e.g.
+    /// "kubectl,Podgorny"; sent as the prompt to OpenAI and Groq
+    #[arg(long, value_name = "TERMS", value_delimiter = ',')]
+    vocabulary: Vec<String>,
+
+    /// Rewrite words and phrases in each transcription with the rules in
+    /// FILE, one `from => to` per line [default: replacements.txt next to
+    /// config.toml, if it exists]
+    #[arg(long, value_name = "FILE")]
+    replacements: Option<PathBuf>,
+
+    /// Keep recording after each pause: every utterance is transcribed and
+    /// typed as soon as you stop speaking, until the session is stopped or
+    /// cancelled
+    #[arg(long)]
+    continuous: bool,
+
+    /// Stop recording on its own after this long, e.g. "30s", "5m", or
+    /// "1m30s", for scripted capture; the usual ways to stop still work
+    #[arg(long, value_name = "TIME", value_parser = parse_duration)]
+    duration: Option<std::time::Duration>,
+
+    /// Feed this audio file through the pipeline as if it had just been
+    /// recorded, instead of using the microphone
+    #[arg(long, value_name = "FILE")]
+    replay: Option<PathBuf>,
+
+    /// Check the providers (as `rpdictation check` does) before recording,
+    /// or before the daemon starts, and refuse to go on if one fails
+    #[arg(long)]
+    preflight: bool,
+
+    /// Look up API keys not given otherwise in the Secret Service keyring
+    /// (through secret-tool), under service=rpdictation provider=NAME
+    #[arg(long)]
+    keyring: bool,
+
+    /// Send API requests through this proxy (http://, https://, or
+    /// socks5://) instead of the one from $HTTPS_PROXY
+    #[arg(long, value_name = "URL")]
+    proxy: Option<String>,
+
+    /// Trust the CA certificates in this PEM file, e.g. a corporate proxy's,
+    /// in addition to the system ones
+    #[arg(long, value_name = "FILE")]
+    ca_cert: Option<PathBuf>,
+
+    /// How long to wait for a provider's answer, on top of one second per
+    /// second of audio (which covers the upload of long recordings)
+    #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+    api_timeout: u64,
+
+    /// Ask before sending a recording whose transcription would cost more
+    /// than USD dollars, e.g. one left running by accident; without a
+    /// terminal or notification buttons to ask with, it is dropped
+    #[arg(long, value_name = "USD")]
+    max_cost: Option<f64>,
+
+    /// RMS level (0-32767) above which continuous mode treats audio as
+    /// speech; raise it for a noisy microphone
+    #[arg(long, value_name = "LEVEL", default_value_t = 500.0)]
+    vad_threshold: f64,
+
+    /// Print a JSON object describing each finished dictation on stdout and
+    /// send all progress output to stderr
+    #[arg(long)]
+    json: bool,
+
+    /// Print nothing but the final transcript on stdout: no banner, timer,
+    /// or notifications
+    #[arg(long, visible_alias = "print-only", conflicts_with = "json")]
+    quiet: bool,
+
+    /// Don't show desktop notifications (same as --notifier none)
+    #[arg(long)]
+    no_notifications: bool,
+
+    /// Directory for the stop FIFO, PID file, and daemon socket (default:
+    /// $XDG_RUNTIME_DIR/rpdictation)
+    #[arg(long, value_name = "DIR")]
+    runtime_dir: Option<PathBuf>,
+
+    /// Keep this session's FIFO, PID file, socket, and D-Bus name apart from
+    /// other sessions, so several can record at once (e.g. one per
+    /// microphone); control commands take the same name
+    #[arg(long, value_name = "NAME")]
+    session: Option<String>,
+
+    /// Where notifications go
+    #[arg(long, value_enum, value_name = "KIND", default_value_t = notifier::Kind::Auto)]
+    notifier: notifier::Kind,
+
+    /// Input device to record from (default: the system default input)
+    #[arg(long, value_name = "NAME")]
+    device: Option<String>,
+
+    /// Keep the audio and transcript of every dictation in
+    /// $XDG_DATA_HOME/rpdictation/history
+    #[arg(long)]
+    history: bool,
+
+    /// Store TAG with each dictation kept by --history, to list them again
+    /// with `history --tag TAG`; repeat or separate with commas for more
+    #[arg(long, value_name = "TAG", value_delimiter = ',')]
+    tag: Vec<String>,
+
+    /// Store NAME with each dictation kept by --history, e.g. "standup"
+    #[arg(long, value_name = "NAME")]
+    name: Option<String>,
+
+    /// Delete dictations from the history once they are this old, e.g.
+    /// "90d"; checked on startup and by `rpdictation gc`
+    #[arg(long, value_name = "TIME", value_parser = parse_duration)]
+    keep_transcripts: Option<std::time::Duration>,
+
+    /// Delete the audio of dictations in the history once they are this
+    /// old, e.g. "7d", keeping the transcripts
+    #[arg(long, value_name = "TIME", value_parser = parse_duration)]
+    keep_audio: Option<std::time::Duration>,
+
+    /// Daemon only: transcribe audio files dropped into this directory and
+    /// write each transcript next to its file as <name>.txt (needs ffmpeg)
+    #[arg(long, value_name = "DIR")]
+    watch_dir: Option<PathBuf>,
+
+    /// Daemon only: register a global shortcut that toggles dictation through
+    /// the desktop portal, e.g. "CTRL+ALT+d" (the desktop may let you pick a
+    /// different key)
+    #[arg(long, value_name = "TRIGGER")]
+    global_shortcut: Option<String>,
+
+    /// Daemon only: record while this key is held and transcribe on release,
+    /// e.g. KEY_RIGHTCTRL (reads /dev/input, needs the 'input' group)
+    #[arg(long, value_name = "KEY")]
+    push_to_talk: Option<String>,
+
+    /// Daemon only: start dictating when this phrase is heard, e.g. "start
+    /// dictation". Listens through the --wyoming-server service, so nothing
+    /// said near the microphone leaves your network
+    #[arg(long, value_name = "PHRASE")]
+    wake_word: Option<String>,
+
+    /// Daemon only: push recording state and transcripts as JSON to
+    /// WebSocket clients connecting to ADDR, e.g. 127.0.0.1:8765
+    #[arg(long, value_name = "ADDR")]
+    websocket: Option<String>,
+
+    /// Daemon only: serve Prometheus metrics (dictations, audio seconds,
+    /// provider latency, failures, cost) at http://ADDR/metrics, e.g.
+    /// 127.0.0.1:9464
+    #[arg(long, value_name = "ADDR")]
+    metrics: Option<String>,
+
+    /// Export the steps of each dictation (record, encode, transcribe,
+    /// rewrite, output) as OpenTelemetry spans to the OTLP/HTTP collector at
+    /// URL, e.g. http://localhost:4318
+    #[arg(long, value_name = "URL")]
+    otlp_endpoint: Option<String>,
 }
 
 #[derive(Subcommand, Clone)]
@@ -225,693 +546,503 @@ enum Command {
     Start,
     /// Stop a running recording
     Stop,
+    /// Cancel a running recording, discarding the audio without transcribing
+    Cancel,
     /// Toggle recording (start if not running, stop if running)
     Toggle,
+    /// Run in the background with the microphone and provider clients kept
+    /// warm; start/stop/toggle/cancel are then forwarded to it
+    Daemon,
+    /// Make a cheap request to every provider in the chain to check the API
+    /// keys and models, without recording anything
+    Check,
+    /// List the input devices to pick from with --device; the default one
+    /// is marked with *
+    Devices,
+    /// Print whether a recording is in progress and for how long
+    Status {
+        /// Output format
+        #[arg(long, value_enum, default_value = "plain")]
+        format: status::StatusFormat,
+        /// Keep running and print a new line whenever the status changes
+        #[arg(long)]
+        follow: bool,
+        /// Add the language the daemon transcribes, e.g. `idle cs`
+        #[arg(long)]
+        show_language: bool,
+    },
+    /// Print the language a running daemon transcribes, or switch it, e.g.
+    /// `rpdictation lang cs`
+    Lang { language: Option<String> },
+    /// Show a tray icon (StatusNotifierItem) with the recording state and
+    /// menu actions to start, stop, or cancel
+    Tray,
+    /// Dictate in a full-screen terminal interface with an input level
+    /// meter, the text recognized so far, and the finished dictations
+    Tui,
+    /// Dictate in a small window with a record button, a microphone picker,
+    /// and the transcripts
+    Gui,
+    /// Transcribe an audio file (WAV, or anything ffmpeg reads) instead of
+    /// recording. Nothing is typed unless --output says so; --quiet prints
+    /// just the text.
+    File {
+        /// The audio file
+        path: PathBuf,
+        /// Transcript format; timestamped needs a provider that returns
+        /// timestamps (openai, groq)
+        #[arg(long, value_enum, default_value = "text")]
+        format: file::FileFormat,
+        /// Write SubRip subtitles, with the time each line was spoken, to
+        /// FILE instead (needs a provider that returns timestamps: openai,
+        /// groq)
+        #[arg(long, value_name = "FILE")]
+        output_srt: Option<PathBuf>,
+        /// Like --output-srt, but WebVTT
+        #[arg(long, value_name = "FILE")]
+        output_vtt: Option<PathBuf>,
+    },
+    /// Delete what the history keeps beyond --keep-transcripts and
+    /// --keep-audio now
+    Gc,
+    /// List the dictations kept by --history, or work with them
+    History {
+        #[command(subcommand)]
+        action: Option<HistoryCommand>,
+        /// Only those stored with this tag; repeat for ones with all of them
+        #[arg(long, value_name = "TAG")]
+        tag: Vec<String>,
+        /// Only those stored with this name
+        #[arg(long, value_name = "NAME")]
+        name: Option<String>,
+    },
 }
 
-async fn main_async() -> Result<()> {
-    let args = Args::parse();
-
-    // Determine effective command (default to Start)
-    let command = args.command.clone().unwrap_or(Command::Start);
+#[derive(Subcommand, Clone)]
+enum HistoryCommand {
+    /// Export recordings paired with their final text as JSONL, for
+    /// fine-tuning a speech model. A hand-corrected transcript saved as
+    /// <id>.txt in the history directory replaces the provider's text.
+    ExportTraining {
+        /// Write to this file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Export the transcripts with their time, duration, and cost as a
+    /// document, for archiving or reporting
+    Export {
+        #[arg(long, value_enum, default_value = "md")]
+        format: history::ExportFormat,
+        /// Only dictations from this day on, e.g. 2025-01-01
+        #[arg(long, value_name = "DATE")]
+        since: Option<chrono::NaiveDate>,
+        /// Write to this file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
 
-    match command {
-        Command::Stop => {
-            return stop_recording().await;
-        }
-        Command::Toggle => {
-            if is_instance_running().await.is_some() {
-                return stop_recording().await;
-            }
-            // Fall through to start recording
-        }
-        Command::Start => {
-            if let Some(pid) = is_instance_running().await {
-                anyhow::bail!("Already running (pid {})", pid);
-            }
-            // Fall through to start recording
+// Helper to get a static OpenAI API key from the CLI arg, the environment,
+// or the config file; the key command, the keyring, and the token endpoint
+// come after it (see rpdictation::setup)
+fn get_openai_api_key(args: &Args) -> Option<String> {
+    // Check CLI argument first
+    if let Some(ref key) = args.openai_api_key {
+        if !key.is_empty() {
+            return Some(key.clone());
         }
     }
-
-    async fn command_exists(name: &str) -> bool {
-        tokio::process::Command::new("which")
-            .arg(name)
-            .stdout(std::process::Stdio::null())
-            .status()
-            .await
-            .map(|s| s.success())
-            .unwrap_or(false)
-    }
-
-    if let Some(ref typer) = args.typer {
-        if !command_exists(typer).await {
-            eprintln!("{} command not found. Please install it.", typer);
-            return Ok(());
+    // Check environment variable
+    if let Ok(key) = env::var("OPENAI_API_KEY") {
+        if !key.is_empty() {
+            return Some(key);
         }
     }
+    // Fall back to the config file
+    args.settings
+        .openai_api_key
+        .clone()
+        .filter(|k| !k.is_empty())
+}
 
-    // Helper to get OpenAI API key from CLI arg or environment
-    fn get_openai_api_key(args: &Args) -> Option<String> {
-        // Check CLI argument first
-        if let Some(ref key) = args.openai_api_key {
-            if !key.is_empty() {
-                return Some(key.clone());
-            }
+// Helper to get a static Mistral API key from the CLI arg, the environment,
+// or the config file; the key command, the keyring, and the token endpoint
+// come after it (see rpdictation::setup)
+fn get_mistral_api_key(args: &Args) -> Option<String> {
+    if let Some(ref key) = args.mistral_api_key {
+        if !key.is_empty() {
+            return Some(key.clone());
         }
-        // Check environment variable
-        if let Ok(key) = env::var("OPENAI_API_KEY") {
-            if !key.is_empty() {
-                return Some(key);
-            }
+    }
+    if let Ok(key) = env::var("MISTRAL_API_KEY") {
+        if !key.is_empty() {
+            return Some(key);
         }
-        None
     }
+    // Fall back to the config file
+    args.settings
+        .mistral_api_key
+        .clone()
+        .filter(|k| !k.is_empty())
+}
 
-    // Helper to get Mistral API key from CLI arg or environment
-    fn get_mistral_api_key(args: &Args) -> Option<String> {
-        if let Some(ref key) = args.mistral_api_key {
-            if !key.is_empty() {
-                return Some(key.clone());
-            }
+// Helper to get a static Groq API key from the CLI arg, the environment,
+// or the config file; the key command, the keyring, and the token endpoint
+// come after it (see rpdictation::setup)
+fn get_groq_api_key(args: &Args) -> Option<String> {
+    if let Some(ref key) = args.groq_api_key {
+        if !key.is_empty() {
+            return Some(key.clone());
         }
-        if let Ok(key) = env::var("MISTRAL_API_KEY") {
-            if !key.is_empty() {
-                return Some(key);
-            }
+    }
+    if let Ok(key) = env::var("GROQ_API_KEY") {
+        if !key.is_empty() {
+            return Some(key);
         }
-        None
     }
+    // Fall back to the config file
+    args.settings.groq_api_key.clone().filter(|k| !k.is_empty())
+}
 
-    // Helper to get Groq API key from CLI arg or environment
-    fn get_groq_api_key(args: &Args) -> Option<String> {
-        if let Some(ref key) = args.groq_api_key {
-            if !key.is_empty() {
-                return Some(key.clone());
-            }
+// Helper to get the token endpoint URL from the CLI arg (or the config file)
+// or the environment
+fn get_token_endpoint(args: &Args) -> Option<String> {
+    if let Some(ref url) = args.token_endpoint {
+        if !url.is_empty() {
+            return Some(url.clone());
         }
-        if let Ok(key) = env::var("GROQ_API_KEY") {
-            if !key.is_empty() {
-                return Some(key);
-            }
+    }
+    if let Ok(url) = env::var("RPDICTATION_TOKEN_ENDPOINT") {
+        if !url.is_empty() {
+            return Some(url);
         }
-        None
     }
+    None
+}
 
-    let build_provider = |name: &str| -> Result<Box<dyn TranscriptionProvider>> {
-        match name {
-            "openai" => {
-                let api_key = get_openai_api_key(&args).context(
-                    "OPENAI_API_KEY environment variable not set or --openai-api-key not provided",
-                )?;
-                Ok(Box::new(OpenAIProvider::new(api_key)))
-            }
-            "mistral" => {
-                let api_key = get_mistral_api_key(&args).context(
-                    "MISTRAL_API_KEY environment variable not set or --mistral-api-key not provided",
-                )?;
-                Ok(Box::new(MistralProvider::new(api_key)))
-            }
-            "groq" => {
-                let api_key = get_groq_api_key(&args).context(
-                    "GROQ_API_KEY environment variable not set or --groq-api-key not provided",
-                )?;
-                Ok(Box::new(GroqProvider::new(api_key)))
-            }
-            "google" => Ok(Box::new(GoogleProvider::new(
-                args.google_api_key.clone(),
-                args.language.clone(),
-            ))),
-            other => anyhow::bail!(
-                "Invalid provider '{}'. Valid options: openai, mistral, groq, google",
-                other
+impl Args {
+    /// What the library's builders need, from the flags, the environment,
+    /// and the config file
+    fn options(&self) -> Options {
+        // The default file is optional; one given explicitly has to exist
+        let replacements = self.replacements.clone().or_else(|| {
+            let path = config::default_path().with_file_name("replacements.txt");
+            path.exists().then_some(path)
+        });
+        let key_commands = ["openai", "mistral", "groq"]
+            .into_iter()
+            .filter_map(|p| Some((p.to_string(), self.settings.key_command(p)?.to_string())))
+            .collect();
+        Options {
+            provider: self.provider.clone(),
+            openai_api_key: get_openai_api_key(self),
+            mistral_api_key: get_mistral_api_key(self),
+            groq_api_key: get_groq_api_key(self),
+            google_api_key: self.google_api_key.clone(),
+            key_commands,
+            keyring: self.keyring,
+            token_endpoint: get_token_endpoint(self),
+            wyoming_server: self.wyoming_server.clone(),
+            language: self.language.clone(),
+            vocabulary: self.vocabulary.clone(),
+            typer: self.typer.clone(),
+            output: self.output.clone(),
+            paste: self.paste,
+            paste_key: self.paste_key,
+            type_delay: self.type_delay,
+            enter: self.enter,
+            append: self.append,
+            track_window: self.track_window,
+            strict_focus: self.strict_focus,
+            apps: self.settings.app.clone(),
+            clipboard: self.clipboard,
+            primary: self.primary,
+            osc52: self.osc52,
+            history: self.history,
+            name: self.name.clone(),
+            tag: self.tag.clone(),
+            exec: self.exec.clone(),
+            webhook: self.webhook.clone(),
+            mqtt: self.mqtt.clone(),
+            append_file: self.append_file.clone(),
+            append_header: self.append_header.clone(),
+            journal: self.journal.clone(),
+            journal_heading: self.journal_heading.clone(),
+            journal_template: self.journal_template.clone(),
+            org: self.org.clone(),
+            org_headline: self.org_headline.clone(),
+            nvim: self.nvim.clone(),
+            tmux: self.tmux.clone(),
+            quiet: self.quiet,
+            otlp_endpoint: self.otlp_endpoint.clone(),
+            replacements,
+            spell: self.spell,
+            numbers: self.numbers,
+            date_format: self.date_format.clone(),
+            case: self.case,
+            strip_period: self.strip_period,
+            collapse_whitespace: self.collapse_whitespace,
+            profanity: self.profanity,
+            voice_commands: self.voice_commands,
+            routes: self.settings.route.clone(),
+            summarize: self.summarize,
+            translate_to: self.translate_to.clone(),
+            llm: self.llm.clone(),
+            llm_model: self.llm_model.clone(),
+            confirm: self.confirm,
+            edit: self.edit,
+            max_cost: self.max_cost,
+            timings: self.timings,
+            // Nobody watches the daemon's terminal, and the interfaces have
+            // theirs to themselves
+            background: matches!(
+                self.command,
+                Some(Command::Daemon | Command::Tui | Command::Gui)
             ),
+            delay: self.delay,
+            device: self.device.clone(),
+            replay: self.replay.clone(),
+            duration: self.duration,
+            continuous: self.continuous,
+            vad_threshold: self.vad_threshold,
+            live: self.live,
+            live_type: self.live_type,
+            overlay: self.overlay,
         }
-    };
-
-    // Build the provider chain. A comma-separated list means "try these in order,
-    // falling back to the next on failure". Auto-detection yields a single provider.
-    let providers: Vec<Box<dyn TranscriptionProvider>> = match args.provider.as_deref() {
-        Some(list) => {
-            let mut providers: Vec<Box<dyn TranscriptionProvider>> = Vec::new();
-            for name in list.split(',').map(str::trim).filter(|s| !s.is_empty()) {
-                providers.push(build_provider(name)?);
-            }
-            if providers.is_empty() {
-                anyhow::bail!("--provider list is empty");
-            }
-            let names: Vec<&str> = providers.iter().map(|p| p.name()).collect();
-            eprintln!("Provider chain: {}", names.join(" -> "));
-            providers
-        }
-        None => {
-            // Auto-detect: build a best-effort fallback chain from every provider
-            // that has an API key available, ordered cheapest-first. Google is
-            // always appended last since it has a built-in default key.
-            let mut providers: Vec<Box<dyn TranscriptionProvider>> = Vec::new();
-            if let Some(api_key) = get_groq_api_key(&args) {
-                providers.push(Box::new(GroqProvider::new(api_key)));
-            }
-            if let Some(api_key) = get_openai_api_key(&args) {
-                providers.push(Box::new(OpenAIProvider::new(api_key)));
-            }
-            if let Some(api_key) = get_mistral_api_key(&args) {
-                providers.push(Box::new(MistralProvider::new(api_key)));
-            }
-            providers.push(Box::new(GoogleProvider::new(
-                args.google_api_key.clone(),
-                args.language.clone(),
-            )));
-            let names: Vec<&str> = providers.iter().map(|p| p.name()).collect();
-            eprintln!("Auto-detected provider chain: {}", names.join(" -> "));
-            providers
-        }
-    };
-
-    // Initialize focus provider if tracking is enabled
-    let focus_provider: Option<Box<dyn FocusProvider>> = if args.track_window {
-        match focus::detect_focus_provider().await {
-            Some(fp) => {
-                eprintln!("Using focus provider: {}", fp.name());
-                Some(fp)
-            }
-            None => {
-                eprintln!("Warning: --track-window enabled but no compositor detected, focus tracking disabled");
-                None
-            }
-        }
-    } else {
-        None
-    };
-
-    // Capture focused window at recording start
-    let saved_window_id = if let Some(ref fp) = focus_provider {
-        match fp.get_focused_window().await {
-            Ok(wid) => {
-                if let Some(ref w) = wid {
-                    eprintln!("Captured window ID: {:?}", w);
-                }
-                wid
-            }
-            Err(e) => {
-                eprintln!("Warning: Failed to capture focused window: {}", e);
-                None
-            }
-        }
-    } else {
-        None
-    };
-
-    // Initialize audio host and device
-    let host = cpal::default_host();
-    let device = host
-        .default_input_device()
-        .context("Failed to get default input device")?;
-
-    // Collect raw samples in memory
-    let samples: Arc<Mutex<Vec<i16>>> = Arc::new(Mutex::new(Vec::new()));
-
-    // Configure input stream
-    let config = cpal::StreamConfig {
-        channels: CHANNELS,
-        sample_rate: cpal::SampleRate(SAMPLE_RATE),
-        buffer_size: cpal::BufferSize::Default,
-    };
-
-    let samples_clone = Arc::clone(&samples);
-    let stream = device.build_input_stream(
-        &config,
-        move |data: &[f32], _: &_| {
-            if let Ok(mut guard) = samples_clone.try_lock() {
-                guard.extend(data.iter().map(|&s| (s * i16::MAX as f32) as i16));
-            }
-        },
-        move |err| eprintln!("An error occurred on stream: {}", err),
-        None,
-    )?;
-
-    stream.play()?;
-
-    if tokio::fs::metadata(FIFO_PATH).await.is_ok() {
-        tokio::fs::remove_file(FIFO_PATH).await?;
     }
-    nix::unistd::mkfifo(FIFO_PATH, nix::sys::stat::Mode::S_IRWXU)?;
+}
 
-    // Write PID file
-    let pid_path = get_pid_path();
-    tokio::fs::write(&pid_path, std::process::id().to_string()).await?;
+/// A length of time such as "30s", "5m", "1h", "7d", or "1m30s"; a bare
+/// number is seconds
+fn parse_duration(s: &str) -> std::result::Result<std::time::Duration, String> {
+    let mut seconds = 0;
+    let mut number = String::new();
+    for c in s.trim().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'd' => 86400,
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(format!("unknown unit '{}' (use d, h, m, or s)", c)),
+        };
+        let n: u64 = number
+            .parse()
+            .map_err(|_| format!("no number before '{}'", c))?;
+        seconds += n * unit;
+        number.clear();
+    }
+    if !number.is_empty() {
+        seconds += number.parse::<u64>().map_err(|e| e.to_string())?;
+    }
+    if seconds == 0 {
+        return Err("must be at least one second".to_string());
+    }
+    Ok(std::time::Duration::from_secs(seconds))
+}
 
-    let stdin_is_tty = std::io::stdin().is_terminal();
+async fn main_async() -> Result<()> {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let language_from_cli =
+        matches.value_source("language") == Some(clap::parser::ValueSource::CommandLine);
+    let mut settings = config::load(args.config.as_deref())?;
+    if let Some(ref profile) = args.profile {
+        settings = settings.with_profile(profile)?;
+    }
+    settings.apply(&mut args, &matches);
+    args.settings = settings;
 
-    println!("Recording... Stop with:");
-    println!("- Run: rpdictation stop, or");
-    if stdin_is_tty {
-        println!("- Press Enter, or");
+    if args.json {
+        output::set_mode(output::Mode::Json);
+    } else if args.quiet {
+        output::set_mode(output::Mode::Quiet);
     }
-    println!("- Run: echo x > {}, or", FIFO_PATH);
-    println!("- Click the notification");
-    println!();
-
-    let cancel_token = CancellationToken::new();
-
-    let start_time = tokio::time::Instant::now();
-
-    let timer_handle = tokio::spawn({
-        let cancel_token = cancel_token.clone();
-        async move {
-            let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
-            loop {
-                tokio::select! {
-                    _ = cancel_token.cancelled() => { break; }
-                    _ = interval.tick() => {
-                        let elapsed = start_time.elapsed();
-                        let minutes = elapsed.as_secs() / 60;
-                        let seconds = elapsed.as_secs() % 60;
-
-                        // Update notification (fire-and-forget, uses same hint to replace)
-                        let _ = tokio::process::Command::new("notify-send")
-                            .args([
-                                "--hint=string:x-canonical-private-synchronous:rpdictation",
-                                "--expire-time=0",
-                            ])
-                            .arg(format!("Recording {:02}:{:02}", minutes, seconds))
-                            .spawn();
-
-                        // Keep terminal output
-                        print!("\rRecording length: {:02}:{:02}", minutes, seconds);
-                        let _ = tokio::io::stdout().flush().await;
-                    }
-                }
-            }
-            eprintln!("timer exit");
-            Ok::<_, anyhow::Error>(())
+    let mut runtime_dir = args.runtime_dir.clone();
+    if let Some(ref name) = args.session {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            anyhow::bail!(
+                "Invalid session name '{}': use letters, digits, '-', and '_'",
+                name
+            );
         }
-    });
+        let base = runtime_dir.unwrap_or_else(rpdictation::get_runtime_dir);
+        runtime_dir = Some(base.join("sessions").join(name));
+    }
+    if let Some(dir) = runtime_dir {
+        set_runtime_dir(dir);
+    }
+    providers::set_timeout(std::time::Duration::from_secs(args.api_timeout));
+    #[cfg(feature = "http")]
+    providers::configure_http(args.proxy.as_deref(), args.ca_cert.as_deref())?;
+    create_runtime_dir()?;
+    notifier::init(if args.no_notifications || args.quiet {
+        notifier::Kind::None
+    } else {
+        args.notifier
+    })
+    .await;
 
-    let (stdin_tx, mut stdin_rx) = tokio::sync::oneshot::channel::<()>();
-    let stdin_handle = tokio::spawn({
-        let cancel_token = cancel_token.clone();
-        async move {
-            if !stdin_is_tty {
-                // Not a TTY, just wait for cancellation
-                cancel_token.cancelled().await;
-                eprintln!("stdin exit (not a tty)");
-                return Ok::<_, anyhow::Error>(());
-            }
+    // Determine effective command (default to Start)
+    let command = args.command.clone().unwrap_or(Command::Start);
 
-            let mut stdin = tokio::io::BufReader::new(tokio::io::stdin());
-            let mut buf = String::new();
-            tokio::select! {
-                _ = cancel_token.cancelled() => {}
-                _ = stdin.read_line(&mut buf) => {
-                    stdin_tx.send(()).map_err(|_| anyhow::anyhow!("Failed to send stdin signal"))?;
-                }
-            }
-            eprintln!("stdin exit");
-            Ok::<_, anyhow::Error>(())
+    // Control commands go to a running daemon if there is one, otherwise to
+    // a one-shot recording process (see control::connect).
+    match command {
+        Command::Daemon => {
+            return daemon::run(args).await;
         }
-    });
-
-    let (fifo_tx, mut fifo_rx) = tokio::sync::oneshot::channel();
-    let fifo_handle = tokio::spawn({
-        let cancel_token = cancel_token.clone();
-        async move {
-            eprintln!("fifo open");
-            tokio::select! {
-                _ = cancel_token.cancelled() => {}
-                _ = tokio::fs::File::open(FIFO_PATH) => {
-                    fifo_tx.send(()).map_err(|_| anyhow::anyhow!("Failed to send fifo signal"))?;
-                }
-            }
-            /*
-            let mut fifo = File::open(FIFO_PATH).await?;
-            let mut buf = [0u8; 1];
-            eprintln!("fifo select");
-            tokio::select! {
-                _ = cancel_token.cancelled() => {}
-                /*_ = fifo.read(&mut buf) => {
-                    fifo_tx.send(()).map_err(|_| anyhow::anyhow!("Failed to send fifo signal"))?;
-                }*/
-            }
-            */
-            eprintln!("fifo exit");
-            Ok::<_, anyhow::Error>(())
+        Command::Check => {
+            return check::run(&args).await;
         }
-    });
-
-    let (notify_tx, mut notify_rx) = tokio::sync::oneshot::channel();
-    let notify_handle = tokio::spawn({
-        let mut proc_notify = tokio::process::Command::new("notify-send")
-            .args([
-                "--hint=string:x-canonical-private-synchronous:rpdictation",
-                "--expire-time=0",
-                "--wait",
-                "--action=stop=Stop",
-            ])
-            .arg("Recording 00:00")
-            .spawn()
-            .context("Failed to spawn notify-send")?;
-
-        let cancel_token = cancel_token.clone();
-        async move {
-            tokio::select! {
-                _ = cancel_token.cancelled() => {}
-                _ = proc_notify.wait() => {
-                    notify_tx.send(()).map_err(|_| anyhow::anyhow!("Failed to send notify signal"))?;
+        Command::Devices => {
+            let default = audio::default_input_device();
+            for name in audio::input_devices()? {
+                let is_default = default.as_deref() == Some(name.as_str());
+                if output::mode() == output::Mode::Json {
+                    println!(
+                        "{}",
+                        serde_json::json!({"name": name, "default": is_default})
+                    );
+                } else {
+                    println!("{} {}", if is_default { "*" } else { " " }, name);
                 }
             }
-            if let Some(pid) = proc_notify.id() {
-                let pid = nix::unistd::Pid::from_raw(pid as i32);
-                nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGINT)?;
-                proc_notify.wait().await?; // TODO: i have to keep this here - why?
-            }
-            //eprintln!("notify extra kill");
-            //proc_notify.kill().await?;
-            //proc_notify.wait().await?;
-            eprintln!("notify exit");
-            Ok::<_, anyhow::Error>(())
+            return Ok(());
         }
-    });
-
-    let (signal_tx, mut signal_rx) = tokio::sync::oneshot::channel();
-    let signal_handle = tokio::spawn({
-        let cancel_token = cancel_token.clone();
-        async move {
-            let mut sig =
-                signal(SignalKind::user_defined1()).context("Failed to create signal handler")?;
-            tokio::select! {
-                _ = cancel_token.cancelled() => {}
-                _ = sig.recv() => {
-                    signal_tx.send(()).ok();
-                }
-            }
-            eprintln!("signal exit");
-            Ok::<_, anyhow::Error>(())
+        Command::Status {
+            format,
+            follow,
+            show_language,
+        } => {
+            return status::run(format, follow, show_language).await;
         }
-    });
-
-    let source = tokio::select! {
-        _ = &mut stdin_rx => "stdin",
-        _ = &mut fifo_rx => "fifo",
-        _ = &mut notify_rx => "notify",
-        _ = &mut signal_rx => "signal",
-    };
-    eprintln!("Stopped by {}", source);
-
-    cancel_token.cancel();
-
-    /*
-        stdin_rx.close();
-        fifo_rx.close();
-        notify_rx.close();
-    */
-
-    eprintln!("joining");
-    //timer_handle.await??;
-    //stdin_handle.await??;
-    //fifo_handle.await??;
-    //notify_handle.await??;
-    let _ = tokio::try_join!(
-        timer_handle,
-        stdin_handle,
-        fifo_handle,
-        notify_handle,
-        signal_handle
-    )
-    .map_err(|_| anyhow::anyhow!("Failed to join"))?;
-    eprintln!("joined");
-
-    tokio::fs::remove_file(FIFO_PATH).await?;
-    let _ = tokio::fs::remove_file(get_pid_path()).await;
-
-    drop(stream);
-    send_notification("Analyzing audio...", false).await;
-
-    let samples = Arc::try_unwrap(samples)
-        .expect("samples Arc should have single owner after stream drop")
-        .into_inner()
-        .unwrap();
-
-    let duration_seconds = samples.len() as f64 / SAMPLE_RATE as f64;
-    let audio_duration = duration_seconds;
-    let size_mb = (samples.len() * BYTES_PER_SAMPLE) as f64 / (1024.0 * 1024.0);
-    println!(
-        "Recording length: {:.1} seconds ({:.1} MB)",
-        duration_seconds, size_mb
-    );
-
-    if duration_seconds < MIN_RECORDING_DURATION_SECONDS {
-        eprintln!(
-            "Recording too short ({:.1} seconds), discarding.",
-            duration_seconds
-        );
-        send_notification("Recording too short, discarding", true).await;
-        return Ok(());
-    }
-
-    let result: Result<(String, f64, usize)> = async {
-        let wav_bytes =
-            tokio::task::spawn_blocking(move || audio::samples_to_wav(&samples, SAMPLE_RATE))
-                .await
-                .context("WAV encoding task panicked")??;
-
-        let total = providers.len();
-        let mut text: Option<String> = None;
-        let mut succeeded_idx: Option<usize> = None;
-        let mut last_err: Option<anyhow::Error> = None;
-        for (i, p) in providers.iter().enumerate() {
-            let msg = if total == 1 {
-                format!("Transcribing ({})...", p.name())
-            } else if i == 0 {
-                format!("Transcribing ({}) [1/{}]...", p.name(), total)
-            } else {
-                format!("Retrying with {} [{}/{}]...", p.name(), i + 1, total)
+        Command::Lang { language } => {
+            let command = match language {
+                Some(language) => format!("lang {}", language),
+                None => "lang".to_string(),
             };
-            send_notification(&msg, false).await;
-            println!("\n{}", msg);
-
-            match p.transcribe(&wav_bytes, SAMPLE_RATE).await {
-                Ok(t) => {
-                    text = Some(t.trim().to_string());
-                    succeeded_idx = Some(i);
-                    break;
-                }
-                Err(e) => {
-                    eprintln!("Provider {} failed: {:#}", p.name(), e);
-                    last_err = Some(e);
-                }
+            let reply = daemon::request(&command)
+                .await
+                .context("No daemon running; give a one-shot recording --language instead")??;
+            println!("{}", reply);
+            return Ok(());
+        }
+        Command::Tray => {
+            #[cfg(feature = "tray")]
+            return tray::run(args.session.clone()).await;
+            #[cfg(not(feature = "tray"))]
+            anyhow::bail!("The tray icon needs rpdictation built with the tray feature");
+        }
+        Command::Tui => {
+            #[cfg(feature = "tui")]
+            return tui::run(args).await;
+            #[cfg(not(feature = "tui"))]
+            anyhow::bail!("The terminal interface needs rpdictation built with the tui feature");
+        }
+        Command::Gui => {
+            #[cfg(feature = "gui")]
+            return gui::run(args).await;
+            #[cfg(not(feature = "gui"))]
+            anyhow::bail!("The window needs rpdictation built with the gui feature");
+        }
+        Command::File {
+            path,
+            format,
+            output_srt,
+            output_vtt,
+        } => {
+            return file::run(args, &path, format, output_srt, output_vtt).await;
+        }
+        Command::Gc => {
+            if args.keep_transcripts.is_none() && args.keep_audio.is_none() {
+                anyhow::bail!("Nothing to clean up: set keep_transcripts or keep_audio");
             }
+            let pruned = history::prune(args.keep_transcripts, args.keep_audio).await?;
+            say!(
+                "Deleted {} dictations, and the audio of {} more.",
+                pruned.entries,
+                pruned.audio
+            );
+            return Ok(());
         }
-        let text = text.ok_or_else(|| {
-            last_err
-                .map(|e| e.context("all providers failed"))
-                .unwrap_or_else(|| anyhow::anyhow!("all providers failed"))
-        })?;
-        let succeeded_idx = succeeded_idx.expect("succeeded_idx set on success");
-
-        println!();
-        println!("Transcription:");
-        println!("{}", text);
-
-        if let Some(ref typer) = args.typer {
-            send_notification("Typing text...", false).await;
-            println!("\nTyping text using {}...", typer);
-
-            // Handle focus tracking if enabled
-            let restore_window_id = if let (Some(ref fp), Some(ref saved_wid)) =
-                (&focus_provider, &saved_window_id)
-            {
-                // Get current focused window
-                let current_wid = fp.get_focused_window().await.ok().flatten();
-
-                if current_wid.as_ref() != Some(saved_wid) {
-                    // Focus changed, need to switch back
-                    eprintln!(
-                        "Focus changed from {:?} to {:?}, switching back",
-                        saved_wid, current_wid
-                    );
-
-                    // Try to focus the original window
-                    match fp.set_focused_window(saved_wid).await {
-                        Ok(true) => {
-                            eprintln!("Switched focus to original window");
-                            // Remember current window for restoration after typing
-                            current_wid
-                        }
-                        Ok(false) => {
-                            eprintln!(
-                                "Warning: Failed to switch to original window (may be closed), typing into current"
-                            );
-                            None
-                        }
-                        Err(e) => {
-                            eprintln!("Warning: Error switching focus: {}, typing into current", e);
-                            None
-                        }
-                    }
-                } else {
-                    // Focus unchanged, no need to restore
-                    None
+        Command::History { action, tag, name } => {
+            return match action {
+                None => history::list(&tag, name.as_deref()).await,
+                Some(HistoryCommand::ExportTraining { output }) => {
+                    history::export_training(output.as_deref()).await
                 }
-            } else {
-                None
+                Some(HistoryCommand::Export {
+                    format,
+                    since,
+                    output,
+                }) => history::export(format, since, output.as_deref()).await,
             };
-
-            // Non-English forces paste mode because ydotool's direct-type
-            // strips diacritics at the evdev level.
-            // See: https://github.com/ReimuNotMoe/ydotool/issues/249
-            let paste = args.paste || !args.language.starts_with("en");
-
-            // Type the text (and optionally press Enter)
-            match typer.as_str() {
-                "wtype" => {
-                    if paste {
-                        let saved_clipboard = save_selection(false).await;
-                        let saved_primary = save_selection(true).await;
-
-                        tokio::process::Command::new("wl-copy")
-                            .args(["--", &text])
-                            .status()
-                            .await
-                            .context("Failed to run wl-copy")?;
-                        tokio::process::Command::new("wl-copy")
-                            .args(["--primary", "--", &text])
-                            .status()
-                            .await
-                            .context("Failed to run wl-copy --primary")?;
-
-                        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
-
-                        tokio::process::Command::new("wtype")
-                            .args(["-M", "shift", "-k", "Insert", "-m", "shift"])
-                            .status()
-                            .await
-                            .context("Failed to run wtype for Shift+Insert paste")?;
-
-                        if args.enter {
-                            tokio::process::Command::new("wtype")
-                                .args(["-k", "Return"])
-                                .status()
-                                .await
-                                .context("Failed to run wtype for Enter")?;
-                        }
-
-                        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-
-                        restore_selection(false, saved_clipboard).await.ok();
-                        restore_selection(true, saved_primary).await.ok();
-                    } else {
-                        let mut cmd = tokio::process::Command::new("wtype");
-                        cmd.arg(&text);
-                        if args.enter {
-                            cmd.arg("-k").arg("Return");
-                        }
-                        cmd.status().await.context("Failed to run wtype")?;
-                    }
-                }
-                "ydotool" => {
-                    // Shift+Insert is more universal than Ctrl+V (doesn't work
-                    // in all terminals/apps).
-                    if paste {
-                        let saved_clipboard = save_selection(false).await;
-                        let saved_primary = save_selection(true).await;
-
-                        // Set both CLIPBOARD and PRIMARY selections — Shift+Insert
-                        // pastes from PRIMARY in many apps (especially terminals),
-                        // while others paste from CLIPBOARD.
-                        tokio::process::Command::new("wl-copy")
-                            .args(["--", &text])
-                            .status()
-                            .await
-                            .context("Failed to run wl-copy")?;
-                        tokio::process::Command::new("wl-copy")
-                            .args(["--primary", "--", &text])
-                            .status()
-                            .await
-                            .context("Failed to run wl-copy --primary")?;
-
-                        // Small delay to ensure clipboard is ready
-                        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
-
-                        // Shift+Insert to paste (42=KEY_LEFTSHIFT, 110=KEY_INSERT)
-                        tokio::process::Command::new("ydotool")
-                            .args(["key", "42:1", "110:1", "110:0", "42:0"])
-                            .status()
-                            .await
-                            .context("Failed to run ydotool key for Shift+Insert paste")?;
-
-                        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-
-                        restore_selection(false, saved_clipboard).await.ok();
-                        restore_selection(true, saved_primary).await.ok();
-                    } else {
-                        tokio::process::Command::new("ydotool")
-                            .args(["type", "-d", "1", "--", &text])
-                            .status()
-                            .await
-                            .context("Failed to run ydotool")?;
-                    }
-                    if args.enter {
-                        tokio::process::Command::new("ydotool")
-                            .args(["key", "28:1", "28:0"])
-                            .status()
-                            .await
-                            .context("Failed to run ydotool key")?;
-                    }
-                }
-                _ => {
-                    eprintln!("Unknown typer '{}'. Supported: wtype, ydotool", typer);
-                    return Ok((text, audio_duration, succeeded_idx));
+        }
+        Command::Stop => {
+            let channel = control::connect()
+                .await
+                .ok_or(rpdictation::Error::NotRecording)?;
+            return send_control(&*channel, "stop").await;
+        }
+        Command::Cancel => {
+            let channel = control::connect()
+                .await
+                .ok_or(rpdictation::Error::NotRecording)?;
+            return send_control(&*channel, "cancel").await;
+        }
+        Command::Toggle => {
+            if let Some(channel) = control::connect().await {
+                if language_from_cli {
+                    switch_language(&args.language).await?;
                 }
+                return send_control(&*channel, "toggle").await;
             }
-
-            // Restore focus to the window that was focused before we switched
-            if let (Some(ref fp), Some(ref restore_wid)) = (&focus_provider, &restore_window_id) {
-                eprintln!("Restoring focus to {:?}", restore_wid);
-                if let Err(e) = fp.set_focused_window(restore_wid).await {
-                    eprintln!("Warning: Failed to restore focus: {}", e);
+            // Fall through to start recording
+        }
+        Command::Start => {
+            if let Some(channel) = control::connect().await {
+                if language_from_cli {
+                    switch_language(&args.language).await?;
                 }
+                return send_control(&*channel, "start").await;
             }
+            // Fall through to start recording
         }
-
-        Ok((text, audio_duration, succeeded_idx))
     }
-    .await;
 
-    match result {
-        Ok((text, audio_duration, succeeded_idx)) => {
-            // Show first ~50 chars of transcription in notification.
-            // Must use .chars().count() instead of .len() because non-English
-            // text (e.g. Czech ě, ř, ž) uses multi-byte UTF-8 characters —
-            // slicing by byte index would panic at a non-char boundary.
-            let preview = if text.chars().count() > 50 {
-                format!("{}...", text.chars().take(50).collect::<String>())
-            } else {
-                text.clone()
-            };
-            send_notification(&format!("Done: {}", preview), true).await;
-
-            println!();
-            println!("Audio duration: {:.1} seconds", duration_seconds);
-            if let Some(cost_per_min) = providers[succeeded_idx].cost_per_minute() {
-                let minutes = (audio_duration / 60.0).ceil();
-                let cost = minutes * cost_per_min;
-                println!("Cost: ${:.4}", cost);
-            }
-        }
-        Err(e) => {
-            send_notification(&format!("Error: {}", e), true).await;
-            return Err(e);
-        }
+    spawn_prune(&args);
+    if args.preflight {
+        check::run(&args).await?;
     }
+    record::record_once(&args.options()).await
+}
 
-    eprintln!("exit");
-    Ok(())
+/// Apply --keep-transcripts and --keep-audio in the background, so that
+/// starting to record isn't held up by it
+fn spawn_prune(args: &Args) {
+    if args.keep_transcripts.is_none() && args.keep_audio.is_none() {
+        return;
+    }
+    let (keep_transcripts, keep_audio) = (args.keep_transcripts, args.keep_audio);
+    tokio::spawn(async move {
+        if let Err(e) = history::prune(keep_transcripts, keep_audio).await {
+            eprintln!("Warning: Failed to clean up the history: {:#}", e);
+        }
+    });
 }
 
 fn main() {
     // Load .env file before starting async runtime (blocking but only at startup)
     if std::path::Path::new(".env").exists() {
-        println!("loading environment from .env");
+        eprintln!("loading environment from .env");
         if let Err(e) = dotenvy::dotenv() {
             eprintln!("Warning: Failed to load .env file: {}", e);
         }
@@ -930,6 +1061,7 @@ fn main() {
     eprintln!("main exit");
 
     if let Err(e) = result {
-        eprintln!("Error: {}", e);
+        eprintln!("Error: {:#}", e);
+        std::process::exit(rpdictation::error::exit_code(&e));
     }
 }
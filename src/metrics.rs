@@ -0,0 +1,258 @@
+//! Where the time of a dictation goes, and counters of all of them for
+//! monitoring
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::sync::{Mutex, OnceLock};
+
+/// Seconds spent in each step of a dictation after recording stopped. Steps
+/// a dictation didn't go through are 0.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Timings {
+    /// Getting the recorded audio once recording was stopped
+    pub capture: f64,
+    /// Encoding it as WAV
+    pub encode: f64,
+    /// Sending it to the provider that transcribed it; 0 for providers not
+    /// reached over HTTP, whose upload counts as `provider`
+    pub upload: f64,
+    /// Waiting for the providers to answer, including ones that failed
+    pub provider: f64,
+    /// Voice commands, post-processing, and rewriting (summaries,
+    /// translation)
+    pub rewrite: f64,
+    /// Waiting for the user to agree to the cost, or to accept or edit the
+    /// text
+    pub confirm: f64,
+    /// Outputting it: typing, the clipboard, and the rest
+    pub output: f64,
+}
+
+impl Timings {
+    pub fn total(&self) -> f64 {
+        self.capture
+            + self.encode
+            + self.upload
+            + self.provider
+            + self.rewrite
+            + self.confirm
+            + self.output
+    }
+}
+
+/// One line, e.g. "capture 0.1 s, encode 0.2 s, ..., total 7.9 s"
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, seconds) in [
+            ("capture", self.capture),
+            ("encode", self.encode),
+            ("upload", self.upload),
+            ("provider", self.provider),
+            ("rewrite", self.rewrite),
+            ("confirm", self.confirm),
+            ("output", self.output),
+        ] {
+            write!(f, "{} {:.1} s, ", name, seconds)?;
+        }
+        write!(f, "total {:.1} s", self.total())
+    }
+}
+
+/// Upper bounds of the latency histogram buckets, in seconds
+const BUCKETS: &[f64] = &[0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 30.0, 60.0];
+
+/// Counts of observations up to each of [`BUCKETS`], as Prometheus
+/// histograms have them
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        for (bucket, le) in self.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= *le {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    /// The `_bucket`, `_sum`, and `_count` lines, with `labels` (`key="value"`
+    /// pairs joined with commas, or empty) on each
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let sep = if labels.is_empty() { "" } else { "," };
+        for (count, le) in self.buckets.iter().zip(BUCKETS) {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}{}le=\"{}\"}} {}",
+                name, labels, sep, le, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}{}le=\"+Inf\"}} {}",
+            name, labels, sep, self.count
+        );
+        let braced = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{}}}", labels)
+        };
+        let _ = writeln!(out, "{}_sum{} {}", name, braced, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, braced, self.count);
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    recordings: u64,
+    /// Finished dictations by how they ended
+    dictations: BTreeMap<&'static str, u64>,
+    audio_seconds: f64,
+    cost: f64,
+    /// Requests by provider and whether they succeeded
+    requests: BTreeMap<(String, bool), u64>,
+    provider_latency: BTreeMap<String, Histogram>,
+    latency: Histogram,
+}
+
+/// Counters of everything the dictation sessions of this process did, for
+/// the daemon's Prometheus endpoint
+#[derive(Default)]
+pub struct Metrics {
+    counters: Mutex<Counters>,
+}
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+/// The counters shared by every session in the process
+pub fn get() -> &'static Metrics {
+    METRICS.get_or_init(Metrics::default)
+}
+
+impl Metrics {
+    pub fn recording_started(&self) {
+        self.counters.lock().unwrap().recordings += 1;
+    }
+
+    /// A dictation ended without output: "cancelled", "too_short",
+    /// "over_cost", "rejected", "scratched", or "failed"
+    pub fn dictation_dropped(&self, result: &'static str) {
+        *self
+            .counters
+            .lock()
+            .unwrap()
+            .dictations
+            .entry(result)
+            .or_default() += 1;
+    }
+
+    /// A dictation was transcribed and output
+    pub fn dictation_completed(&self, audio_seconds: f64, cost: Option<f64>, timings: &Timings) {
+        let mut counters = self.counters.lock().unwrap();
+        *counters.dictations.entry("completed").or_default() += 1;
+        counters.audio_seconds += audio_seconds;
+        counters.cost += cost.unwrap_or(0.0);
+        counters.latency.observe(timings.total());
+    }
+
+    /// `provider` answered a transcription request, or failed to, after
+    /// `seconds`
+    pub fn provider_request(&self, provider: &str, ok: bool, seconds: f64) {
+        let mut counters = self.counters.lock().unwrap();
+        *counters
+            .requests
+            .entry((provider.to_string(), ok))
+            .or_default() += 1;
+        if ok {
+            counters
+                .provider_latency
+                .entry(provider.to_string())
+                .or_default()
+                .observe(seconds);
+        }
+    }
+
+    /// Everything counted so far in the Prometheus text format
+    pub fn render(&self) -> String {
+        let counters = self.counters.lock().unwrap();
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "# HELP rpdictation_recordings_total Recordings started.\n\
+             # TYPE rpdictation_recordings_total counter\n\
+             rpdictation_recordings_total {}",
+            counters.recordings
+        );
+        out.push_str(
+            "# HELP rpdictation_dictations_total Dictations finished, by how they ended.\n\
+             # TYPE rpdictation_dictations_total counter\n",
+        );
+        for (result, count) in &counters.dictations {
+            let _ = writeln!(
+                out,
+                "rpdictation_dictations_total{{result=\"{}\"}} {}",
+                result, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "# HELP rpdictation_audio_seconds_total Audio transcribed and output.\n\
+             # TYPE rpdictation_audio_seconds_total counter\n\
+             rpdictation_audio_seconds_total {}",
+            counters.audio_seconds
+        );
+        let _ = writeln!(
+            out,
+            "# HELP rpdictation_cost_dollars_total Estimated cost of the transcriptions.\n\
+             # TYPE rpdictation_cost_dollars_total counter\n\
+             rpdictation_cost_dollars_total {}",
+            counters.cost
+        );
+        out.push_str(
+            "# HELP rpdictation_provider_requests_total Transcription requests, by provider and result.\n\
+             # TYPE rpdictation_provider_requests_total counter\n",
+        );
+        for ((provider, ok), count) in &counters.requests {
+            let _ = writeln!(
+                out,
+                "rpdictation_provider_requests_total{{provider=\"{}\",result=\"{}\"}} {}",
+                escape(provider),
+                if *ok { "ok" } else { "error" },
+                count
+            );
+        }
+        out.push_str(
+            "# HELP rpdictation_provider_latency_seconds Time for a provider to transcribe a recording, upload included.\n\
+             # TYPE rpdictation_provider_latency_seconds histogram\n",
+        );
+        for (provider, histogram) in &counters.provider_latency {
+            histogram.render(
+                &mut out,
+                "rpdictation_provider_latency_seconds",
+                &format!("provider=\"{}\"", escape(provider)),
+            );
+        }
+        out.push_str(
+            "# HELP rpdictation_latency_seconds Time from stopping a recording to having it output.\n\
+             # TYPE rpdictation_latency_seconds histogram\n",
+        );
+        counters
+            .latency
+            .render(&mut out, "rpdictation_latency_seconds", "");
+        out
+    }
+}
+
+/// `value` escaped for a label in the Prometheus text format
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
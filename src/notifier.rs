@@ -0,0 +1,208 @@
+use async_trait::async_trait;
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::sync::OnceLock;
+use tokio::io::AsyncReadExt;
+use tokio_util::sync::CancellationToken;
+
+/// Where notifications go, selected with --notifier
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kind {
+    /// Desktop notifications when a session bus and notify-send are
+    /// available, the terminal otherwise
+    #[default]
+    Auto,
+    /// Desktop notifications through notify-send
+    Desktop,
+    /// One line per notification on stderr, for SSH and headless sessions
+    Terminal,
+    /// No notifications at all
+    None,
+}
+
+/// Something that can show the user what rpdictation is doing. Failing to
+/// show a notification is never an error: dictation goes on without it.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Show the notification, replacing the previous one. `expire` lets it
+    /// disappear after a few seconds instead of staying up.
+    async fn show(&self, summary: &str, body: Option<&str>, expire: bool);
+
+    /// Show a notification with buttons, given as `(key, label)`, and wait
+    /// for the user to pick one. Resolves to the picked key (an empty string
+    /// when the notification itself was clicked), or `None` once `cancel`
+    /// fires, which is all a backend without buttons ever does.
+    async fn ask(
+        &self,
+        summary: &str,
+        body: Option<&str>,
+        actions: &[(&str, &str)],
+        cancel: CancellationToken,
+    ) -> Option<String> {
+        let _ = (summary, body, actions);
+        cancel.cancelled().await;
+        None
+    }
+
+    /// Whether [`Notifier::ask`] shows buttons at all
+    fn buttons(&self) -> bool {
+        false
+    }
+
+    /// Whether to refresh the notification with fast-changing progress such
+    /// as the recording timer
+    fn live(&self) -> bool {
+        true
+    }
+}
+
+/// Desktop notifications through notify-send. All of them share one
+/// synchronous hint, so each replaces the last instead of piling up.
+pub struct DesktopNotifier;
+
+const SYNCHRONOUS_HINT: &str = "--hint=string:x-canonical-private-synchronous:rpdictation";
+
+#[async_trait]
+impl Notifier for DesktopNotifier {
+    async fn show(&self, summary: &str, body: Option<&str>, expire: bool) {
+        let expire_time = if expire { "5000" } else { "0" };
+        let _ = tokio::process::Command::new("notify-send")
+            .args([SYNCHRONOUS_HINT, &format!("--expire-time={}", expire_time)])
+            .arg(summary)
+            .args(body)
+            .status()
+            .await;
+    }
+
+    async fn ask(
+        &self,
+        summary: &str,
+        body: Option<&str>,
+        actions: &[(&str, &str)],
+        cancel: CancellationToken,
+    ) -> Option<String> {
+        let spawned = tokio::process::Command::new("notify-send")
+            .args([SYNCHRONOUS_HINT, "--expire-time=0", "--wait"])
+            .args(
+                actions
+                    .iter()
+                    .map(|(key, label)| format!("--action={}={}", key, label)),
+            )
+            .arg(summary)
+            .args(body)
+            .stdout(std::process::Stdio::piped())
+            .spawn();
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("Failed to spawn notify-send: {}", e);
+                cancel.cancelled().await;
+                return None;
+            }
+        };
+        let mut stdout = child.stdout.take();
+        let picked = tokio::select! {
+            _ = cancel.cancelled() => None,
+            status = child.wait() => match status {
+                // notify-send prints the key of the action that was picked
+                Ok(status) if status.success() => {
+                    let mut picked = String::new();
+                    if let Some(ref mut stdout) = stdout {
+                        stdout.read_to_string(&mut picked).await.ok();
+                    }
+                    Some(picked.trim().to_string())
+                }
+                // No notification daemon to show it: wait like a backend
+                // without buttons would
+                _ => {
+                    cancel.cancelled().await;
+                    None
+                }
+            },
+        };
+        if let Some(pid) = child.id() {
+            let pid = nix::unistd::Pid::from_raw(pid as i32);
+            nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGINT).ok();
+            child.wait().await.ok();
+        }
+        picked
+    }
+
+    fn buttons(&self) -> bool {
+        true
+    }
+}
+
+/// Notifications as lines on stderr. The terminal already shows the
+/// recording timer, so live progress is left out.
+pub struct TerminalNotifier;
+
+#[async_trait]
+impl Notifier for TerminalNotifier {
+    async fn show(&self, summary: &str, body: Option<&str>, _expire: bool) {
+        match body {
+            Some(body) => eprintln!("[rpdictation] {}: {}", summary, body),
+            None => eprintln!("[rpdictation] {}", summary),
+        }
+    }
+
+    fn live(&self) -> bool {
+        false
+    }
+}
+
+/// Shows nothing
+pub struct NullNotifier;
+
+#[async_trait]
+impl Notifier for NullNotifier {
+    async fn show(&self, _summary: &str, _body: Option<&str>, _expire: bool) {}
+
+    fn live(&self) -> bool {
+        false
+    }
+}
+
+static NOTIFIER: OnceLock<Box<dyn Notifier>> = OnceLock::new();
+
+/// Pick the backend for `kind`, once at startup
+pub async fn init(kind: Kind) {
+    let kind = match kind {
+        Kind::Auto if desktop_available().await => Kind::Desktop,
+        Kind::Auto => Kind::Terminal,
+        kind => kind,
+    };
+    let notifier: Box<dyn Notifier> = match kind {
+        Kind::Desktop => Box::new(DesktopNotifier),
+        Kind::Terminal => Box::new(TerminalNotifier),
+        Kind::Auto | Kind::None => Box::new(NullNotifier),
+    };
+    let _ = NOTIFIER.set(notifier);
+}
+
+/// The backend chosen by [`init`], or [`NullNotifier`] before that
+pub fn get() -> &'static dyn Notifier {
+    match NOTIFIER.get() {
+        Some(notifier) => notifier.as_ref(),
+        None => &NullNotifier,
+    }
+}
+
+/// notify-send needs a session bus to reach the notification daemon, which
+/// SSH and headless sessions usually don't have.
+async fn desktop_available() -> bool {
+    let bus = std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_some_and(|a| !a.is_empty())
+        || crate::get_user_runtime_dir().join("bus").exists();
+    bus && crate::command_exists("notify-send").await
+}
+
+pub async fn send_notification(message: &str, expire: bool) {
+    send_notification_with_body(message, None, expire).await;
+}
+
+/// Show (or replace, as all rpdictation notifications share one slot) the
+/// notification, with `body` as its second line.
+pub async fn send_notification_with_body(summary: &str, body: Option<&str>, expire: bool) {
+    get().show(summary, body, expire).await;
+}
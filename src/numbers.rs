@@ -0,0 +1,429 @@
+//! Numbers and dates as words or digits, in the form the target wants:
+//! digits for a spreadsheet, words for small numbers in prose, dates in a
+//! fixed format. Only English number words and month names are recognized.
+
+use anyhow::Result;
+use chrono::Datelike;
+use clap::ValueEnum;
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use std::fmt::Write;
+use std::sync::OnceLock;
+
+/// How numbers in a transcript are written
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NumberStyle {
+    /// As the provider wrote them
+    #[default]
+    Keep,
+    /// In digits ("twenty three" → "23", "forty-second" → "42nd"), except a
+    /// lone "one" and ordinals below ten, which are usually just words
+    Digits,
+    /// In digits from 10 up and in words below ("3" → "three"), as most
+    /// style guides have it
+    Prose,
+}
+
+const MONTHS: &[&str] = &[
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const SMALL: &[&str] = &[
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+];
+
+/// `text` with its numbers written in `style`
+pub fn normalize_numbers(text: &str, style: NumberStyle) -> String {
+    match style {
+        NumberStyle::Keep => text.to_string(),
+        NumberStyle::Digits => words_to_digits(text, 1),
+        NumberStyle::Prose => small_digits_to_words(&words_to_digits(text, 10)),
+    }
+}
+
+/// `text` with every date it mentions ("March fifth", "the 5th of March
+/// 2025") written in `format` (strftime syntax, e.g. `%Y-%m-%d`). A date
+/// without a year is taken to be in the current one. A month name before
+/// the day may be lowercase ("march fifth"), but after it has to be
+/// capitalized, which providers do, so "may" and "march" stay verbs.
+pub fn normalize_dates(text: &str, format: &str) -> String {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| {
+        let month = MONTHS.join("|");
+        let day = format!(
+            r"(?i:(?:[12]\d|3[01]|0?[1-9])(?:st|nd|rd|th)?|{})",
+            day_ordinals()
+        );
+        Regex::new(&format!(
+            r"\b(?:(?P<month1>(?i:{month}))\s+(?P<day1>{day})|(?i:the\s+)?(?P<day2>{day})\s+(?i:of\s+)?(?P<month2>{month}))(?:,?\s+(?P<year>\d{{4}}))?\b"
+        ))
+        .expect("valid date pattern")
+    });
+    let this_year = chrono::Local::now().year();
+    pattern
+        .replace_all(text, |caps: &Captures| {
+            let month = caps.name("month1").or(caps.name("month2")).unwrap();
+            let day = caps.name("day1").or(caps.name("day2")).unwrap();
+            let month = MONTHS
+                .iter()
+                .position(|m| m.eq_ignore_ascii_case(month.as_str()))
+                .unwrap() as u32
+                + 1;
+            let year = caps
+                .name("year")
+                .and_then(|y| y.as_str().parse().ok())
+                .unwrap_or(this_year);
+            let mut formatted = String::new();
+            match parse_day(day.as_str())
+                .and_then(|day| chrono::NaiveDate::from_ymd_opt(year, month, day))
+            {
+                Some(date) if write!(formatted, "{}", date.format(format)).is_ok() => formatted,
+                // Not a real date, e.g. "February 30th"
+                _ => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// Fail if `format` isn't a valid strftime format.
+pub fn check_date_format(format: &str) -> Result<()> {
+    use chrono::format::{Item, StrftimeItems};
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        anyhow::bail!("Invalid date format '{}'", format);
+    }
+    Ok(())
+}
+
+/// The ordinals from "first" to "thirty-first" as a regex alternation,
+/// longest first so "twenty first" isn't cut short at "twenty"
+fn day_ordinals() -> String {
+    let units = [
+        "first", "second", "third", "fourth", "fifth", "sixth", "seventh", "eighth", "ninth",
+    ];
+    let mut ordinals: Vec<String> = units.iter().map(|u| format!(r"twenty[\s-]{}", u)).collect();
+    ordinals.push(r"thirty[\s-]first".to_string());
+    ordinals.extend(
+        [
+            "tenth",
+            "eleventh",
+            "twelfth",
+            "thirteenth",
+            "fourteenth",
+            "fifteenth",
+            "sixteenth",
+            "seventeenth",
+            "eighteenth",
+            "nineteenth",
+            "twentieth",
+            "thirtieth",
+        ]
+        .map(str::to_string),
+    );
+    ordinals.extend(units.map(str::to_string));
+    ordinals.join("|")
+}
+
+/// The day of the month in "5", "5th", "fifth", or "twenty-first"
+fn parse_day(day: &str) -> Option<u32> {
+    if day.starts_with(|c: char| c.is_ascii_digit()) {
+        return day.trim_end_matches(char::is_alphabetic).parse().ok();
+    }
+    let mut number = Number::default();
+    for word in day.split(|c: char| c.is_whitespace() || c == '-') {
+        if !number.push(&word.to_lowercase()) {
+            return None;
+        }
+    }
+    Some(number.value() as u32)
+}
+
+/// Rewrite spelled-out numbers of at least `min` in digits.
+fn words_to_digits(text: &str, min: u64) -> String {
+    static WORD: OnceLock<Regex> = OnceLock::new();
+    let word = WORD.get_or_init(|| Regex::new(r"[A-Za-z]+").expect("valid word pattern"));
+    let words: Vec<_> = word.find_iter(text).collect();
+
+    let mut out = String::new();
+    let mut copied = 0;
+    let mut i = 0;
+    while i < words.len() {
+        // The longest run of words from here that makes one number
+        let mut number = Number::default();
+        let mut longest: Option<(usize, Number)> = None;
+        let mut j = i;
+        while j < words.len() && !number.complete {
+            let joined = j == i || {
+                let gap = &text[words[j - 1].end()..words[j].start()];
+                !gap.is_empty() && gap.chars().all(|c| c == ' ' || c == '-')
+            };
+            if !joined {
+                break;
+            }
+            let word = words[j].as_str().to_lowercase();
+            j += 1;
+            // As in "a hundred and five"; it only counts if a number follows
+            if word == "and" && matches!(number.last, Some(Kind::Hundred | Kind::Scale)) {
+                continue;
+            }
+            if !number.push(&word) {
+                break;
+            }
+            longest = Some((j, number.clone()));
+        }
+        let Some((end, number)) = longest else {
+            i += 1;
+            continue;
+        };
+        let value = number.value();
+        // "one" is a number in "one two three" but not in "the one I liked"
+        // or "the first one"
+        let is_number = |k: usize| {
+            number_word(&words[k].as_str().to_lowercase()).is_some_and(|(_, _, ordinal)| !ordinal)
+        };
+        let lone_one = end - i == 1
+            && value == 1
+            && !number.ordinal
+            && !(i > 0 && is_number(i - 1))
+            && !(end < words.len() && is_number(end));
+        let keep = value < min || (number.ordinal && value < 10) || lone_one;
+        if !keep {
+            out.push_str(&text[copied..words[i].start()]);
+            out.push_str(&value.to_string());
+            if number.ordinal {
+                out.push_str(ordinal_suffix(value));
+            }
+            copied = words[end - 1].end();
+        }
+        i = end;
+    }
+    out.push_str(&text[copied..]);
+    out
+}
+
+/// Rewrite lone digits ("3 apples") as words, leaving alone those that are
+/// part of something bigger ("3.5", "3%", "10:3", "$3") and days of a month
+/// ("May 5", "5 May").
+fn small_digits_to_words(text: &str) -> String {
+    static DIGIT: OnceLock<Regex> = OnceLock::new();
+    let digit = DIGIT.get_or_init(|| Regex::new(r"\b[0-9]\b").expect("valid digit pattern"));
+    digit
+        .replace_all(text, |caps: &Captures| {
+            let m = caps.get(0).unwrap();
+            let before = text[..m.start()].chars().last();
+            let mut after = text[m.end()..].chars();
+            let next = after.next();
+            let part_of_more = matches!(
+                before,
+                Some('.' | ',' | ':' | '/' | '$' | '€' | '£' | '#' | '+' | '-')
+            ) || matches!(next, Some('%' | '°' | ':' | '/' | '-'))
+                || (matches!(next, Some('.' | ','))
+                    && after.next().is_some_and(|c| c.is_ascii_digit()))
+                || text[..m.start()]
+                    .split_whitespace()
+                    .next_back()
+                    .is_some_and(is_month)
+                || text[m.end()..]
+                    .split_whitespace()
+                    .next()
+                    .is_some_and(|w| is_month(w.trim_end_matches(|c: char| !c.is_alphabetic())));
+            match m.as_str().parse::<usize>().ok().and_then(|n| SMALL.get(n)) {
+                Some(word) if !part_of_more => word.to_string(),
+                _ => m.as_str().to_string(),
+            }
+        })
+        .into_owned()
+}
+
+fn is_month(word: &str) -> bool {
+    MONTHS.iter().any(|m| m.eq_ignore_ascii_case(word))
+}
+
+fn ordinal_suffix(value: u64) -> &'static str {
+    match (value % 10, value % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Unit,
+    Teen,
+    Tens,
+    Hundred,
+    Scale,
+}
+
+/// A number being read word by word
+#[derive(Clone)]
+struct Number {
+    total: u64,
+    current: u64,
+    last: Option<Kind>,
+    /// The last thousand/million/billion, which the next has to be smaller
+    /// than
+    scale: u64,
+    ordinal: bool,
+    /// Nothing can follow: after "zero" or an ordinal
+    complete: bool,
+}
+
+impl Default for Number {
+    fn default() -> Self {
+        Self {
+            total: 0,
+            current: 0,
+            last: None,
+            scale: u64::MAX,
+            ordinal: false,
+            complete: false,
+        }
+    }
+}
+
+impl Number {
+    fn value(&self) -> u64 {
+        self.total + self.current
+    }
+
+    /// Add `word` to the number; false if it can't continue it, which
+    /// leaves the number as it was
+    fn push(&mut self, word: &str) -> bool {
+        let Some((value, kind, ordinal)) = number_word(word) else {
+            return false;
+        };
+        if self.complete {
+            return false;
+        }
+        use Kind::*;
+        let follows = |kinds: &[Option<Kind>]| kinds.contains(&self.last);
+        match kind {
+            _ if value == 0 => {
+                if self.last.is_some() {
+                    return false;
+                }
+                self.complete = true;
+            }
+            Unit if follows(&[None, Some(Tens), Some(Hundred), Some(Scale)]) => {
+                self.current += value
+            }
+            Teen | Tens if follows(&[None, Some(Hundred), Some(Scale)]) => self.current += value,
+            // A year, as in "nineteen ninety" or "twenty twenty", and only
+            // one: "twenty twenty twenty" is a year and another number.
+            // Other pairs ("eleven thirty", "fifty fifty") stay two numbers.
+            Teen | Tens
+                if follows(&[Some(Teen), Some(Tens)])
+                    && matches!(self.current, 19 | 20)
+                    && self.total == 0 =>
+            {
+                self.current = self.current * 100 + value;
+                // Nothing bigger can follow
+                self.scale = 1;
+            }
+            Hundred if follows(&[Some(Unit), Some(Teen)]) && self.current < 100 => {
+                self.current *= 100
+            }
+            Scale
+                if follows(&[Some(Unit), Some(Teen), Some(Tens), Some(Hundred)])
+                    && value < self.scale =>
+            {
+                self.total += self.current * value;
+                self.current = 0;
+                self.scale = value;
+            }
+            _ => return false,
+        }
+        self.last = Some(kind);
+        if ordinal {
+            self.ordinal = true;
+            self.complete = true;
+        }
+        true
+    }
+}
+
+/// The value of a number word, what kind it is, and whether it's an ordinal
+fn number_word(word: &str) -> Option<(u64, Kind, bool)> {
+    use Kind::*;
+    Some(match word {
+        "zero" => (0, Unit, false),
+        "one" => (1, Unit, false),
+        "first" => (1, Unit, true),
+        "two" => (2, Unit, false),
+        "second" => (2, Unit, true),
+        "three" => (3, Unit, false),
+        "third" => (3, Unit, true),
+        "four" => (4, Unit, false),
+        "fourth" => (4, Unit, true),
+        "five" => (5, Unit, false),
+        "fifth" => (5, Unit, true),
+        "six" => (6, Unit, false),
+        "sixth" => (6, Unit, true),
+        "seven" => (7, Unit, false),
+        "seventh" => (7, Unit, true),
+        "eight" => (8, Unit, false),
+        "eighth" => (8, Unit, true),
+        "nine" => (9, Unit, false),
+        "ninth" => (9, Unit, true),
+        "ten" => (10, Teen, false),
+        "tenth" => (10, Teen, true),
+        "eleven" => (11, Teen, false),
+        "eleventh" => (11, Teen, true),
+        "twelve" => (12, Teen, false),
+        "twelfth" => (12, Teen, true),
+        "thirteen" => (13, Teen, false),
+        "thirteenth" => (13, Teen, true),
+        "fourteen" => (14, Teen, false),
+        "fourteenth" => (14, Teen, true),
+        "fifteen" => (15, Teen, false),
+        "fifteenth" => (15, Teen, true),
+        "sixteen" => (16, Teen, false),
+        "sixteenth" => (16, Teen, true),
+        "seventeen" => (17, Teen, false),
+        "seventeenth" => (17, Teen, true),
+        "eighteen" => (18, Teen, false),
+        "eighteenth" => (18, Teen, true),
+        "nineteen" => (19, Teen, false),
+        "nineteenth" => (19, Teen, true),
+        "twenty" => (20, Tens, false),
+        "twentieth" => (20, Tens, true),
+        "thirty" => (30, Tens, false),
+        "thirtieth" => (30, Tens, true),
+        "forty" => (40, Tens, false),
+        "fortieth" => (40, Tens, true),
+        "fifty" => (50, Tens, false),
+        "fiftieth" => (50, Tens, true),
+        "sixty" => (60, Tens, false),
+        "sixtieth" => (60, Tens, true),
+        "seventy" => (70, Tens, false),
+        "seventieth" => (70, Tens, true),
+        "eighty" => (80, Tens, false),
+        "eightieth" => (80, Tens, true),
+        "ninety" => (90, Tens, false),
+        "ninetieth" => (90, Tens, true),
+        "hundred" => (100, Hundred, false),
+        "hundredth" => (100, Hundred, true),
+        "thousand" => (1_000, Scale, false),
+        "thousandth" => (1_000, Scale, true),
+        "million" => (1_000_000, Scale, false),
+        "millionth" => (1_000_000, Scale, true),
+        "billion" => (1_000_000_000, Scale, false),
+        "billionth" => (1_000_000_000, Scale, true),
+        _ => return None,
+    })
+}
@@ -0,0 +1,70 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Where human-readable progress output goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Progress and the transcript on stdout
+    Human = 0,
+    /// Progress on stderr; stdout carries one JSON object per dictation
+    Json = 1,
+    /// No progress output or notifications; stdout carries just the
+    /// transcript
+    Quiet = 2,
+}
+
+static MODE: AtomicU8 = AtomicU8::new(Mode::Human as u8);
+
+tokio::task_local! {
+    /// Set while a future runs under [`silenced`]
+    static SILENCED: ();
+}
+
+pub fn set_mode(mode: Mode) {
+    MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+pub fn mode() -> Mode {
+    if SILENCED.try_with(|_| ()).is_ok() {
+        return Mode::Quiet;
+    }
+    match MODE.load(Ordering::Relaxed) {
+        1 => Mode::Json,
+        2 => Mode::Quiet,
+        _ => Mode::Human,
+    }
+}
+
+/// Run `future` as if in quiet mode, for background work whose progress
+/// messages would get in the way of what the user is looking at.
+pub async fn silenced<F: std::future::Future>(future: F) -> F::Output {
+    SILENCED.scope((), future).await
+}
+
+/// `println!` for progress messages: stdout normally, stderr when stdout is
+/// reserved for machine-readable output, nowhere in quiet mode.
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        match $crate::output::mode() {
+            $crate::output::Mode::Human => println!($($arg)*),
+            $crate::output::Mode::Json => eprintln!($($arg)*),
+            $crate::output::Mode::Quiet => {}
+        }
+    };
+}
+
+/// `print!` counterpart of [`say!`], flushed immediately.
+#[macro_export]
+macro_rules! say_inline {
+    ($($arg:tt)*) => {{
+        use std::io::Write;
+        match $crate::output::mode() {
+            $crate::output::Mode::Human => {
+                print!($($arg)*);
+                let _ = std::io::stdout().flush();
+            }
+            $crate::output::Mode::Json => eprint!($($arg)*),
+            $crate::output::Mode::Quiet => {}
+        }
+    }};
+}
@@ -0,0 +1,430 @@
+//! Live captions in an always-on-top strip at the bottom of the screen,
+//! drawn through the compositor's wlr-layer-shell-unstable-v1 protocol.
+
+use ab_glyph::{point, Font, FontVec, GlyphId, PxScale, ScaleFont};
+use anyhow::{Context, Result};
+use std::os::fd::AsFd;
+use std::os::unix::fs::FileExt;
+use std::sync::mpsc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::{
+    wl_buffer::{self, WlBuffer},
+    wl_compositor::WlCompositor,
+    wl_region::WlRegion,
+    wl_registry,
+    wl_shm::{self, WlShm},
+    wl_shm_pool::WlShmPool,
+    wl_surface::WlSurface,
+};
+use wayland_client::{delegate_noop, Connection, Dispatch, QueueHandle};
+use wayland_protocols_wlr::layer_shell::v1::client::{
+    zwlr_layer_shell_v1::{Layer, ZwlrLayerShellV1},
+    zwlr_layer_surface_v1::{self, Anchor, KeyboardInteractivity, ZwlrLayerSurfaceV1},
+};
+
+use crate::session::Event;
+
+/// Caption text size in pixels
+const FONT_SIZE: f32 = 28.0;
+
+/// Lines of text shown; longer captions scroll, keeping the latest words
+const LINES: usize = 2;
+
+/// Space between the text and the edge of its background
+const PADDING: u32 = 16;
+
+/// Distance from the bottom of the screen
+const MARGIN: i32 = 48;
+
+/// Widest the caption box gets, narrower screens permitting
+const MAX_WIDTH: u32 = 1000;
+
+/// How long a finished transcript stays on screen
+const HOLD: Duration = Duration::from_secs(5);
+
+/// Shown while recording, until there are words to show
+const LISTENING: &str = "…";
+
+/// The caption strip. It is drawn by a thread of its own, which holds the
+/// Wayland connection until the overlay is dropped.
+pub struct Overlay {
+    tx: mpsc::Sender<Option<String>>,
+}
+
+impl Overlay {
+    /// Connect to the compositor and put an empty, click-through strip on
+    /// the overlay layer. Fails when not running under a compositor with
+    /// the layer shell (wlroots-based ones, KDE, niri, and others).
+    pub fn open() -> Result<Self> {
+        let font = load_font()?;
+        let (tx, rx) = mpsc::channel();
+        let (ready_tx, ready_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut strip = match Strip::open(font) {
+                Ok(strip) => {
+                    let _ = ready_tx.send(Ok(()));
+                    strip
+                }
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
+            if let Err(e) = strip.run(rx) {
+                eprintln!("Warning: Overlay closed: {:#}", e);
+            }
+        });
+        ready_rx
+            .recv()
+            .context("Overlay thread exited")?
+            .map(|()| Self { tx })
+    }
+
+    /// Show `text` as the caption
+    pub fn show(&self, text: &str) {
+        let _ = self.tx.send(Some(text.to_string()));
+    }
+
+    /// Clear the strip
+    pub fn hide(&self) {
+        let _ = self.tx.send(None);
+    }
+
+    /// Caption the dictations `events` report: what has been recognized so
+    /// far while recording (with --live), then the transcript for a few
+    /// seconds. Runs until the session is gone.
+    pub async fn follow(self, mut events: broadcast::Receiver<Event>) {
+        let mut hide_at: Option<tokio::time::Instant> = None;
+        loop {
+            let event = tokio::select! {
+                event = events.recv() => event,
+                _ = async { tokio::time::sleep_until(hide_at.expect("checked by the guard")).await }, if hide_at.is_some() => {
+                    hide_at = None;
+                    self.hide();
+                    continue;
+                }
+            };
+            match event {
+                Ok(Event::Started { .. }) => {
+                    hide_at = None;
+                    self.show(LISTENING);
+                }
+                Ok(Event::Partial { text, .. }) => {
+                    hide_at = None;
+                    self.show(&text);
+                }
+                Ok(Event::Completed { text, .. }) => {
+                    self.show(&text);
+                    hide_at = Some(tokio::time::Instant::now() + HOLD);
+                }
+                Ok(Event::Cancelled { .. } | Event::Failed { .. }) => {
+                    hide_at = None;
+                    self.hide();
+                }
+                Ok(Event::Stopped { .. }) => {}
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+}
+
+/// The font fontconfig picks for sans-serif text
+fn load_font() -> Result<FontVec> {
+    let output = std::process::Command::new("fc-match")
+        .args(["-f", "%{file}", "sans-serif"])
+        .output()
+        .context("Failed to run fc-match (is fontconfig installed?)")?;
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || path.is_empty() {
+        anyhow::bail!("fc-match found no sans-serif font");
+    }
+    let data = std::fs::read(&path).with_context(|| format!("Failed to read {}", path))?;
+    FontVec::try_from_vec(data).with_context(|| format!("Failed to load font {}", path))
+}
+
+/// Wayland side of the overlay
+struct Strip {
+    conn: Connection,
+    queue: wayland_client::EventQueue<State>,
+    state: State,
+    shm: WlShm,
+    surface: WlSurface,
+    _layer_surface: ZwlrLayerSurfaceV1,
+    font: FontVec,
+}
+
+#[derive(Default)]
+struct State {
+    /// Size given by the compositor; the width is the output's
+    width: u32,
+    height: u32,
+    closed: bool,
+}
+
+impl Strip {
+    fn open(font: FontVec) -> Result<Self> {
+        let conn =
+            Connection::connect_to_env().context("Not running under a Wayland compositor")?;
+        let (globals, mut queue) = registry_queue_init::<State>(&conn)?;
+        let qh = queue.handle();
+        let compositor: WlCompositor = globals
+            .bind(&qh, 1..=4, ())
+            .context("No Wayland compositor global")?;
+        let shm: WlShm = globals.bind(&qh, 1..=1, ()).context("No Wayland shm")?;
+        let layer_shell: ZwlrLayerShellV1 = globals
+            .bind(&qh, 1..=4, ())
+            .context("Compositor doesn't support wlr-layer-shell-unstable-v1")?;
+
+        let surface = compositor.create_surface(&qh, ());
+        // Clicks go through to whatever is underneath
+        let region = compositor.create_region(&qh, ());
+        surface.set_input_region(Some(&region));
+        region.destroy();
+
+        let layer_surface = layer_shell.get_layer_surface(
+            &surface,
+            None,
+            Layer::Overlay,
+            "rpdictation".to_string(),
+            &qh,
+            (),
+        );
+        layer_surface.set_anchor(Anchor::Bottom | Anchor::Left | Anchor::Right);
+        layer_surface.set_size(0, strip_height(&font));
+        layer_surface.set_margin(0, 0, MARGIN, 0);
+        layer_surface.set_keyboard_interactivity(KeyboardInteractivity::None);
+        surface.commit();
+
+        let mut state = State::default();
+        queue.roundtrip(&mut state)?;
+        if state.width == 0 {
+            anyhow::bail!("Compositor didn't configure the overlay");
+        }
+
+        let mut strip = Self {
+            conn,
+            queue,
+            state,
+            shm,
+            surface,
+            _layer_surface: layer_surface,
+            font,
+        };
+        strip.draw(None)?;
+        Ok(strip)
+    }
+
+    /// Draw every caption sent until the overlay is dropped, skipping ones
+    /// that were replaced before they could be drawn.
+    fn run(&mut self, rx: mpsc::Receiver<Option<String>>) -> Result<()> {
+        while let Ok(mut caption) = rx.recv() {
+            while let Ok(newer) = rx.try_recv() {
+                caption = newer;
+            }
+            self.queue.dispatch_pending(&mut self.state)?;
+            if self.state.closed {
+                anyhow::bail!("The compositor closed it");
+            }
+            self.draw(caption.as_deref())?;
+        }
+        Ok(())
+    }
+
+    /// Replace the strip's contents with `caption`, or nothing
+    fn draw(&mut self, caption: Option<&str>) -> Result<()> {
+        let (width, height) = (self.state.width, self.state.height);
+        let mut pixels = vec![0u8; (width * height * 4) as usize];
+        if let Some(caption) = caption.filter(|c| !c.trim().is_empty()) {
+            render(&self.font, caption, width, height, &mut pixels);
+        }
+
+        let file = buffer_file(&pixels)?;
+        let qh = self.queue.handle();
+        let pool = self
+            .shm
+            .create_pool(file.as_fd(), pixels.len() as i32, &qh, ());
+        let buffer = pool.create_buffer(
+            0,
+            width as i32,
+            height as i32,
+            (width * 4) as i32,
+            wl_shm::Format::Argb8888,
+            &qh,
+            (),
+        );
+        pool.destroy();
+        self.surface.attach(Some(&buffer), 0, 0);
+        self.surface.damage(0, 0, width as i32, height as i32);
+        self.surface.commit();
+        self.queue.roundtrip(&mut self.state)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+}
+
+/// Height of the strip: the lines of text and the padding around them
+fn strip_height(font: &FontVec) -> u32 {
+    let scaled = font.as_scaled(PxScale::from(FONT_SIZE));
+    (scaled.height() + scaled.line_gap()).ceil() as u32 * LINES as u32 + 2 * PADDING
+}
+
+/// Draw `caption` centered at the bottom of a `width`×`height` ARGB8888
+/// image, white on translucent black, wrapped at word boundaries. Only the
+/// last [`LINES`] lines are drawn.
+fn render(font: &FontVec, caption: &str, width: u32, height: u32, pixels: &mut [u8]) {
+    let scaled = font.as_scaled(PxScale::from(FONT_SIZE));
+    let line_height = scaled.height() + scaled.line_gap();
+    let room = width.min(MAX_WIDTH).saturating_sub(2 * PADDING) as f32;
+
+    let advance = |text: &str| {
+        let mut x = 0.0;
+        let mut prev: Option<GlyphId> = None;
+        for c in text.chars() {
+            let id = scaled.glyph_id(c);
+            if let Some(prev) = prev {
+                x += scaled.kern(prev, id);
+            }
+            x += scaled.h_advance(id);
+            prev = Some(id);
+        }
+        x
+    };
+
+    let mut lines: Vec<String> = Vec::new();
+    for word in caption.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if advance(&format!("{} {}", line, word)) <= room => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    let lines = &lines[lines.len().saturating_sub(LINES)..];
+
+    let text_width = lines.iter().map(|l| advance(l)).fold(0.0, f32::max);
+    let box_width = (text_width.ceil() as u32 + 2 * PADDING).min(width);
+    let box_height = (line_height * lines.len() as f32).ceil() as u32 + 2 * PADDING;
+    let left = (width - box_width) / 2;
+    let top = height.saturating_sub(box_height);
+
+    // Premultiplied ARGB8888, stored little-endian as B, G, R, A
+    const BACKGROUND_ALPHA: f32 = 0.75;
+    for y in top..height {
+        for x in left..left + box_width {
+            let i = ((y * width + x) * 4) as usize;
+            pixels[i + 3] = (BACKGROUND_ALPHA * 255.0) as u8;
+        }
+    }
+
+    for (n, line) in lines.iter().enumerate() {
+        let baseline = top as f32 + PADDING as f32 + line_height * n as f32 + scaled.ascent();
+        let mut x = left as f32 + (box_width as f32 - advance(line)) / 2.0;
+        let mut prev: Option<GlyphId> = None;
+        for c in line.chars() {
+            let id = scaled.glyph_id(c);
+            if let Some(prev) = prev {
+                x += scaled.kern(prev, id);
+            }
+            let glyph = id.with_scale_and_position(FONT_SIZE, point(x, baseline));
+            if let Some(outline) = font.outline_glyph(glyph) {
+                let bounds = outline.px_bounds();
+                outline.draw(|gx, gy, coverage| {
+                    let px = bounds.min.x as i32 + gx as i32;
+                    let py = bounds.min.y as i32 + gy as i32;
+                    if px < 0 || py < 0 || px as u32 >= width || py as u32 >= height {
+                        return;
+                    }
+                    let i = ((py as u32 * width + px as u32) * 4) as usize;
+                    let coverage = coverage.min(1.0);
+                    let alpha = coverage + (1.0 - coverage) * BACKGROUND_ALPHA;
+                    let white = (coverage * 255.0) as u8;
+                    pixels[i..i + 4].copy_from_slice(&[white, white, white, (alpha * 255.0) as u8]);
+                });
+            }
+            x += scaled.h_advance(id);
+            prev = Some(id);
+        }
+    }
+}
+
+/// The pixels have to be handed over as a file descriptor: write them to an
+/// unlinked file in the runtime directory.
+fn buffer_file(pixels: &[u8]) -> Result<std::fs::File> {
+    let path = crate::get_runtime_dir().join(format!(
+        "rpdictation-overlay-{}",
+        uuid::Uuid::new_v4().simple()
+    ));
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    std::fs::remove_file(&path).ok();
+    file.write_all_at(pixels, 0)?;
+    Ok(file)
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrLayerSurfaceV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        layer_surface: &ZwlrLayerSurfaceV1,
+        event: zwlr_layer_surface_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_layer_surface_v1::Event::Configure {
+                serial,
+                width,
+                height,
+            } => {
+                layer_surface.ack_configure(serial);
+                state.width = width;
+                state.height = height;
+            }
+            zwlr_layer_surface_v1::Event::Closed => state.closed = true,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlBuffer, ()> for State {
+    fn event(
+        _: &mut Self,
+        buffer: &WlBuffer,
+        event: wl_buffer::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        // Every caption gets a buffer of its own
+        if let wl_buffer::Event::Release = event {
+            buffer.destroy();
+        }
+    }
+}
+
+delegate_noop!(State: ignore WlCompositor);
+delegate_noop!(State: ignore WlShm);
+delegate_noop!(State: ignore WlShmPool);
+delegate_noop!(State: ignore WlSurface);
+delegate_noop!(State: ignore WlRegion);
+delegate_noop!(State: ignore ZwlrLayerShellV1);
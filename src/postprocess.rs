@@ -0,0 +1,47 @@
+use crate::numbers::{self, NumberStyle};
+use crate::profanity::{self, Profanity};
+use crate::punctuation::{self, Case};
+use crate::replacements::Replacements;
+use crate::spelling;
+
+/// How a transcript is rewritten between the provider and the outputs. Each
+/// step works on the result of the one before, in the order of the fields.
+#[derive(Default)]
+pub struct Postprocess {
+    /// Rewrite dates in this strftime format
+    pub date_format: Option<String>,
+    pub numbers: NumberStyle,
+    pub case: Case,
+    /// Read the whole dictation as spelled text, instead of only
+    /// "spell … stop" sequences
+    pub spell: bool,
+    pub profanity: Profanity,
+    pub replacements: Replacements,
+    pub strip_period: bool,
+    pub collapse_whitespace: bool,
+}
+
+impl Postprocess {
+    pub fn apply(&self, text: &str) -> String {
+        let text = match self.date_format {
+            Some(ref format) => numbers::normalize_dates(text, format),
+            None => text.to_string(),
+        };
+        let text = numbers::normalize_numbers(&text, self.numbers);
+        let text = punctuation::apply_case(&text, self.case);
+        let text = if self.spell {
+            spelling::spell_all(&text)
+        } else {
+            spelling::spell_inline(&text)
+        };
+        let text = profanity::filter(&text, self.profanity);
+        let mut text = self.replacements.apply(&text);
+        if self.strip_period {
+            text = punctuation::strip_trailing_period(&text);
+        }
+        if self.collapse_whitespace {
+            text = punctuation::collapse_whitespace(&text);
+        }
+        text
+    }
+}
@@ -0,0 +1,53 @@
+use clap::ValueEnum;
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+/// What happens to swear words in a transcript
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Profanity {
+    /// Left as they were said
+    #[default]
+    Keep,
+    /// All but the first letter replaced with asterisks ("f***")
+    Mask,
+    /// Left out altogether
+    Remove,
+}
+
+/// English swear words and their common forms, as a regex alternation.
+/// Words that are also names or harmless in other senses ("Dick", "prick")
+/// are left out.
+const WORDS: &str = r"\w*fuck\w*|bullshit\w*|shit(?:s|ty|ting|ted|head|heads)?|motherf\w+|asshole\w*|arsehole\w*|bitch(?:es|ing|y)?|bastards?|cunts?|wank(?:er|ers|ing)?|bollocks|dickheads?|piss(?:ed|es|ing)?|goddamn\w*|twats?";
+
+/// `text` with its swear words masked or removed.
+pub fn filter(text: &str, profanity: Profanity) -> String {
+    static MASK: OnceLock<Regex> = OnceLock::new();
+    static REMOVE: OnceLock<Regex> = OnceLock::new();
+    match profanity {
+        Profanity::Keep => text.to_string(),
+        Profanity::Mask => MASK
+            .get_or_init(|| Regex::new(&format!(r"(?i)\b(?:{})\b", WORDS)).expect("valid pattern"))
+            .replace_all(text, |caps: &Captures| {
+                let mut chars = caps[0].chars();
+                let first = chars.next().unwrap_or_default();
+                std::iter::once(first)
+                    .chain(chars.map(|_| '*'))
+                    .collect::<String>()
+            })
+            .into_owned(),
+        Profanity::Remove => {
+            // With the space before the word, or at the very start with the
+            // punctuation and space after it ("Shit, it broke")
+            let pattern = REMOVE.get_or_init(|| {
+                Regex::new(&format!(
+                    r"(?i)^\s*\b(?:{words})\b[,!.]*\s*|\s*\b(?:{words})\b",
+                    words = WORDS
+                ))
+                .expect("valid pattern")
+            });
+            pattern.replace_all(text, "").into_owned()
+        }
+    }
+}
@@ -1,11 +1,14 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use std::sync::Mutex;
 
 use super::TranscriptionProvider;
 
 pub struct GoogleProvider {
     api_key: String,
-    language: String,
+    language: Mutex<String>,
+    profanity_filter: bool,
+    client: reqwest::Client,
 }
 
 impl GoogleProvider {
@@ -15,20 +18,23 @@ impl GoogleProvider {
     pub fn new(api_key: Option<String>, language: String) -> Self {
         Self {
             api_key: api_key.unwrap_or(Self::DEFAULT_KEY.to_string()),
-            language,
+            language: Mutex::new(language),
+            profanity_filter: false,
+            client: super::http_client(),
         }
     }
-}
 
-#[async_trait]
-impl TranscriptionProvider for GoogleProvider {
-    fn name(&self) -> &str {
-        "Google"
+    /// Have the API mask swear words itself
+    pub fn with_profanity_filter(mut self, profanity_filter: bool) -> Self {
+        self.profanity_filter = profanity_filter;
+        self
     }
 
-    async fn transcribe(&self, audio_data: &[u8], sample_rate: u32) -> Result<String> {
+    /// Send the WAV-encoded `audio_data` to the API and return its raw,
+    /// newline-delimited JSON answer.
+    async fn recognize(&self, audio_data: &[u8], sample_rate: u32) -> Result<String> {
         // Convert WAV to FLAC (CPU-intensive, run in blocking thread)
-        println!("Converting WAV to FLAC...");
+        say!("Converting WAV to FLAC...");
         let audio_data_owned = audio_data.to_vec();
         let flac_data = tokio::task::spawn_blocking(move || {
             crate::audio::wav_to_flac(&audio_data_owned, sample_rate)
@@ -36,36 +42,60 @@ impl TranscriptionProvider for GoogleProvider {
         .await
         .context("FLAC encoding task panicked")??;
 
-        // Send to Google API
-        let client = reqwest::Client::new();
-        let url = format!(
+        // Send to Google API. It can't detect the language, so it gets the
+        // default instead.
+        let language = match self.language.lock().unwrap().as_str() {
+            super::DETECT_LANGUAGE => "en-us".to_string(),
+            language => language.to_string(),
+        };
+        let mut url = format!(
             "{}?key={}&lang={}&output=json",
             Self::ENDPOINT,
             self.api_key,
-            self.language
+            language
         );
-
-        println!("Sending request to Google Chromium Speech API...");
-        let response = client
-            .post(&url)
-            .header(
-                "Content-Type",
-                format!("audio/x-flac; rate={}", sample_rate),
-            )
-            .body(flac_data)
-            .timeout(super::API_TIMEOUT)
-            .send()
-            .await
-            .context("Failed to send request to Google API")?;
-
-        println!("Got response with status: {}", response.status());
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(anyhow::anyhow!("API error: {}", error_text));
+        if self.profanity_filter {
+            // What Chrome sends with its profanity filter on
+            url.push_str("&pFilter=2");
         }
 
-        // Parse newline-delimited JSON response
-        let response_text = response.text().await?;
+        say!("Sending request to Google Chromium Speech API...");
+        let flac_len = flac_data.len();
+        let upload = super::Upload::new(
+            self.name(),
+            flac_data,
+            crate::audio::wav_seconds(audio_data),
+        );
+        let response = super::send(
+            self.client
+                .post(&url)
+                .header(
+                    "Content-Type",
+                    format!("audio/x-flac; rate={}", sample_rate),
+                )
+                // Streamed bodies are chunked otherwise
+                .header(reqwest::header::CONTENT_LENGTH, flac_len)
+                .body(upload.body()),
+            upload,
+        )
+        .await?;
+
+        Ok(response.text().await?)
+    }
+}
+
+#[async_trait]
+impl TranscriptionProvider for GoogleProvider {
+    fn name(&self) -> &str {
+        "Google"
+    }
+
+    fn set_language(&self, language: &str) {
+        *self.language.lock().unwrap() = language.to_string();
+    }
+
+    async fn transcribe(&self, audio_data: &[u8], sample_rate: u32) -> Result<String> {
+        let response_text = self.recognize(audio_data, sample_rate).await?;
 
         // The response contains multiple JSON objects separated by newlines
         // We want the one with actual results (not just {"result":[]} empty)
@@ -95,6 +125,13 @@ impl TranscriptionProvider for GoogleProvider {
         ))
     }
 
+    async fn check(&self) -> Result<()> {
+        // Silence comes back without results, which is fine here
+        let silence = vec![0; crate::SAMPLE_RATE as usize];
+        let wav = crate::audio::samples_to_wav(&silence, crate::SAMPLE_RATE)?;
+        self.recognize(&wav, crate::SAMPLE_RATE).await.map(|_| ())
+    }
+
     fn cost_per_minute(&self) -> Option<f64> {
         None
     }
@@ -2,14 +2,50 @@ use anyhow::{Context, Result};
 use async_trait::async_trait;
 
 use super::TranscriptionProvider;
+use crate::credentials::ApiKey;
 
 pub struct MistralProvider {
-    api_key: String,
+    api_key: ApiKey,
+    client: reqwest::Client,
 }
 
 impl MistralProvider {
-    pub fn new(api_key: String) -> Self {
-        Self { api_key }
+    const MODEL: &str = "voxtral-mini-latest";
+
+    pub fn new(api_key: ApiKey) -> Self {
+        Self {
+            api_key,
+            client: super::http_client(),
+        }
+    }
+
+    /// Send `audio_data` off and return the API's JSON answer
+    async fn request(&self, audio_data: &[u8]) -> Result<serde_json::Value> {
+        let api_key = self.api_key.resolve().await?;
+        let upload = super::Upload::new(
+            self.name(),
+            audio_data.to_vec(),
+            crate::audio::wav_seconds(audio_data),
+        );
+        let file_part = upload.part("recording.wav", "audio/wav")?;
+        let form = reqwest::multipart::Form::new()
+            .part("file", file_part)
+            .text("model", Self::MODEL);
+
+        say!("Sending request to Mistral API...");
+        let response = super::send(
+            self.client
+                .post("https://api.mistral.ai/v1/audio/transcriptions")
+                .header("Authorization", format!("Bearer {}", api_key))
+                .multipart(form),
+            upload,
+        )
+        .await?;
+
+        response
+            .json()
+            .await
+            .context("Failed to parse API response as JSON")
     }
 }
 
@@ -20,40 +56,37 @@ impl TranscriptionProvider for MistralProvider {
     }
 
     async fn transcribe(&self, audio_data: &[u8], _sample_rate: u32) -> Result<String> {
-        let client = reqwest::Client::new();
-        let file_part = reqwest::multipart::Part::bytes(audio_data.to_vec())
-            .file_name("recording.wav")
-            .mime_str("audio/wav")?;
-        let form = reqwest::multipart::Form::new()
-            .part("file", file_part)
-            .text("model", "voxtral-mini-latest");
-
-        println!("Sending request to Mistral API...");
-        let response = client
-            .post("https://api.mistral.ai/v1/audio/transcriptions")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .multipart(form)
-            .timeout(std::time::Duration::from_secs(60))
-            .send()
-            .await
-            .context("Failed to send request to Mistral API")?;
-
-        println!("Got response with status: {}", response.status());
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(anyhow::anyhow!("API error: {}", error_text));
-        }
+        let result = self.request(audio_data).await?;
+        let Some(text) = result["text"].as_str() else {
+            anyhow::bail!("Failed to get transcription from response");
+        };
 
-        let result: serde_json::Value = response
-            .json()
-            .await
-            .context("Failed to parse API response as JSON")?;
+        Ok(text.to_string())
+    }
 
+    async fn transcribe_detecting(
+        &self,
+        audio_data: &[u8],
+        _sample_rate: u32,
+    ) -> Result<(String, Option<String>)> {
+        let result = self.request(audio_data).await?;
         let Some(text) = result["text"].as_str() else {
             anyhow::bail!("Failed to get transcription from response");
         };
+        let language = result["language"].as_str().and_then(super::language_code);
+        Ok((text.to_string(), language))
+    }
 
-        Ok(text.to_string())
+    async fn check(&self) -> Result<()> {
+        let api_key = self.api_key.resolve().await?;
+        super::query(
+            self.name(),
+            self.client
+                .get(format!("https://api.mistral.ai/v1/models/{}", Self::MODEL))
+                .header("Authorization", format!("Bearer {}", api_key)),
+        )
+        .await?;
+        Ok(())
     }
 
     fn cost_per_minute(&self) -> Option<f64> {
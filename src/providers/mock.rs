@@ -0,0 +1,103 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+
+use super::{Segment, TranscriptionProvider};
+
+/// Stand-in provider for tests and dry runs: checks that it was sent a
+/// readable WAV file and answers with a fixed text, or fails on purpose.
+/// Never touches the network.
+pub struct MockProvider {
+    /// `None` makes every request fail
+    text: Option<String>,
+    /// What it claims to have heard
+    language: Option<String>,
+}
+
+impl MockProvider {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: Some(text.into()),
+            language: None,
+        }
+    }
+
+    /// A provider whose every request fails, for exercising fallback
+    pub fn failing() -> Self {
+        Self {
+            text: None,
+            language: None,
+        }
+    }
+
+    /// Report having heard `language`, for exercising language detection
+    pub fn detecting(mut self, language: &str) -> Self {
+        self.language = Some(language.to_string());
+        self
+    }
+}
+
+#[async_trait]
+impl TranscriptionProvider for MockProvider {
+    fn name(&self) -> &str {
+        "Mock"
+    }
+
+    async fn transcribe(&self, audio_data: &[u8], sample_rate: u32) -> Result<String> {
+        let reader = hound::WavReader::new(audio_data).context("Mock got invalid WAV data")?;
+        if reader.spec().sample_rate != sample_rate {
+            anyhow::bail!(
+                "Mock got {} Hz audio, expected {} Hz",
+                reader.spec().sample_rate,
+                sample_rate
+            );
+        }
+        match self.text {
+            Some(ref text) => Ok(text.clone()),
+            None => anyhow::bail!("Mock provider failure"),
+        }
+    }
+
+    async fn transcribe_detecting(
+        &self,
+        audio_data: &[u8],
+        sample_rate: u32,
+    ) -> Result<(String, Option<String>)> {
+        let text = self.transcribe(audio_data, sample_rate).await?;
+        Ok((text, self.language.clone()))
+    }
+
+    /// One segment per sentence of the text, spread over the audio in
+    /// proportion to their lengths
+    async fn transcribe_segments(
+        &self,
+        audio_data: &[u8],
+        sample_rate: u32,
+    ) -> Result<Vec<Segment>> {
+        let text = self.transcribe(audio_data, sample_rate).await?;
+        let seconds = crate::audio::wav_seconds(audio_data);
+        let sentences: Vec<&str> = text
+            .split_inclusive(['.', '?', '!'])
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .collect();
+        let total: usize = sentences.iter().map(|s| s.chars().count()).sum();
+        let mut start = 0.0;
+        Ok(sentences
+            .into_iter()
+            .map(|sentence| {
+                let length = seconds * sentence.chars().count() as f64 / total as f64;
+                let segment = Segment {
+                    start,
+                    end: start + length,
+                    text: sentence.to_string(),
+                };
+                start += length;
+                segment
+            })
+            .collect())
+    }
+
+    fn cost_per_minute(&self) -> Option<f64> {
+        None
+    }
+}
@@ -1,16 +1,302 @@
 use anyhow::Result;
 use async_trait::async_trait;
+use std::future::Future;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+/// A stretch of a transcript with the time it was spoken, in seconds from
+/// the start of the audio
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// The language that has providers detect the language of each recording
+/// instead of being told it
+pub const DETECT_LANGUAGE: &str = "auto";
 
 #[async_trait]
 pub trait TranscriptionProvider: Send + Sync {
     fn name(&self) -> &str;
     async fn transcribe(&self, audio_data: &[u8], sample_rate: u32) -> Result<String>;
     fn cost_per_minute(&self) -> Option<f64>;
+
+    /// Transcribe `language` (e.g. "cs-CZ", or [`DETECT_LANGUAGE`]) from now
+    /// on. Providers that detect the language by themselves ignore it.
+    fn set_language(&self, _language: &str) {}
+
+    /// Like [`Self::transcribe`], but also with the language the provider
+    /// heard, as a code such as "cs". Not every provider can tell; by
+    /// default the language is `None`.
+    async fn transcribe_detecting(
+        &self,
+        audio_data: &[u8],
+        sample_rate: u32,
+    ) -> Result<(String, Option<String>)> {
+        Ok((self.transcribe(audio_data, sample_rate).await?, None))
+    }
+
+    /// Like [`Self::transcribe`], but with the time each segment was spoken,
+    /// for subtitles. Not every provider can tell; by default this fails.
+    async fn transcribe_segments(
+        &self,
+        _audio_data: &[u8],
+        _sample_rate: u32,
+    ) -> Result<Vec<Segment>> {
+        anyhow::bail!("{} doesn't return timestamps", self.name())
+    }
+
+    /// Make sure the provider is usable (key accepted, model available) with
+    /// a request that costs as little as possible. By default a second of
+    /// silence is transcribed.
+    async fn check(&self) -> Result<()> {
+        let silence = vec![0; crate::SAMPLE_RATE as usize];
+        let wav = crate::audio::samples_to_wav(&silence, crate::SAMPLE_RATE)?;
+        self.transcribe(&wav, crate::SAMPLE_RATE).await.map(|_| ())
+    }
+}
+
+tokio::task_local! {
+    /// Set while a transcription runs under [`timing_upload`]: how long
+    /// sending the audio took, once it is all out
+    static UPLOAD_TIME: Arc<Mutex<Option<Duration>>>;
+}
+
+/// Run `transcription` and also find out how long sending the audio took,
+/// for providers that upload it over HTTP
+pub async fn timing_upload<T>(transcription: impl Future<Output = T>) -> (T, Option<Duration>) {
+    let clock = Arc::new(Mutex::new(None));
+    let result = UPLOAD_TIME.scope(Arc::clone(&clock), transcription).await;
+    let upload = *clock.lock().unwrap();
+    (result, upload)
+}
+
+/// Default for [`set_timeout`]
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Set from --api-timeout at startup
+static TIMEOUT: OnceLock<Duration> = OnceLock::new();
+
+/// Wait `base` plus the length of the audio for a provider to answer. Only
+/// the first call has an effect.
+pub fn set_timeout(base: Duration) {
+    let _ = TIMEOUT.set(base);
+}
+
+/// How long to wait for a provider to answer about `audio_seconds` of audio:
+/// the base timeout plus as long again as the audio lasts, so that long
+/// recordings have time to upload and be transcribed.
+pub fn timeout_for(audio_seconds: f64) -> Duration {
+    let base = TIMEOUT.get().copied().unwrap_or(DEFAULT_TIMEOUT);
+    base + Duration::from_secs_f64(audio_seconds.max(0.0))
+}
+
+/// Shared by all providers and the token endpoint, see [`configure_http`]
+#[cfg(feature = "http")]
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// Send API requests through `proxy` instead of the one from $HTTPS_PROXY
+/// (and friends), and trust the CA certificates in the PEM bundle `ca_cert`
+/// on top of the system ones. Has to come before the first
+/// [`http_client`]; only the first call has an effect.
+#[cfg(feature = "http")]
+pub fn configure_http(proxy: Option<&str>, ca_cert: Option<&std::path::Path>) -> Result<()> {
+    use anyhow::Context;
+
+    let mut builder = reqwest::Client::builder();
+    if let Some(url) = proxy {
+        let proxy =
+            reqwest::Proxy::all(url).with_context(|| format!("Invalid proxy URL '{}'", url))?;
+        builder = builder.proxy(proxy);
+    }
+    if let Some(path) = ca_cert {
+        let pem =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let certs = reqwest::Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("Invalid CA bundle {}", path.display()))?;
+        if certs.is_empty() {
+            anyhow::bail!("No certificates in {}", path.display());
+        }
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    let client = builder
+        .build()
+        .context("Failed to set up the HTTP client")?;
+    let _ = HTTP_CLIENT.set(client);
+    Ok(())
+}
+
+/// The client set up by [`configure_http`], or one with the defaults
+#[cfg(feature = "http")]
+pub fn http_client() -> reqwest::Client {
+    HTTP_CLIENT.get_or_init(reqwest::Client::new).clone()
+}
+
+/// Send `request`, which carries `upload`, to the provider's API and return
+/// the response if it succeeded. Rejected keys, exhausted quotas, and
+/// unreachable servers come back as the matching [`Error`] so they get their
+/// own exit codes.
+#[cfg(feature = "http")]
+pub async fn send(request: reqwest::RequestBuilder, upload: Upload) -> Result<reqwest::Response> {
+    let timeout = timeout_for(upload.audio_seconds());
+    let result = request.timeout(timeout).send().await;
+    upload.finish();
+    let response = check_response(upload.provider(), timeout, result).await?;
+    say!("Got response with status: {}", response.status());
+    Ok(response)
+}
+
+/// [`send`] for a request without audio, such as looking up a model
+#[cfg(feature = "http")]
+pub async fn query(provider: &str, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+    let timeout = timeout_for(0.0);
+    let result = request.timeout(timeout).send().await;
+    check_response(provider, timeout, result).await
 }
 
-pub const API_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+#[cfg(feature = "http")]
+async fn check_response(
+    provider: &str,
+    timeout: Duration,
+    result: reqwest::Result<reqwest::Response>,
+) -> Result<reqwest::Response> {
+    use crate::error::Error;
+    use reqwest::StatusCode;
 
+    let response = match result {
+        Ok(response) => response,
+        Err(source) if source.is_timeout() => {
+            return Err(anyhow::Error::new(Error::Network {
+                provider: provider.to_string(),
+                source,
+            })
+            .context(format!(
+                "No answer within {} seconds (see --api-timeout)",
+                timeout.as_secs()
+            )))
+        }
+        Err(source) => {
+            return Err(Error::Network {
+                provider: provider.to_string(),
+                source,
+            }
+            .into())
+        }
+    };
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let message = response.text().await.unwrap_or_default();
+    let provider = provider.to_string();
+    Err(match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Error::ApiAuth { provider, message },
+        StatusCode::PAYMENT_REQUIRED | StatusCode::TOO_MANY_REQUESTS => {
+            Error::ApiQuota { provider, message }
+        }
+        _ => anyhow::bail!("API error ({}): {}", status, message),
+    }
+    .into())
+}
+
+/// The prompt that makes a Whisper model expect the terms in `vocabulary`.
+/// Whisper reads the prompt as text preceding the recording, so a plain list
+/// of the terms, spelled the way they should come out, is enough.
+#[cfg(any(feature = "openai", feature = "groq"))]
+fn whisper_prompt(vocabulary: &[String]) -> Option<String> {
+    if vocabulary.is_empty() {
+        return None;
+    }
+    Some(format!("{}.", vocabulary.join(", ")))
+}
+
+/// The code of a language Whisper names in a `verbose_json` response
+/// ("czech" is "cs"). Codes are passed through.
+#[cfg(any(feature = "openai", feature = "groq", feature = "mistral"))]
+fn language_code(language: &str) -> Option<String> {
+    const NAMES: &[(&str, &str)] = &[
+        ("arabic", "ar"),
+        ("chinese", "zh"),
+        ("croatian", "hr"),
+        ("czech", "cs"),
+        ("danish", "da"),
+        ("dutch", "nl"),
+        ("english", "en"),
+        ("finnish", "fi"),
+        ("french", "fr"),
+        ("german", "de"),
+        ("greek", "el"),
+        ("hebrew", "he"),
+        ("hindi", "hi"),
+        ("hungarian", "hu"),
+        ("italian", "it"),
+        ("japanese", "ja"),
+        ("korean", "ko"),
+        ("norwegian", "no"),
+        ("polish", "pl"),
+        ("portuguese", "pt"),
+        ("romanian", "ro"),
+        ("russian", "ru"),
+        ("slovak", "sk"),
+        ("slovenian", "sl"),
+        ("spanish", "es"),
+        ("swedish", "sv"),
+        ("turkish", "tr"),
+        ("ukrainian", "uk"),
+        ("vietnamese", "vi"),
+    ];
+    let language = language.trim().to_lowercase();
+    if language.len() == 2 || language.contains('-') {
+        return Some(language);
+    }
+    NAMES
+        .iter()
+        .find(|(name, _)| *name == language)
+        .map(|(_, code)| code.to_string())
+}
+
+/// The segments of a Whisper-style `verbose_json` response
+#[cfg(any(feature = "openai", feature = "groq"))]
+fn verbose_segments(response: &serde_json::Value) -> Result<Vec<Segment>> {
+    let Some(segments) = response["segments"].as_array() else {
+        anyhow::bail!("Failed to get segments from response");
+    };
+    segments
+        .iter()
+        .map(|segment| {
+            let (Some(start), Some(end), Some(text)) = (
+                segment["start"].as_f64(),
+                segment["end"].as_f64(),
+                segment["text"].as_str(),
+            ) else {
+                anyhow::bail!("Malformed segment in response: {}", segment);
+            };
+            Ok(Segment {
+                start,
+                end,
+                text: text.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(feature = "google")]
 pub mod google;
+#[cfg(feature = "groq")]
 pub mod groq;
+#[cfg(feature = "mistral")]
 pub mod mistral;
+pub mod mock;
+#[cfg(feature = "openai")]
 pub mod openai;
+#[cfg(feature = "http")]
+mod upload;
+#[cfg(feature = "wyoming")]
+pub mod wyoming;
+
+#[cfg(feature = "http")]
+pub use upload::Upload;
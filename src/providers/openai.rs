@@ -2,14 +2,69 @@ use anyhow::{Context, Result};
 use async_trait::async_trait;
 
 use super::TranscriptionProvider;
+use crate::credentials::ApiKey;
 
 pub struct OpenAIProvider {
-    api_key: String,
+    api_key: ApiKey,
+    vocabulary: Vec<String>,
+    client: reqwest::Client,
 }
 
 impl OpenAIProvider {
-    pub fn new(api_key: String) -> Self {
-        Self { api_key }
+    const MODEL: &str = "whisper-1";
+
+    pub fn new(api_key: ApiKey) -> Self {
+        Self {
+            api_key,
+            vocabulary: Vec::new(),
+            client: super::http_client(),
+        }
+    }
+
+    /// Terms to steer the model towards, sent as its prompt
+    pub fn with_vocabulary(mut self, vocabulary: Vec<String>) -> Self {
+        self.vocabulary = vocabulary;
+        self
+    }
+
+    /// Send `audio_data` off, asking for `response_format` (the API's
+    /// default, plain JSON, if `None`)
+    async fn request(
+        &self,
+        audio_data: &[u8],
+        response_format: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        let api_key = self.api_key.resolve().await?;
+        let upload = super::Upload::new(
+            self.name(),
+            audio_data.to_vec(),
+            crate::audio::wav_seconds(audio_data),
+        );
+        let file_part = upload.part("recording.wav", "audio/wav")?;
+        let mut form = reqwest::multipart::Form::new()
+            .part("file", file_part)
+            .text("model", Self::MODEL);
+        if let Some(prompt) = super::whisper_prompt(&self.vocabulary) {
+            form = form.text("prompt", prompt);
+        }
+        if let Some(format) = response_format {
+            form = form.text("response_format", format.to_string());
+        }
+
+        say!("Sending request to OpenAI API...");
+        let response = super::send(
+            self.client
+                .post("https://api.openai.com/v1/audio/transcriptions")
+                .header("Authorization", format!("Bearer {}", api_key))
+                .multipart(form),
+            upload,
+        )
+        .await?;
+
+        response
+            .json()
+            .await
+            .context("Failed to parse API response as JSON")
     }
 }
 
@@ -20,40 +75,46 @@ impl TranscriptionProvider for OpenAIProvider {
     }
 
     async fn transcribe(&self, audio_data: &[u8], _sample_rate: u32) -> Result<String> {
-        let client = reqwest::Client::new();
-        let file_part = reqwest::multipart::Part::bytes(audio_data.to_vec())
-            .file_name("recording.wav")
-            .mime_str("audio/wav")?;
-        let form = reqwest::multipart::Form::new()
-            .part("file", file_part)
-            .text("model", "whisper-1");
-
-        println!("Sending request to OpenAI API...");
-        let response = client
-            .post("https://api.openai.com/v1/audio/transcriptions")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .multipart(form)
-            .timeout(super::API_TIMEOUT)
-            .send()
-            .await
-            .context("Failed to send request to OpenAI API")?;
-
-        println!("Got response with status: {}", response.status());
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(anyhow::anyhow!("API error: {}", error_text));
-        }
+        let result = self.request(audio_data, None).await?;
+        let Some(text) = result["text"].as_str() else {
+            anyhow::bail!("Failed to get transcription from response");
+        };
 
-        let result: serde_json::Value = response
-            .json()
-            .await
-            .context("Failed to parse API response as JSON")?;
+        Ok(text.to_string())
+    }
 
+    async fn transcribe_detecting(
+        &self,
+        audio_data: &[u8],
+        _sample_rate: u32,
+    ) -> Result<(String, Option<String>)> {
+        let result = self.request(audio_data, Some("verbose_json")).await?;
         let Some(text) = result["text"].as_str() else {
             anyhow::bail!("Failed to get transcription from response");
         };
+        let language = result["language"].as_str().and_then(super::language_code);
+        Ok((text.to_string(), language))
+    }
 
-        Ok(text.to_string())
+    async fn transcribe_segments(
+        &self,
+        audio_data: &[u8],
+        _sample_rate: u32,
+    ) -> Result<Vec<super::Segment>> {
+        let result = self.request(audio_data, Some("verbose_json")).await?;
+        super::verbose_segments(&result)
+    }
+
+    async fn check(&self) -> Result<()> {
+        let api_key = self.api_key.resolve().await?;
+        super::query(
+            self.name(),
+            self.client
+                .get(format!("https://api.openai.com/v1/models/{}", Self::MODEL))
+                .header("Authorization", format!("Bearer {}", api_key)),
+        )
+        .await?;
+        Ok(())
     }
 
     fn cost_per_minute(&self) -> Option<f64> {
@@ -0,0 +1,110 @@
+use anyhow::Result;
+use futures_util::StreamExt;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::output::{self, Mode};
+
+/// Size of the pieces the audio is streamed in, and so how often the
+/// progress bar moves
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Audio on its way to a provider. While it is being sent, a progress bar
+/// shows how much went out and the transfer rate; once it's all out, a
+/// spinner with the elapsed time shows the provider is still working on it.
+/// Both are drawn on stderr, and only when that is a terminal.
+pub struct Upload {
+    provider: String,
+    data: Vec<u8>,
+    audio_seconds: f64,
+    bar: ProgressBar,
+    /// Where to note how long sending took, see [`super::timing_upload`]
+    clock: Option<Arc<Mutex<Option<Duration>>>>,
+}
+
+impl Upload {
+    /// `data` is the encoded recording, `audio_seconds` how long it plays.
+    pub fn new(provider: &str, data: Vec<u8>, audio_seconds: f64) -> Self {
+        let target = match output::mode() {
+            Mode::Quiet => ProgressDrawTarget::hidden(),
+            _ => ProgressDrawTarget::stderr(),
+        };
+        let bar = ProgressBar::with_draw_target(Some(data.len() as u64), target);
+        bar.set_style(
+            ProgressStyle::with_template(
+                "Uploading to {msg} [{bar:30}] {bytes}/{total_bytes} ({bytes_per_sec})",
+            )
+            .expect("valid template")
+            .progress_chars("=> "),
+        );
+        bar.set_message(provider.to_string());
+        Self {
+            provider: provider.to_string(),
+            data,
+            audio_seconds,
+            bar,
+            clock: super::UPLOAD_TIME.try_with(Arc::clone).ok(),
+        }
+    }
+
+    pub fn provider(&self) -> &str {
+        &self.provider
+    }
+
+    pub fn audio_seconds(&self) -> f64 {
+        self.audio_seconds
+    }
+
+    /// The audio as a request body that moves the progress bar as it is sent
+    pub fn body(&self) -> reqwest::Body {
+        let bar = self.bar.clone();
+        let provider = self.provider.clone();
+        let clock = self.clock.clone();
+        let mut started = Instant::now();
+        let chunks: Vec<Vec<u8>> = self.data.chunks(CHUNK_SIZE).map(<[u8]>::to_vec).collect();
+        let stream = futures_util::stream::iter(chunks).map(move |chunk| {
+            if bar.position() == 0 {
+                // Rate and elapsed time count from the first byte, not from
+                // when the request was put together
+                bar.reset();
+                started = Instant::now();
+            }
+            bar.inc(chunk.len() as u64);
+            if bar.position() >= bar.length().unwrap_or(0) {
+                bar.set_style(
+                    ProgressStyle::with_template("{spinner} Waiting for {msg}... {elapsed}")
+                        .expect("valid template"),
+                );
+                bar.set_message(provider.clone());
+                bar.reset_elapsed();
+                bar.enable_steady_tick(Duration::from_millis(100));
+                if let Some(ref clock) = clock {
+                    *clock.lock().unwrap() = Some(started.elapsed());
+                }
+            }
+            Ok::<_, std::io::Error>(chunk)
+        });
+        reqwest::Body::wrap_stream(stream)
+    }
+
+    /// The audio as a multipart file part, see [`Upload::body`]
+    pub fn part(&self, file_name: &str, mime: &str) -> Result<reqwest::multipart::Part> {
+        Ok(
+            reqwest::multipart::Part::stream_with_length(self.body(), self.data.len() as u64)
+                .file_name(file_name.to_string())
+                .mime_str(mime)?,
+        )
+    }
+
+    /// Take the progress bar or spinner off the screen.
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+impl Drop for Upload {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
@@ -0,0 +1,216 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::sync::Mutex;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use super::TranscriptionProvider;
+
+/// Samples sent per `audio-chunk` event (a second of audio)
+const CHUNK_SAMPLES: usize = crate::SAMPLE_RATE as usize;
+
+/// Speech-to-text service speaking the Wyoming protocol, such as
+/// wyoming-faster-whisper or wyoming-vosk on a Home Assistant box. Events
+/// are a line of JSON, optionally followed by more JSON data and a binary
+/// payload, over plain TCP.
+pub struct WyomingProvider {
+    /// `host:port`
+    address: String,
+    /// Two-letter code the service is asked to transcribe, e.g. "en", or
+    /// [`super::DETECT_LANGUAGE`] to have it detect the language
+    language: Mutex<String>,
+}
+
+impl WyomingProvider {
+    /// Connect to `server` (`host:port`, optionally prefixed by `tcp://`)
+    /// for each request. Only the primary subtag of `language` is sent
+    /// ("cs" for "cs-CZ"), as that's what Wyoming services expect.
+    pub fn new(server: &str, language: &str) -> Self {
+        let address = server.strip_prefix("tcp://").unwrap_or(server);
+        Self {
+            address: address.trim_end_matches('/').to_string(),
+            language: Mutex::new(primary_subtag(language)),
+        }
+    }
+
+    async fn connect(&self) -> Result<BufReader<TcpStream>> {
+        let stream = TcpStream::connect(&self.address)
+            .await
+            .with_context(|| format!("Failed to connect to Wyoming service at {}", self.address))?;
+        Ok(BufReader::new(stream))
+    }
+
+    /// Answer of the service to `events`, the first event of type `wanted`
+    async fn exchange(
+        &self,
+        events: Vec<(Value, Vec<u8>)>,
+        wanted: &str,
+        audio_seconds: f64,
+    ) -> Result<Value> {
+        let conversation = async {
+            let mut stream = self.connect().await?;
+            for (header, payload) in events {
+                write_event(stream.get_mut(), header, &payload).await?;
+            }
+            stream.get_mut().flush().await?;
+            loop {
+                let (kind, data) = read_event(&mut stream).await?;
+                if kind == wanted {
+                    return Ok(data);
+                }
+                if kind == "error" {
+                    anyhow::bail!(
+                        "Wyoming service error: {}",
+                        data["text"].as_str().unwrap_or("unknown")
+                    );
+                }
+                // Anything else the service reports on the way is skipped
+            }
+        };
+        let timeout = super::timeout_for(audio_seconds);
+        tokio::time::timeout(timeout, conversation)
+            .await
+            .with_context(|| {
+                format!(
+                    "No answer from the Wyoming service within {} seconds (see --api-timeout)",
+                    timeout.as_secs()
+                )
+            })?
+    }
+}
+
+#[async_trait]
+impl TranscriptionProvider for WyomingProvider {
+    fn name(&self) -> &str {
+        "Wyoming"
+    }
+
+    fn set_language(&self, language: &str) {
+        *self.language.lock().unwrap() = primary_subtag(language);
+    }
+
+    async fn transcribe(&self, audio_data: &[u8], sample_rate: u32) -> Result<String> {
+        Ok(self.transcribe_detecting(audio_data, sample_rate).await?.0)
+    }
+
+    /// The language is the one the service reports, which Whisper-based
+    /// services do when they were asked to detect it
+    async fn transcribe_detecting(
+        &self,
+        audio_data: &[u8],
+        sample_rate: u32,
+    ) -> Result<(String, Option<String>)> {
+        let reader = hound::WavReader::new(audio_data).context("Failed to parse WAV data")?;
+        let samples: Vec<i16> = reader
+            .into_samples::<i16>()
+            .collect::<Result<_, _>>()
+            .context("Failed to read WAV samples")?;
+        let format = json!({ "rate": sample_rate, "width": 2, "channels": 1 });
+        let language = self.language.lock().unwrap().clone();
+        // Without a language the service detects it
+        let request = match language.as_str() {
+            super::DETECT_LANGUAGE => json!({}),
+            language => json!({ "language": language }),
+        };
+
+        let mut events = vec![
+            (json!({ "type": "transcribe", "data": request }), Vec::new()),
+            (json!({ "type": "audio-start", "data": format }), Vec::new()),
+        ];
+        for chunk in samples.chunks(CHUNK_SAMPLES) {
+            let payload = chunk.iter().flat_map(|s| s.to_le_bytes()).collect();
+            events.push((json!({ "type": "audio-chunk", "data": format }), payload));
+        }
+        events.push((json!({ "type": "audio-stop", "data": {} }), Vec::new()));
+
+        say!("Sending audio to Wyoming service at {}...", self.address);
+        let audio_seconds = samples.len() as f64 / sample_rate as f64;
+        let transcript = self.exchange(events, "transcript", audio_seconds).await?;
+        let Some(text) = transcript["text"].as_str() else {
+            anyhow::bail!("Failed to get transcription from Wyoming transcript event");
+        };
+        let language = transcript["language"].as_str().map(primary_subtag);
+        Ok((text.trim().to_string(), language))
+    }
+
+    /// Ask the service what it offers instead of transcribing anything
+    async fn check(&self) -> Result<()> {
+        let info = self
+            .exchange(
+                vec![(json!({ "type": "describe" }), Vec::new())],
+                "info",
+                0.0,
+            )
+            .await?;
+        let installed = info["asr"].as_array().is_some_and(|asr| {
+            asr.iter()
+                .any(|service| service["installed"].as_bool().unwrap_or(true))
+        });
+        if !installed {
+            anyhow::bail!(
+                "Wyoming service at {} offers no speech-to-text",
+                self.address
+            );
+        }
+        Ok(())
+    }
+
+    fn cost_per_minute(&self) -> Option<f64> {
+        None
+    }
+}
+
+/// Send an event: its header as a line of JSON, then the payload, if any
+async fn write_event(stream: &mut TcpStream, mut header: Value, payload: &[u8]) -> Result<()> {
+    if !payload.is_empty() {
+        header["payload_length"] = json!(payload.len());
+    }
+    let mut line = serde_json::to_vec(&header)?;
+    line.push(b'\n');
+    stream
+        .write_all(&line)
+        .await
+        .context("Failed to send to the Wyoming service")?;
+    stream
+        .write_all(payload)
+        .await
+        .context("Failed to send to the Wyoming service")?;
+    Ok(())
+}
+
+/// Receive an event: its type and data. The data may be in the header or
+/// follow it; payloads are skipped, as no event we wait for has one.
+async fn read_event(stream: &mut BufReader<TcpStream>) -> Result<(String, Value)> {
+    let mut line = String::new();
+    if stream.read_line(&mut line).await? == 0 {
+        anyhow::bail!("Wyoming service closed the connection without answering");
+    }
+    let header: Value =
+        serde_json::from_str(&line).context("Invalid event from the Wyoming service")?;
+    let mut data = header.get("data").cloned().unwrap_or_else(|| json!({}));
+    if let Some(length) = header["data_length"].as_u64().filter(|&n| n > 0) {
+        let mut extra = vec![0; length as usize];
+        stream.read_exact(&mut extra).await?;
+        let extra: Value = serde_json::from_slice(&extra)
+            .context("Invalid event data from the Wyoming service")?;
+        if let (Some(data), Value::Object(extra)) = (data.as_object_mut(), extra) {
+            data.extend(extra);
+        }
+    }
+    if let Some(length) = header["payload_length"].as_u64().filter(|&n| n > 0) {
+        let mut payload = vec![0; length as usize];
+        stream.read_exact(&mut payload).await?;
+    }
+    let kind = header["type"].as_str().unwrap_or_default().to_string();
+    Ok((kind, data))
+}
+
+/// "cs" for "cs-CZ"
+fn primary_subtag(language: &str) -> String {
+    language
+        .split(['-', '_'])
+        .next()
+        .unwrap_or(language)
+        .to_lowercase()
+}
@@ -0,0 +1,134 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// How the letters of a transcript are cased
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Case {
+    /// As the provider wrote them
+    #[default]
+    Preserve,
+    /// All lowercase, e.g. for commands typed into a terminal
+    Lower,
+    /// Every sentence starting with a capital letter, for providers that
+    /// leave some out; the rest is left alone
+    Sentence,
+}
+
+/// Sentence starters that providers capitalize only because every utterance
+/// is transcribed as if it began a new sentence. When such a word continues
+/// an unfinished sentence it is lowercased again; anything else (names,
+/// acronyms) is left alone.
+const CONTINUATION_WORDS: &[&str] = &[
+    "a", "about", "after", "also", "an", "and", "are", "as", "at", "because", "before", "but",
+    "by", "for", "from", "he", "her", "his", "if", "in", "is", "it", "its", "my", "no", "not",
+    "of", "on", "or", "our", "she", "so", "that", "the", "their", "then", "there", "they", "this",
+    "to", "was", "we", "were", "when", "which", "while", "with", "you", "your",
+];
+
+/// Rewrite `next` so it reads naturally when typed right after `prev`.
+///
+/// Providers transcribe each utterance as a standalone sentence, so naively
+/// appending one dictation to another gives "end of sentence.And another
+/// thought." This inserts the separating space and fixes the capitalization
+/// of the first word: capitalized after a sentence end, lowercased again when
+/// `prev` stopped mid-sentence.
+pub fn join(prev: &str, next: &str) -> String {
+    let next = next.trim_start();
+    let Some(last) = prev.chars().last() else {
+        return next.to_string();
+    };
+    let Some(first) = next.chars().next() else {
+        return String::new();
+    };
+
+    let separator = if last.is_whitespace() || ",.;:!?)]}".contains(first) {
+        ""
+    } else {
+        " "
+    };
+
+    let prev_end = prev
+        .trim_end()
+        .trim_end_matches(['"', '\'', ')', ']', '”', '’']);
+    let next = if prev_end.ends_with(['.', '!', '?', '…']) {
+        capitalize_first(next)
+    } else if continues_sentence(next) {
+        lowercase_first(next)
+    } else {
+        next.to_string()
+    };
+
+    format!("{}{}", separator, next)
+}
+
+/// Whether the first word of `text` is a capitalized continuation word.
+fn continues_sentence(text: &str) -> bool {
+    let word: String = text.chars().take_while(|c| c.is_alphabetic()).collect();
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(c) if c.is_uppercase() && chars.all(|c| c.is_lowercase()) => {
+            CONTINUATION_WORDS.contains(&word.to_lowercase().as_str())
+        }
+        _ => false,
+    }
+}
+
+fn capitalize_first(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn lowercase_first(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) => c.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// `text` cased as `case` says
+pub fn apply_case(text: &str, case: Case) -> String {
+    match case {
+        Case::Preserve => text.to_string(),
+        Case::Lower => text.to_lowercase(),
+        Case::Sentence => {
+            let mut out = String::with_capacity(text.len());
+            let mut sentence_start = true;
+            let mut prev = ' ';
+            for c in text.chars() {
+                if ".!?…".contains(prev) && c.is_whitespace() {
+                    sentence_start = true;
+                }
+                if sentence_start && c.is_alphanumeric() {
+                    out.extend(c.to_uppercase());
+                    sentence_start = false;
+                } else {
+                    out.push(c);
+                }
+                prev = c;
+            }
+            out
+        }
+    }
+}
+
+/// `text` without the full stop providers put at the end of everything,
+/// which is out of place in a command or a search. An ellipsis, "?", and
+/// "!" stay.
+pub fn strip_trailing_period(text: &str) -> String {
+    let trimmed = text.trim_end();
+    match trimmed.strip_suffix('.') {
+        Some(rest) if !rest.ends_with('.') => rest.to_string(),
+        _ => text.to_string(),
+    }
+}
+
+/// `text` with every run of whitespace, including line breaks, made a
+/// single space, and none at either end
+pub fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,76 @@
+use anyhow::{Context, Result};
+use evdev::{EventSummary, KeyCode};
+use std::str::FromStr;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::daemon::Request;
+
+/// Key state values in evdev key events
+const KEY_RELEASED: i32 = 0;
+const KEY_PRESSED: i32 = 1;
+
+/// Record while `key` is held: pressing it sends `start` to the daemon loop,
+/// releasing it sends `stop`.
+///
+/// `key` is an evdev key name such as `KEY_RIGHTCTRL` or `BTN_EXTRA`. Every
+/// input device that has the key is watched. Reading /dev/input needs
+/// membership in the `input` group (or root). The key still reaches the
+/// focused application, so pick one that does nothing on its own.
+pub async fn run(key: String, requests: mpsc::Sender<Request>) -> Result<()> {
+    let code = KeyCode::from_str(&key)
+        .map_err(|_| anyhow::anyhow!("Unknown key '{}' (expected e.g. KEY_RIGHTCTRL)", key))?;
+
+    let (event_tx, mut event_rx) = mpsc::channel::<i32>(16);
+    let mut watched = 0;
+    for (path, device) in evdev::enumerate() {
+        if !device
+            .supported_keys()
+            .is_some_and(|keys| keys.contains(code))
+        {
+            continue;
+        }
+        let mut events = device
+            .into_event_stream()
+            .with_context(|| format!("Failed to watch {}", path.display()))?;
+        let event_tx = event_tx.clone();
+        tokio::spawn(async move {
+            while let Ok(event) = events.next_event().await {
+                if let EventSummary::Key(_, c, value) = event.destructure() {
+                    if c == code && event_tx.send(value).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        watched += 1;
+    }
+    drop(event_tx);
+    if watched == 0 {
+        anyhow::bail!(
+            "No readable input device has {} (is the user in the 'input' group?)",
+            key
+        );
+    }
+    println!("Push-to-talk on {} ({} devices)", key, watched);
+
+    while let Some(value) = event_rx.recv().await {
+        let command = match value {
+            KEY_PRESSED => "start",
+            KEY_RELEASED => "stop",
+            // Autorepeat while held
+            _ => continue,
+        };
+        let (reply_tx, reply_rx) = oneshot::channel();
+        if requests
+            .send((command.to_string(), reply_tx))
+            .await
+            .is_err()
+        {
+            break;
+        }
+        if let Ok(Err(e)) = reply_rx.await {
+            eprintln!("{}", e);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,606 @@
+//! Recording from the terminal: the one-shot `rpdictation` run, which
+//! records until it is told to stop and then transcribes, and the pieces of
+//! it the daemon shares.
+
+use anyhow::{Context, Result};
+use futures_util::future::BoxFuture;
+use nix::sys::termios::{
+    tcgetattr, tcsetattr, LocalFlags, SetArg, SpecialCharacterIndices, Termios,
+};
+use std::env;
+use std::io::IsTerminal;
+use std::os::fd::AsRawFd;
+use tokio::io::AsyncReadExt;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio_util::sync::CancellationToken;
+
+use crate::notifier::{self, send_notification};
+use crate::setup::{
+    app_style, build_session, build_typing_queue, capture_focus, spawn_overlay, Options,
+};
+use crate::typing_queue::{Ticket, TypingQueue};
+use crate::{audio, get_fifo_path, get_pid_path, output, vad, DictationSession};
+
+/// How often --live transcribes the recording so far
+pub const LIVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How often continuous mode looks for pauses in the recording
+pub const VAD_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Puts the terminal into non-canonical, no-echo mode so single key presses
+/// can be read as they happen. Restores the original settings on drop.
+struct RawTerminal {
+    original: Termios,
+}
+
+impl RawTerminal {
+    fn enable() -> Option<Self> {
+        let fd = std::io::stdin().as_raw_fd();
+        let original = tcgetattr(fd).ok()?;
+        let mut raw = original.clone();
+        raw.local_flags
+            .remove(LocalFlags::ICANON | LocalFlags::ECHO);
+        raw.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+        raw.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+        tcsetattr(fd, SetArg::TCSANOW, &raw).ok()?;
+        Some(Self { original })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = tcsetattr(
+            std::io::stdin().as_raw_fd(),
+            SetArg::TCSANOW,
+            &self.original,
+        );
+    }
+}
+
+/// The next byte typed on stdin, or `None` at EOF. Unlike reading through
+/// `tokio::io::stdin()`, dropping the future leaves no read pending in a
+/// background thread, which would swallow the answer to --confirm.
+async fn read_key(stdin: &tokio::io::unix::AsyncFd<std::io::Stdin>) -> std::io::Result<Option<u8>> {
+    loop {
+        let mut guard = stdin.readable().await?;
+        let mut byte = [0u8; 1];
+        match guard
+            .try_io(|fd| nix::unistd::read(fd.as_raw_fd(), &mut byte).map_err(std::io::Error::from))
+        {
+            Ok(Ok(0)) => return Ok(None),
+            Ok(Ok(_)) => return Ok(Some(byte[0])),
+            Ok(Err(e)) => return Err(e),
+            // Spurious wakeup
+            Err(_) => continue,
+        }
+    }
+}
+
+/// What a control source asked the recording to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopAction {
+    /// Stop recording and transcribe
+    Stop,
+    /// Stop recording and discard the audio
+    Cancel,
+}
+
+/// --delay: count down the seconds before recording, on the terminal (with
+/// a bell) and in the notification
+pub async fn countdown(options: &Options) {
+    let Some(seconds) = options.delay.filter(|&s| s > 0) else {
+        return;
+    };
+    for left in (1..=seconds).rev() {
+        say_inline!("\rRecording in {}...\x07", left);
+        if notifier::get().live() {
+            send_notification(&format!("Recording in {}...", left), false).await;
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    }
+    say_inline!("\r\x1b[K");
+}
+
+/// Show the persistent "Recording" notification, with Stop and Cancel
+/// buttons, until `cancel_token` fires. The receiver gets what the user
+/// picked; clicking the notification itself stops. When the notifier has no
+/// buttons, the receiver only closes once `cancel_token` fires.
+pub fn spawn_recording_notification(
+    cancel_token: CancellationToken,
+) -> Result<(
+    tokio::task::JoinHandle<Result<()>>,
+    tokio::sync::oneshot::Receiver<StopAction>,
+)> {
+    let (action_tx, action_rx) = tokio::sync::oneshot::channel();
+    let handle = tokio::spawn(async move {
+        let picked = notifier::get()
+            .ask(
+                "Recording 00:00",
+                None,
+                &[("stop", "Stop"), ("cancel", "Cancel")],
+                cancel_token,
+            )
+            .await;
+        // Without a pick action_tx is dropped only now, so a missing
+        // notification doesn't count as a stop request
+        if let Some(picked) = picked {
+            let action = match picked.as_str() {
+                "cancel" => StopAction::Cancel,
+                _ => StopAction::Stop,
+            };
+            action_tx
+                .send(action)
+                .map_err(|_| anyhow::anyhow!("Failed to send notify signal"))?;
+        }
+        eprintln!("notify exit");
+        Ok::<_, anyhow::Error>(())
+    });
+    Ok((handle, action_rx))
+}
+
+/// How many words at the start of `current` have settled: they came out the
+/// same in the `previous` preview. The last word is left out, as the
+/// recording may have cut it off mid-word.
+fn settled_words(previous: &[String], current: &[String]) -> usize {
+    previous
+        .iter()
+        .zip(current)
+        .take_while(|(a, b)| a == b)
+        .count()
+        .min(current.len().saturating_sub(1))
+}
+
+/// Show the elapsed recording time in the terminal and the notification,
+/// updated once a second until `cancel_token` fires. With --live the text
+/// recognized so far, from `preview`, follows it on the same terminal line.
+pub fn spawn_timer(
+    cancel_token: CancellationToken,
+    mut preview: tokio::sync::watch::Receiver<String>,
+) -> tokio::task::JoinHandle<Result<()>> {
+    tokio::spawn(async move {
+        let start_time = tokio::time::Instant::now();
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
+        loop {
+            let tick = tokio::select! {
+                _ = cancel_token.cancelled() => { break; }
+                _ = interval.tick() => true,
+                Ok(()) = preview.changed() => false,
+            };
+            let elapsed = start_time.elapsed();
+            let minutes = elapsed.as_secs() / 60;
+            let seconds = elapsed.as_secs() % 60;
+
+            // Update notification (fire-and-forget, replaces the previous
+            // one)
+            if tick && notifier::get().live() {
+                let message = format!("Recording {:02}:{:02}", minutes, seconds);
+                tokio::spawn(async move { send_notification(&message, false).await });
+            }
+
+            // Keep terminal output
+            let length = format!("Recording length: {:02}:{:02}", minutes, seconds);
+            let text = preview.borrow_and_update().clone();
+            if text.is_empty() {
+                say_inline!("\r{}", length);
+            } else {
+                // Only the end of the text if it doesn't fit, with the line
+                // cleared after it in case the new text is shorter
+                let width = env::var("COLUMNS")
+                    .ok()
+                    .and_then(|c| c.parse().ok())
+                    .unwrap_or(80usize);
+                let room = width.saturating_sub(length.len() + 4);
+                let chars: Vec<char> = text.chars().collect();
+                let shown: String = if chars.len() > room {
+                    std::iter::once('…')
+                        .chain(chars[chars.len() + 1 - room.max(1)..].iter().copied())
+                        .collect()
+                } else {
+                    text
+                };
+                say_inline!("\r{} | {}\x1b[K", length, shown);
+            }
+        }
+        eprintln!("timer exit");
+        Ok::<_, anyhow::Error>(())
+    })
+}
+
+/// Continuous mode: transcribe and type one segment. The first segment uses
+/// the ticket and utterance ID set up when recording started; later ones get
+/// their own. A failed segment is logged and the session goes on.
+async fn process_segment(
+    options: &Options,
+    session: &DictationSession,
+    typing_queue: &mut Option<TypingQueue>,
+    first: &mut Option<(Option<Ticket>, uuid::Uuid)>,
+    samples: Vec<i16>,
+) {
+    let (ticket, utterance_id) = match first.take() {
+        Some(first) => first,
+        None => {
+            let focus = capture_focus(options).await;
+            let style = app_style(options).await;
+            let ticket = typing_queue.as_mut().map(|q| q.reserve(focus, style));
+            let utterance_id = uuid::Uuid::new_v4();
+            eprintln!("Utterance ID: {}", utterance_id);
+            (ticket, utterance_id)
+        }
+    };
+    if let Err(e) = session.process(samples, ticket, utterance_id).await {
+        eprintln!("Error: {}", e);
+    }
+}
+
+/// One-shot mode: record until one of the stop sources fires, then
+/// transcribe. The stop sources are Enter and Esc on the terminal, the stop
+/// FIFO, the notification, and SIGUSR1 and SIGUSR2.
+pub async fn record_once(options: &Options) -> Result<()> {
+    let session = build_session(options)?;
+    spawn_overlay(options, &session);
+    let mut typing_queue = build_typing_queue(options).await?;
+
+    // Before anything else, as the window to type into is the one focused
+    // once it's over
+    countdown(options).await;
+
+    // Capture focused window at recording start
+    let focus = capture_focus(options).await;
+    let style = app_style(options).await;
+    let ticket = typing_queue.as_mut().map(|q| q.reserve(focus, style));
+
+    // Every dictation gets its own ID so log lines and anything derived from
+    // this run can be correlated later.
+    let utterance_id = uuid::Uuid::new_v4();
+    eprintln!("Utterance ID: {}", utterance_id);
+
+    let recorder = match options.replay {
+        Some(ref path) => audio::Recorder::replay(
+            audio::decode_file(path)
+                .await
+                .with_context(|| format!("Failed to read {}", path.display()))?,
+        ),
+        None => audio::Recorder::open(options.device.as_deref())?,
+    };
+    recorder.start();
+    session.start(utterance_id)?;
+
+    let fifo_path = get_fifo_path();
+    if tokio::fs::metadata(&fifo_path).await.is_ok() {
+        tokio::fs::remove_file(&fifo_path).await?;
+    }
+    nix::unistd::mkfifo(&fifo_path, nix::sys::stat::Mode::S_IRWXU)?;
+
+    // Write PID file
+    let pid_path = get_pid_path();
+    tokio::fs::write(&pid_path, std::process::id().to_string()).await?;
+
+    let stdin_is_tty = std::io::stdin().is_terminal();
+
+    // Read single key presses (Enter to stop, Esc to cancel) without
+    // waiting for a newline; the original terminal mode is restored on drop.
+    let raw_terminal = if stdin_is_tty {
+        RawTerminal::enable()
+    } else {
+        None
+    };
+
+    say!("Recording... Stop with:");
+    say!("- Run: rpdictation stop, or");
+    if stdin_is_tty {
+        say!("- Press Enter, or");
+    }
+    say!("- Run: echo x > {}, or", fifo_path.display());
+    if let Some(duration) = options.duration {
+        say!(
+            "- Wait {} s for it to stop by itself, or",
+            duration.as_secs()
+        );
+    }
+    say!("- Click the notification");
+    say!("Cancel (discard audio) with:");
+    say!("- Run: rpdictation cancel, or");
+    if stdin_is_tty {
+        say!("- Press Esc, or");
+    }
+    say!("- Run: echo cancel > {}", fifo_path.display());
+    say!();
+
+    let cancel_token = CancellationToken::new();
+
+    let (preview_tx, preview_rx) = tokio::sync::watch::channel(String::new());
+    let timer_handle = spawn_timer(cancel_token.clone(), preview_rx);
+
+    let (stdin_tx, mut stdin_rx) = tokio::sync::oneshot::channel::<StopAction>();
+    let stdin_handle = tokio::spawn({
+        let cancel_token = cancel_token.clone();
+        async move {
+            if !stdin_is_tty {
+                // Not a TTY, just wait for cancellation
+                cancel_token.cancelled().await;
+                eprintln!("stdin exit (not a tty)");
+                return Ok::<_, anyhow::Error>(());
+            }
+
+            let stdin = match tokio::io::unix::AsyncFd::new(std::io::stdin()) {
+                Ok(stdin) => stdin,
+                Err(e) => {
+                    eprintln!("Warning: Can't watch the terminal for key presses: {}", e);
+                    cancel_token.cancelled().await;
+                    return Ok(());
+                }
+            };
+            let action = loop {
+                tokio::select! {
+                    _ = cancel_token.cancelled() => break None,
+                    r = read_key(&stdin) => match r {
+                        Ok(Some(b'\n' | b'\r')) => break Some(StopAction::Stop),
+                        Ok(Some(0x1b)) => break Some(StopAction::Cancel),
+                        Ok(Some(_)) => continue,
+                        // EOF or read error, leave it to the other sources
+                        _ => {
+                            cancel_token.cancelled().await;
+                            break None;
+                        }
+                    },
+                }
+            };
+            if let Some(action) = action {
+                stdin_tx
+                    .send(action)
+                    .map_err(|_| anyhow::anyhow!("Failed to send stdin signal"))?;
+            }
+            eprintln!("stdin exit");
+            Ok::<_, anyhow::Error>(())
+        }
+    });
+
+    let (fifo_tx, mut fifo_rx) = tokio::sync::oneshot::channel::<StopAction>();
+    let fifo_handle = tokio::spawn({
+        let cancel_token = cancel_token.clone();
+        let fifo_path = fifo_path.clone();
+        async move {
+            eprintln!("fifo open");
+            let read_fifo = async {
+                let mut contents = String::new();
+                tokio::fs::File::open(&fifo_path)
+                    .await?
+                    .read_to_string(&mut contents)
+                    .await?;
+                Ok::<_, std::io::Error>(contents)
+            };
+            tokio::select! {
+                _ = cancel_token.cancelled() => {}
+                contents = read_fifo => {
+                    let action = match contents {
+                        Ok(c) if c.trim() == "cancel" => StopAction::Cancel,
+                        _ => StopAction::Stop,
+                    };
+                    fifo_tx.send(action).map_err(|_| anyhow::anyhow!("Failed to send fifo signal"))?;
+                }
+            }
+            /*
+            let mut fifo = File::open(&fifo_path).await?;
+            let mut buf = [0u8; 1];
+            eprintln!("fifo select");
+            tokio::select! {
+                _ = cancel_token.cancelled() => {}
+                /*_ = fifo.read(&mut buf) => {
+                    fifo_tx.send(()).map_err(|_| anyhow::anyhow!("Failed to send fifo signal"))?;
+                }*/
+            }
+            */
+            eprintln!("fifo exit");
+            Ok::<_, anyhow::Error>(())
+        }
+    });
+
+    let (notify_handle, mut notify_rx) = spawn_recording_notification(cancel_token.clone())?;
+
+    let (signal_tx, mut signal_rx) = tokio::sync::oneshot::channel();
+    let signal_handle = tokio::spawn({
+        let cancel_token = cancel_token.clone();
+        async move {
+            let mut sig_stop =
+                signal(SignalKind::user_defined1()).context("Failed to create signal handler")?;
+            let mut sig_cancel =
+                signal(SignalKind::user_defined2()).context("Failed to create signal handler")?;
+            tokio::select! {
+                _ = cancel_token.cancelled() => {}
+                _ = sig_stop.recv() => {
+                    signal_tx.send(StopAction::Stop).ok();
+                }
+                _ = sig_cancel.recv() => {
+                    signal_tx.send(StopAction::Cancel).ok();
+                }
+            }
+            eprintln!("signal exit");
+            Ok::<_, anyhow::Error>(())
+        }
+    });
+
+    // Ctrl+C and SIGTERM stop the recording like `rpdictation stop`; the
+    // same streams then catch a second one, which abandons the transcription.
+    let mut sig_int = signal(SignalKind::interrupt()).context("Failed to create signal handler")?;
+    let mut sig_term =
+        signal(SignalKind::terminate()).context("Failed to create signal handler")?;
+    // SIGUSR2 (`rpdictation cancel`) after recording stopped cancels the
+    // transcription and typing
+    let mut sig_cancel =
+        signal(SignalKind::user_defined2()).context("Failed to create signal handler")?;
+
+    // In continuous mode the recording is cut at pauses, and each segment is
+    // transcribed and typed while recording goes on.
+    let mut segmenter = options
+        .continuous
+        .then(|| vad::Segmenter::new(options.vad_threshold));
+    let mut vad_tick = tokio::time::interval(VAD_INTERVAL);
+    let mut first = Some((ticket, utterance_id));
+
+    // With --live, what has been said so far is transcribed every few
+    // seconds and shown next to the recording length; one request at a time
+    let mut live_tick = tokio::time::interval(LIVE_INTERVAL);
+    let mut preview: Option<BoxFuture<Result<Option<String>>>> = None;
+    // With --live-type, the previous preview's words and how many of them
+    // have been typed
+    let mut heard: Vec<String> = Vec::new();
+    let mut typed = 0;
+
+    let auto_stop = tokio::time::sleep(options.duration.unwrap_or_default());
+    tokio::pin!(auto_stop);
+
+    let (source, action) = loop {
+        tokio::select! {
+            r = &mut stdin_rx => break ("stdin", r.unwrap_or(StopAction::Stop)),
+            r = &mut fifo_rx => break ("fifo", r.unwrap_or(StopAction::Stop)),
+            r = &mut notify_rx => break ("notify", r.unwrap_or(StopAction::Stop)),
+            r = &mut signal_rx => break ("signal", r.unwrap_or(StopAction::Stop)),
+            _ = sig_int.recv() => break ("SIGINT", StopAction::Stop),
+            _ = sig_term.recv() => break ("SIGTERM", StopAction::Stop),
+            // A replayed file is "recorded" all at once
+            _ = std::future::ready(()), if options.replay.is_some() => break ("replay", StopAction::Stop),
+            _ = &mut auto_stop, if options.duration.is_some() => break ("duration", StopAction::Stop),
+            _ = vad_tick.tick(), if segmenter.is_some() => {
+                let segments = segmenter
+                    .as_mut()
+                    .map(|s| s.push(&recorder.drain()))
+                    .unwrap_or_default();
+                for segment in segments {
+                    process_segment(options, &session, &mut typing_queue, &mut first, segment)
+                        .await;
+                }
+            }
+            _ = live_tick.tick(), if (options.live || options.live_type) && preview.is_none() => {
+                preview = Some(Box::pin(output::silenced(
+                    session.preview(utterance_id, recorder.snapshot()),
+                )));
+            }
+            r = async { preview.as_mut().expect("checked by the guard").await }, if preview.is_some() => {
+                preview = None;
+                match r {
+                    Ok(Some(text)) => {
+                        let words: Vec<String> = text.split_whitespace().map(str::to_string).collect();
+                        if let Some((Some(ticket), _)) = first.as_mut().filter(|_| options.live_type) {
+                            let settled = settled_words(&heard, &words);
+                            if settled > typed {
+                                match ticket.deliver_part(words[typed..settled].join(" ")).await {
+                                    Ok(()) => typed = settled,
+                                    Err(e) => eprintln!("[{}] Live typing failed: {:#}", utterance_id, e),
+                                }
+                            }
+                        }
+                        heard = words;
+                        preview_tx.send_replace(text);
+                    }
+                    Ok(None) => {}
+                    Err(e) => eprintln!("[{}] Live preview failed: {:#}", utterance_id, e),
+                }
+            }
+        }
+    };
+    drop(preview);
+    eprintln!("[{}] {:?} by {}", utterance_id, action, source);
+
+    cancel_token.cancel();
+
+    /*
+        stdin_rx.close();
+        fifo_rx.close();
+        notify_rx.close();
+    */
+
+    eprintln!("joining");
+    //timer_handle.await??;
+    //stdin_handle.await??;
+    //fifo_handle.await??;
+    //notify_handle.await??;
+    let _ = tokio::try_join!(
+        timer_handle,
+        stdin_handle,
+        fifo_handle,
+        notify_handle,
+        signal_handle
+    )
+    .map_err(|_| anyhow::anyhow!("Failed to join"))?;
+    eprintln!("joined");
+    if !preview_tx.borrow().is_empty() {
+        // Keep the last preview on screen
+        say!();
+    }
+
+    tokio::fs::remove_file(&fifo_path).await?;
+
+    let samples = recorder.stop();
+    drop(recorder);
+    drop(raw_terminal);
+
+    if action == StopAction::Cancel {
+        let _ = tokio::fs::remove_file(get_pid_path()).await;
+        session.cancel()?;
+        say!();
+        say!("Recording cancelled, discarding audio.");
+        send_notification("Recording cancelled", true).await;
+        return Ok(());
+    }
+
+    session.stop()?;
+    if source == "SIGINT" {
+        say!();
+        say!("Stopping; press Ctrl+C again to discard the transcription.");
+    }
+
+    // The PID file stays until the dictation is output, so that it can
+    // still be cancelled
+    let cancelled = CancellationToken::new();
+    let process = async {
+        match segmenter {
+            Some(mut segmenter) => {
+                for segment in segmenter
+                    .push(&samples)
+                    .into_iter()
+                    .chain(segmenter.finish())
+                {
+                    if cancelled.is_cancelled() {
+                        break;
+                    }
+                    process_segment(options, &session, &mut typing_queue, &mut first, segment)
+                        .await;
+                }
+            }
+            None => {
+                let (ticket, utterance_id) = first.take().expect("single recording processed once");
+                session.process(samples, ticket, utterance_id).await?;
+            }
+        }
+        if let Some(queue) = typing_queue {
+            queue.finish().await?;
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+    tokio::pin!(process);
+    let result = loop {
+        tokio::select! {
+            r = &mut process => break r,
+            _ = sig_cancel.recv() => {
+                eprintln!("[{}] Cancel by SIGUSR2", utterance_id);
+                cancelled.cancel();
+                session.cancel_processing();
+            }
+            _ = sig_int.recv() => {
+                eprintln!("[{}] Cancel by SIGINT", utterance_id);
+                say!("Transcription cancelled.");
+                send_notification("Transcription cancelled", true).await;
+                break Ok(());
+            }
+            _ = sig_term.recv() => {
+                eprintln!("[{}] Cancel by SIGTERM", utterance_id);
+                break Ok(());
+            }
+        }
+    };
+    let _ = tokio::fs::remove_file(get_pid_path()).await;
+    result?;
+
+    eprintln!("[{}] exit", utterance_id);
+    Ok(())
+}
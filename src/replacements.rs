@@ -0,0 +1,119 @@
+use anyhow::{Context, Result};
+use regex::{NoExpand, Regex};
+use std::path::Path;
+
+/// Rewrites for words and phrases a provider keeps getting wrong (names,
+/// codenames, jargon), applied to every transcript before it is output.
+///
+/// The file has one rule per line, `from => to`:
+///
+/// ```text
+/// # Literal phrases match whole words, ignoring case
+/// cube control => kubectl
+/// pod Gorny => Podgorny
+/// # Between slashes, a regular expression; $1 etc. refer to its groups
+/// /\bv(\d+) dot (\d+)\b/ => v$1.$2
+/// # A trailing i makes it ignore case
+/// /\bum+\b,? ?/i =>
+/// ```
+///
+/// Rules are applied in order, each to the output of the one before.
+#[derive(Default)]
+pub struct Replacements {
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    pattern: Regex,
+    replacement: String,
+    /// Whether `$1` in the replacement refers to a capture group
+    expand: bool,
+}
+
+impl Replacements {
+    pub fn load(path: &Path) -> Result<Self> {
+        let path = &crate::expand_home(path.to_path_buf());
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid replacements in {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let rule = Rule::parse(line).with_context(|| format!("line {}", i + 1))?;
+            rules.push(rule);
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// `text` with every rule applied
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for rule in &self.rules {
+            let replaced = if rule.expand {
+                rule.pattern.replace_all(&text, rule.replacement.as_str())
+            } else {
+                rule.pattern.replace_all(&text, NoExpand(&rule.replacement))
+            };
+            text = replaced.into_owned();
+        }
+        text
+    }
+}
+
+impl Rule {
+    fn parse(line: &str) -> Result<Self> {
+        let Some((from, to)) = line.split_once("=>") else {
+            anyhow::bail!("expected 'from => to', got '{}'", line);
+        };
+        let (from, replacement) = (from.trim(), to.trim().to_string());
+        if from.is_empty() {
+            anyhow::bail!("nothing to replace in '{}'", line);
+        }
+
+        let regex = from
+            .strip_prefix('/')
+            .and_then(|r| match r.strip_suffix("/i") {
+                Some(r) => Some(format!("(?i){}", r)),
+                None => r.strip_suffix('/').map(str::to_string),
+            });
+        if let Some(regex) = regex {
+            let pattern =
+                Regex::new(&regex).with_context(|| format!("invalid regex '{}'", from))?;
+            return Ok(Self {
+                pattern,
+                replacement,
+                expand: true,
+            });
+        }
+
+        // Whole words only, so "cat" doesn't rewrite "concatenate"; \b only
+        // works next to a word character
+        let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+        let start = if is_word(from.chars().next()) {
+            r"\b"
+        } else {
+            ""
+        };
+        let end = if is_word(from.chars().last()) {
+            r"\b"
+        } else {
+            ""
+        };
+        let pattern = Regex::new(&format!("(?i){}{}{}", start, regex::escape(from), end))?;
+        Ok(Self {
+            pattern,
+            replacement,
+            expand: false,
+        })
+    }
+}
@@ -0,0 +1,26 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// A step that turns the finished transcript into something else, such as a
+/// summary, usually with a remote model. Rewriters run one after another on
+/// the text the local [`Postprocess`](crate::postprocess::Postprocess) left;
+/// one that fails is skipped, so the dictation itself is never lost.
+#[async_trait]
+pub trait Rewriter: Send + Sync {
+    /// What it's doing, for progress messages ("Summarizing (Groq)")
+    fn describe(&self) -> String;
+
+    async fn rewrite(&self, text: &str) -> Result<String>;
+}
+
+/// What --summarize outputs
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SummaryMode {
+    /// The summary instead of the transcript
+    Replace,
+    /// The summary, then the transcript
+    Append,
+}
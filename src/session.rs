@@ -0,0 +1,779 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Instant;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+use crate::confirm::{Confirm, CostLimit};
+use crate::error::Error;
+use crate::metrics::{self, Timings};
+use crate::notifier::{send_notification, send_notification_with_body};
+use crate::postprocess::Postprocess;
+use crate::providers::{self, Segment, TranscriptionProvider};
+use crate::rewrite::Rewriter;
+use crate::sinks::{OutputSink, Transcript};
+use crate::typing_queue::Ticket;
+use crate::voice_commands::{VoiceCommand, VoiceCommands};
+use crate::{audio, output, BYTES_PER_SAMPLE, MIN_RECORDING_DURATION_SECONDS, SAMPLE_RATE};
+
+/// Longest transcript excerpt shown in the completion notification
+const NOTIFICATION_PREVIEW_CHARS: usize = 120;
+
+/// Events buffered for a subscriber that falls behind
+const EVENT_CAPACITY: usize = 64;
+
+/// What the session's microphone is doing. Transcription runs in the
+/// background, so a new recording can start while earlier ones are still
+/// being processed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Idle,
+    Recording { id: uuid::Uuid, since: Instant },
+}
+
+/// Progress of a dictation through the session, for front-ends to follow.
+/// Serializes to JSON as `{"event": "partial", "id": ..., "text": ...}`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Recording began
+    Started { id: uuid::Uuid },
+    /// Recording ended and the audio is on its way to the providers
+    Stopped { id: uuid::Uuid, elapsed: f64 },
+    /// Recording ended and the audio was thrown away, or transcribing and
+    /// outputting it was cancelled
+    Cancelled { id: uuid::Uuid },
+    /// Text recognized so far, from providers that return results while
+    /// the user is still speaking
+    Partial { id: uuid::Uuid, text: String },
+    /// Transcribed and handed to the output sinks; `output` names the ones
+    /// that delivered it, joined with "+", or is "none"
+    Completed {
+        id: uuid::Uuid,
+        text: String,
+        output: String,
+    },
+    /// Nothing was transcribed: every provider failed, an output failed,
+    /// the recording was too short or too expensive, or the user rejected
+    /// or scratched the text
+    Failed { id: uuid::Uuid, error: String },
+}
+
+/// A successfully transcribed and delivered recording
+struct Transcribed {
+    transcript: Transcript,
+    /// Seconds from stopping the recording to having the transcript
+    latency: f64,
+    /// Sinks that output the text, joined with "+" (e.g. "type+clipboard"),
+    /// or "none"
+    output: String,
+}
+
+/// What dictations detected to be in a particular language get instead of
+/// the session's defaults, when it transcribes [`providers::DETECT_LANGUAGE`]
+#[derive(Default)]
+pub struct LanguageRoute {
+    /// Transcribe them again with these, trying each in turn; none keeps the
+    /// first transcript
+    pub providers: Vec<Box<dyn TranscriptionProvider>>,
+    /// Applied instead of the session's post-processing
+    pub postprocess: Option<Postprocess>,
+}
+
+/// Why a transcript wasn't output, though nothing failed
+#[derive(Debug, Clone, Copy)]
+enum Dropped {
+    /// By the user, with --confirm or --edit
+    Rejected,
+    /// It ended with "scratch that"
+    Scratched,
+}
+
+/// The dictation engine shared by every front-end. It tracks whether a
+/// recording is in progress (`Idle` → `Recording` → `Idle`), transcribes
+/// finished recordings with a provider chain, trying each in turn until one
+/// succeeds, hands the results to the output sinks, and reports each step
+/// as an [`Event`].
+pub struct DictationSession {
+    providers: Vec<Box<dyn TranscriptionProvider>>,
+    sinks: Vec<Box<dyn OutputSink>>,
+    /// Language code recorded with each transcript, and passed to the
+    /// providers; can change between dictations
+    language: Mutex<String>,
+    /// Applied to every transcript before it is output
+    postprocess: Postprocess,
+    /// Run after `postprocess`, in order
+    rewriters: Vec<Box<dyn Rewriter>>,
+    /// Looked for at the end of every transcript, before `postprocess`
+    voice_commands: Option<Mutex<VoiceCommands>>,
+    /// By the primary subtag of the detected language ("cs")
+    routes: BTreeMap<String, LanguageRoute>,
+    /// Asks the user before anything is output
+    confirm: Option<Confirm>,
+    /// Asks the user before an expensive recording is sent
+    cost_limit: Option<CostLimit>,
+    /// Print where the time went after every dictation
+    report_timings: bool,
+    state: Mutex<State>,
+    /// The dictation stopped last and when, to tell how long getting its
+    /// audio took
+    stopped: Mutex<Option<(uuid::Uuid, Instant)>>,
+    /// Dictations being transcribed or output, oldest first, and what
+    /// cancels each
+    processing: Mutex<Vec<(uuid::Uuid, CancellationToken)>>,
+    events: broadcast::Sender<Event>,
+}
+
+impl DictationSession {
+    pub fn new(
+        providers: Vec<Box<dyn TranscriptionProvider>>,
+        sinks: Vec<Box<dyn OutputSink>>,
+        language: String,
+    ) -> Self {
+        Self {
+            providers,
+            sinks,
+            language: Mutex::new(language),
+            postprocess: Postprocess::default(),
+            rewriters: Vec::new(),
+            voice_commands: None,
+            routes: BTreeMap::new(),
+            confirm: None,
+            cost_limit: None,
+            report_timings: false,
+            state: Mutex::new(State::Idle),
+            stopped: Mutex::new(None),
+            processing: Mutex::new(Vec::new()),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+
+    /// Rewrite every transcript with `postprocess` before it is output
+    pub fn with_postprocess(mut self, postprocess: Postprocess) -> Self {
+        self.postprocess = postprocess;
+        self
+    }
+
+    /// Pass every transcript through `rewriters` before it is output
+    pub fn with_rewriters(mut self, rewriters: Vec<Box<dyn Rewriter>>) -> Self {
+        self.rewriters = rewriters;
+        self
+    }
+
+    /// Act on `voice_commands` spoken at the end of a transcript instead of
+    /// outputting them
+    pub fn with_voice_commands(mut self, voice_commands: VoiceCommands) -> Self {
+        self.voice_commands = Some(Mutex::new(voice_commands));
+        self
+    }
+
+    /// Treat dictations detected to be in a language as `routes` says, by
+    /// the primary subtag of the language ("cs")
+    pub fn with_language_routes(mut self, routes: BTreeMap<String, LanguageRoute>) -> Self {
+        self.routes = routes;
+        self
+    }
+
+    /// Have the user accept, reject, or edit every transcript before it is
+    /// output
+    pub fn with_confirm(mut self, confirm: Confirm) -> Self {
+        self.confirm = Some(confirm);
+        self
+    }
+
+    /// Have the user agree before sending a recording whose transcription
+    /// by the first provider is estimated to cost more than the limit
+    pub fn with_cost_limit(mut self, cost_limit: CostLimit) -> Self {
+        self.cost_limit = Some(cost_limit);
+        self
+    }
+
+    /// Print how long each step took after every dictation; they are
+    /// part of the JSON output and the history either way
+    pub fn with_timings_report(mut self) -> Self {
+        self.report_timings = true;
+        self
+    }
+
+    /// Receive every event from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Names of the providers, in the order they are tried
+    pub fn provider_names(&self) -> Vec<&str> {
+        self.providers.iter().map(|p| p.name()).collect()
+    }
+
+    /// The language dictations are transcribed in
+    pub fn language(&self) -> String {
+        self.language.lock().unwrap().clone()
+    }
+
+    /// Transcribe `language` (e.g. "cs-CZ") from the next dictation on, or
+    /// the one being recorded. The voice commands follow it, falling back to
+    /// the English ones.
+    pub fn set_language(&self, language: &str) {
+        *self.language.lock().unwrap() = language.to_string();
+        for provider in &self.providers {
+            provider.set_language(language);
+        }
+        if let Some(ref commands) = self.voice_commands {
+            *commands.lock().unwrap() =
+                VoiceCommands::for_language(language).unwrap_or_else(VoiceCommands::english);
+        }
+    }
+
+    pub fn state(&self) -> State {
+        *self.state.lock().unwrap()
+    }
+
+    fn emit(&self, event: Event) {
+        // Nobody listening is fine
+        let _ = self.events.send(event);
+    }
+
+    /// `Idle` → `Recording`: the front-end has started capturing audio for
+    /// dictation `id`.
+    pub fn start(&self, id: uuid::Uuid) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if let State::Recording { .. } = *state {
+            anyhow::bail!("Already recording");
+        }
+        *state = State::Recording {
+            id,
+            since: Instant::now(),
+        };
+        drop(state);
+        metrics::get().recording_started();
+        self.emit(Event::Started { id });
+        Ok(())
+    }
+
+    /// `Recording` → `Idle`, keeping the audio: pass it to [`Self::process`]
+    /// next. Returns the ID given to [`Self::start`].
+    pub fn stop(&self) -> Result<uuid::Uuid> {
+        let (id, since) = self.finish_recording()?;
+        *self.stopped.lock().unwrap() = Some((id, Instant::now()));
+        self.emit(Event::Stopped {
+            id,
+            elapsed: since.elapsed().as_secs_f64(),
+        });
+        Ok(id)
+    }
+
+    /// `Recording` → `Idle`, discarding the audio
+    pub fn cancel(&self) -> Result<uuid::Uuid> {
+        let (id, _) = self.finish_recording()?;
+        metrics::get().dictation_dropped("cancelled");
+        self.emit(Event::Cancelled { id });
+        Ok(id)
+    }
+
+    /// Cancel the dictations still being transcribed or output: requests to
+    /// the providers are dropped, nothing more is output, and typing stops
+    /// after the piece being typed. Returns the ID of the latest one, or
+    /// `None` when there are none.
+    pub fn cancel_processing(&self) -> Option<uuid::Uuid> {
+        let processing = self.processing.lock().unwrap();
+        for (_, cancel) in processing.iter() {
+            cancel.cancel();
+        }
+        processing.last().map(|(id, _)| *id)
+    }
+
+    fn finish_recording(&self) -> Result<(uuid::Uuid, Instant)> {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            State::Recording { id, since } => {
+                *state = State::Idle;
+                Ok((id, since))
+            }
+            State::Idle => Err(Error::NotRecording.into()),
+        }
+    }
+
+    /// Report text recognized while dictation `id` is still going on
+    pub fn partial(&self, id: uuid::Uuid, text: String) {
+        self.emit(Event::Partial { id, text });
+    }
+
+    /// Transcribe `samples`, the audio of dictation `id` recorded so far,
+    /// with the first provider and report the text as [`Event::Partial`],
+    /// for a live preview while recording goes on. Nothing is output, and
+    /// there is no fallback; `None` while the audio is still too short.
+    pub async fn preview(&self, id: uuid::Uuid, samples: Vec<i16>) -> Result<Option<String>> {
+        let Some(provider) = self.providers.first() else {
+            return Ok(None);
+        };
+        if (samples.len() as f64 / SAMPLE_RATE as f64) < MIN_RECORDING_DURATION_SECONDS {
+            return Ok(None);
+        }
+        let wav_bytes =
+            tokio::task::spawn_blocking(move || audio::samples_to_wav(&samples, SAMPLE_RATE))
+                .await
+                .context("WAV encoding task panicked")??;
+        let text = provider.transcribe(&wav_bytes, SAMPLE_RATE).await?;
+        let text = self.postprocess.apply(text.trim());
+        self.partial(id, text.clone());
+        Ok(Some(text))
+    }
+
+    /// Transcribe `samples` with the time each segment was spoken, e.g. for
+    /// subtitles, with the first provider in the chain that can tell,
+    /// falling back to the next one on failure. Each segment is
+    /// post-processed; nothing is output and no events are sent.
+    pub async fn segments(&self, samples: Vec<i16>) -> Result<Vec<Segment>> {
+        let wav_bytes =
+            tokio::task::spawn_blocking(move || audio::samples_to_wav(&samples, SAMPLE_RATE))
+                .await
+                .context("WAV encoding task panicked")??;
+        let mut last_err: Option<anyhow::Error> = None;
+        for provider in &self.providers {
+            say!("Transcribing with timestamps ({})...", provider.name());
+            match provider.transcribe_segments(&wav_bytes, SAMPLE_RATE).await {
+                Ok(segments) => {
+                    return Ok(segments
+                        .into_iter()
+                        .map(|segment| Segment {
+                            text: self.postprocess.apply(segment.text.trim()),
+                            ..segment
+                        })
+                        .collect())
+                }
+                Err(e) => {
+                    eprintln!("Provider {} failed: {:#}", provider.name(), e);
+                    last_err = Some(e);
+                }
+            }
+        }
+        Err(last_err
+            .map(|e| e.context("all providers failed"))
+            .unwrap_or_else(|| anyhow::anyhow!("all providers failed")))
+    }
+
+    /// Transcribe `wav_bytes` with `providers`, trying each in turn until one
+    /// succeeds, and with `detect` also find out the language it heard. The
+    /// time it takes is added to `timings`.
+    async fn transcribe<'a>(
+        &self,
+        providers: &'a [Box<dyn TranscriptionProvider>],
+        wav_bytes: &[u8],
+        detect: bool,
+        utterance_id: uuid::Uuid,
+        timings: &mut Timings,
+    ) -> Result<(String, &'a dyn TranscriptionProvider, Option<String>)> {
+        let step = Instant::now();
+        let total = providers.len();
+        let mut last_err: Option<anyhow::Error> = None;
+        for (i, p) in providers.iter().enumerate() {
+            let msg = if total == 1 {
+                format!("Transcribing ({})...", p.name())
+            } else if i == 0 {
+                format!("Transcribing ({}) [1/{}]...", p.name(), total)
+            } else {
+                format!("Retrying with {} [{}/{}]...", p.name(), i + 1, total)
+            };
+            send_notification(&msg, false).await;
+            say!("\n{}", msg);
+
+            let asked = Instant::now();
+            let request = async {
+                if detect {
+                    p.transcribe_detecting(wav_bytes, SAMPLE_RATE).await
+                } else {
+                    Ok((p.transcribe(wav_bytes, SAMPLE_RATE).await?, None))
+                }
+            };
+            let (result, upload) = providers::timing_upload(request).await;
+            metrics::get().provider_request(
+                p.name(),
+                result.is_ok(),
+                asked.elapsed().as_secs_f64(),
+            );
+            match result {
+                Ok((text, language)) => {
+                    let upload = upload.map_or(0.0, |u| u.as_secs_f64());
+                    timings.upload += upload;
+                    timings.provider += step.elapsed().as_secs_f64() - upload;
+                    return Ok((text.trim().to_string(), p.as_ref(), language));
+                }
+                Err(e) => {
+                    eprintln!("[{}] Provider {} failed: {:#}", utterance_id, p.name(), e);
+                    last_err = Some(e);
+                }
+            }
+        }
+        timings.provider += step.elapsed().as_secs_f64();
+        Err(last_err
+            .map(|e| e.context("all providers failed"))
+            .unwrap_or_else(|| anyhow::anyhow!("all providers failed")))
+    }
+
+    /// Transcribe a finished recording and hand the result to the output
+    /// sinks; the typing sink needs `ticket`. Returns the transcript, or
+    /// `None` when the recording was too short to bother, cost too much, the
+    /// user rejected or scratched the text, or it was cancelled with
+    /// [`Self::cancel_processing`].
+    pub async fn process(
+        &self,
+        samples: Vec<i16>,
+        mut ticket: Option<Ticket>,
+        utterance_id: uuid::Uuid,
+    ) -> Result<Option<String>> {
+        let cancel = CancellationToken::new();
+        self.processing
+            .lock()
+            .unwrap()
+            .push((utterance_id, cancel.clone()));
+        if let Some(ref mut ticket) = ticket {
+            ticket.cancel_on(cancel.clone());
+        }
+        // Dropping the pipeline drops the request in flight with it
+        let result = tokio::select! {
+            result = self.pipeline(samples, ticket, utterance_id) => result,
+            _ = cancel.cancelled() => {
+                eprintln!("[{}] Cancel", utterance_id);
+                say!("Transcription cancelled.");
+                send_notification("Transcription cancelled", true).await;
+                metrics::get().dictation_dropped("cancelled");
+                self.emit(Event::Cancelled { id: utterance_id });
+                Ok(None)
+            }
+        };
+        self.processing
+            .lock()
+            .unwrap()
+            .retain(|(id, _)| *id != utterance_id);
+        result
+    }
+
+    async fn pipeline(
+        &self,
+        samples: Vec<i16>,
+        mut ticket: Option<Ticket>,
+        utterance_id: uuid::Uuid,
+    ) -> Result<Option<String>> {
+        let providers = &self.providers;
+        let sinks = &self.sinks;
+        let stopped_at = std::time::Instant::now();
+        let mut timings = Timings::default();
+        {
+            let mut stopped = self.stopped.lock().unwrap();
+            if let Some((id, at)) = *stopped {
+                if id == utterance_id {
+                    timings.capture = stopped_at.duration_since(at).as_secs_f64();
+                    *stopped = None;
+                }
+            }
+        }
+
+        let duration_seconds = samples.len() as f64 / SAMPLE_RATE as f64;
+        let audio_duration = duration_seconds;
+        let size_mb = (samples.len() * BYTES_PER_SAMPLE) as f64 / (1024.0 * 1024.0);
+        say!(
+            "Recording length: {:.1} seconds ({:.1} MB)",
+            duration_seconds,
+            size_mb
+        );
+
+        if duration_seconds < MIN_RECORDING_DURATION_SECONDS {
+            eprintln!(
+                "Recording too short ({:.1} seconds), discarding.",
+                duration_seconds
+            );
+            send_notification("Recording too short, discarding", true).await;
+            metrics::get().dictation_dropped("too_short");
+            self.emit(Event::Failed {
+                id: utterance_id,
+                error: "Recording too short".to_string(),
+            });
+            return Ok(None);
+        }
+
+        // Billed per started minute, like the cost reported afterwards
+        let estimate = providers
+            .first()
+            .and_then(|p| p.cost_per_minute())
+            .map(|cost_per_min| (duration_seconds / 60.0).ceil() * cost_per_min);
+        if let (Some(limit), Some(estimate)) = (&self.cost_limit, estimate) {
+            let asking = Instant::now();
+            let approved = match limit.approve(estimate, duration_seconds / 60.0).await {
+                Ok(approved) => approved,
+                Err(e) => return Err(self.failed(utterance_id, e).await),
+            };
+            timings.confirm += asking.elapsed().as_secs_f64();
+            if !approved {
+                say!("Recording not transcribed, over the --max-cost limit.");
+                send_notification("Recording discarded, over the cost limit", true).await;
+                metrics::get().dictation_dropped("over_cost");
+                self.emit(Event::Failed {
+                    id: utterance_id,
+                    error: "Over the cost limit".to_string(),
+                });
+                return Ok(None);
+            }
+        }
+
+        send_notification(
+            &format!("Encoding audio ({:.1} s)...", duration_seconds),
+            false,
+        )
+        .await;
+        let result: Result<std::result::Result<Transcribed, Dropped>> = async {
+            let step = Instant::now();
+            let wav_bytes =
+                tokio::task::spawn_blocking(move || audio::samples_to_wav(&samples, SAMPLE_RATE))
+                    .await
+                    .context("WAV encoding task panicked")??;
+            timings.encode = step.elapsed().as_secs_f64();
+
+            let detect = self.language() == providers::DETECT_LANGUAGE;
+            let (text, provider, detected) = self
+                .transcribe(providers, &wav_bytes, detect, utterance_id, &mut timings)
+                .await?;
+            let cost_of = |provider: &dyn TranscriptionProvider| {
+                provider
+                    .cost_per_minute()
+                    .map(|cost_per_min| (audio_duration / 60.0).ceil() * cost_per_min)
+            };
+            let mut cost = cost_of(provider);
+            let route = detected.as_deref().and_then(|language| {
+                say!("Detected language: {}", language);
+                let primary = language.split(['-', '_']).next().unwrap_or_default();
+                self.routes.get(&primary.to_lowercase())
+            });
+            let (text, provider) = match route {
+                Some(route) if !route.providers.is_empty() => {
+                    match self
+                        .transcribe(
+                            &route.providers,
+                            &wav_bytes,
+                            false,
+                            utterance_id,
+                            &mut timings,
+                        )
+                        .await
+                    {
+                        Ok((routed, routed_provider, _)) => {
+                            // Both transcriptions are paid for
+                            cost = match (cost, cost_of(routed_provider)) {
+                                (None, None) => None,
+                                (first, second) => {
+                                    Some(first.unwrap_or(0.0) + second.unwrap_or(0.0))
+                                }
+                            };
+                            (routed, routed_provider)
+                        }
+                        Err(e) => {
+                            eprintln!("[{}] Keeping the first transcript: {:#}", utterance_id, e);
+                            (text, provider)
+                        }
+                    }
+                }
+                _ => (text, provider),
+            };
+
+            let step = Instant::now();
+            // The detected language's phrases, if it has any
+            let detected_commands = detected.as_deref().and_then(VoiceCommands::for_language);
+            let (text, command) = match (&self.voice_commands, detected_commands) {
+                (Some(_), Some(commands)) => commands.parse(&text),
+                (Some(commands), None) => commands.lock().unwrap().parse(&text),
+                (None, _) => (text, None),
+            };
+            if let Some(command) = command {
+                eprintln!("[{}] Voice command: {:?}", utterance_id, command);
+            }
+            if command == Some(VoiceCommand::ScratchThat) {
+                return Ok(Err(Dropped::Scratched));
+            }
+            let postprocess = route
+                .and_then(|route| route.postprocess.as_ref())
+                .unwrap_or(&self.postprocess);
+            let mut text = postprocess.apply(&text);
+            for rewriter in &self.rewriters {
+                let msg = format!("{}...", rewriter.describe());
+                send_notification(&msg, false).await;
+                say!("{}", msg);
+                match rewriter.rewrite(&text).await {
+                    Ok(rewritten) => text = rewritten,
+                    Err(e) => eprintln!(
+                        "[{}] {} failed, keeping the text as it was: {:#}",
+                        utterance_id,
+                        rewriter.describe(),
+                        e
+                    ),
+                }
+            }
+            match command {
+                Some(VoiceCommand::SendIt) => {
+                    if let Some(ref mut ticket) = ticket {
+                        ticket.press_enter();
+                    }
+                }
+                Some(VoiceCommand::NewParagraph) => text.push_str("\n\n"),
+                Some(VoiceCommand::NewLine) => text.push('\n'),
+                _ => {}
+            }
+            timings.rewrite = step.elapsed().as_secs_f64();
+            let latency = stopped_at.elapsed().as_secs_f64();
+
+            say!();
+            say!("Transcription:");
+            say!("{}", text);
+
+            let step = Instant::now();
+            let text = match self.confirm {
+                Some(ref confirm) => match confirm.ask(&text).await? {
+                    Some(text) => text,
+                    None => return Ok(Err(Dropped::Rejected)),
+                },
+                None => text,
+            };
+            timings.confirm += step.elapsed().as_secs_f64();
+
+            let mut transcript = Transcript {
+                id: utterance_id,
+                text,
+                duration_seconds: audio_duration,
+                provider: provider.name().to_string(),
+                language: detected.unwrap_or_else(|| self.language()),
+                cost,
+                wav: wav_bytes,
+                ticket,
+                timings,
+            };
+
+            // Every sink gets its turn even if an earlier one failed
+            let mut delivered: Vec<&str> = Vec::new();
+            let mut first_err: Option<anyhow::Error> = None;
+            let step = Instant::now();
+            for sink in sinks {
+                match sink.deliver(&mut transcript).await {
+                    Ok(true) => delivered.push(sink.name()),
+                    Ok(false) => {}
+                    Err(e) => {
+                        eprintln!("[{}] Output {} failed: {:#}", utterance_id, sink.name(), e);
+                        first_err.get_or_insert(
+                            Error::OutputFailed {
+                                sink: sink.name().to_string(),
+                                error: e,
+                            }
+                            .into(),
+                        );
+                    }
+                }
+                // What the next sink sees: the time spent on output before it
+                transcript.timings.output = step.elapsed().as_secs_f64();
+            }
+            if let Some(e) = first_err {
+                return Err(e);
+            }
+            let output = if delivered.is_empty() {
+                "none".to_string()
+            } else {
+                delivered.join("+")
+            };
+
+            Ok(Ok(Transcribed {
+                transcript,
+                latency,
+                output,
+            }))
+        }
+        .await;
+
+        match result {
+            Ok(Err(dropped)) => {
+                let (message, error, result) = match dropped {
+                    Dropped::Rejected => ("Transcription rejected", "Rejected", "rejected"),
+                    Dropped::Scratched => ("Transcription scratched", "Scratched", "scratched"),
+                };
+                metrics::get().dictation_dropped(result);
+                say!("{}, nothing output.", message);
+                send_notification(message, true).await;
+                self.emit(Event::Failed {
+                    id: utterance_id,
+                    error: error.to_string(),
+                });
+                Ok(None)
+            }
+            Ok(Ok(Transcribed {
+                transcript,
+                latency,
+                output,
+            })) => {
+                let text = transcript.text;
+                // Show the start of the transcription in the notification.
+                // Must use .chars().count() instead of .len() because non-English
+                // text (e.g. Czech ě, ř, ž) uses multi-byte UTF-8 characters —
+                // slicing by byte index would panic at a non-char boundary.
+                let preview = if text.chars().count() > NOTIFICATION_PREVIEW_CHARS {
+                    format!(
+                        "{}...",
+                        text.chars()
+                            .take(NOTIFICATION_PREVIEW_CHARS)
+                            .collect::<String>()
+                    )
+                } else {
+                    text.clone()
+                };
+                let summary = match output.as_str() {
+                    "none" => "Transcribed".to_string(),
+                    outputs => format!("Transcribed ({})", outputs.replace('+', ", ")),
+                };
+                send_notification_with_body(&summary, Some(&preview), true).await;
+
+                say!();
+                say!("Audio duration: {:.1} seconds", duration_seconds);
+                if let Some(cost) = transcript.cost {
+                    say!("Cost: ${:.4}", cost);
+                }
+                if self.report_timings {
+                    say!("Timings: {}", transcript.timings);
+                }
+                metrics::get().dictation_completed(
+                    audio_duration,
+                    transcript.cost,
+                    &transcript.timings,
+                );
+
+                if output::mode() == output::Mode::Json {
+                    let result = serde_json::json!({
+                        "id": utterance_id,
+                        "transcript": text,
+                        "duration_seconds": audio_duration,
+                        "provider": transcript.provider,
+                        "cost": transcript.cost,
+                        "latency_seconds": latency,
+                        "timings": transcript.timings,
+                        "output": output,
+                    });
+                    println!("{}", result);
+                }
+                self.emit(Event::Completed {
+                    id: utterance_id,
+                    text: text.clone(),
+                    output,
+                });
+                Ok(Some(text))
+            }
+            Err(e) => Err(self.failed(utterance_id, e).await),
+        }
+    }
+
+    /// Report dictation `id` as failed with `e`, and hand `e` back
+    async fn failed(&self, id: uuid::Uuid, e: anyhow::Error) -> anyhow::Error {
+        metrics::get().dictation_dropped("failed");
+        self.emit(Event::Failed {
+            id,
+            error: format!("{:#}", e),
+        });
+        send_notification(&format!("Error: {}", e), true).await;
+        eprintln!("[{}] failed", id);
+        e
+    }
+}
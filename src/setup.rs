@@ -0,0 +1,786 @@
+//! Turning settings into a configured [`DictationSession`]: the provider
+//! chain, the outputs, and the steps in between. The `rpdictation` command
+//! fills [`Options`] from its flags and config file; an application
+//! embedding the pipeline fills them itself.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use tokio_util::sync::CancellationToken;
+
+use crate::confirm::{self, Confirm, CostLimit};
+#[cfg(any(feature = "openai", feature = "groq", feature = "mistral"))]
+use crate::credentials::{keyring_has, ApiKey};
+#[cfg(any(feature = "openai", feature = "groq", feature = "mistral"))]
+use crate::llm::{Llm, Summarizer, Translator};
+use crate::notifier;
+use crate::numbers::{self, NumberStyle};
+use crate::postprocess::Postprocess;
+use crate::profanity::Profanity;
+#[cfg(feature = "google")]
+use crate::providers::google::GoogleProvider;
+#[cfg(feature = "groq")]
+use crate::providers::groq::GroqProvider;
+#[cfg(feature = "mistral")]
+use crate::providers::mistral::MistralProvider;
+#[cfg(feature = "openai")]
+use crate::providers::openai::OpenAIProvider;
+#[cfg(feature = "wyoming")]
+use crate::providers::wyoming::WyomingProvider;
+use crate::providers::{self, mock::MockProvider, TranscriptionProvider};
+use crate::punctuation::Case;
+use crate::replacements::Replacements;
+use crate::rewrite::{Rewriter, SummaryMode};
+use crate::session::LanguageRoute;
+#[cfg(feature = "mqtt")]
+use crate::sinks::mqtt::{Mqtt, MqttSink};
+#[cfg(feature = "http")]
+use crate::sinks::otlp::OtlpSink;
+#[cfg(feature = "http")]
+use crate::sinks::webhook::WebhookSink;
+use crate::sinks::{
+    clipboard::{ClipboardSink, PrimarySelectionSink},
+    exec::ExecSink,
+    file::FileSink,
+    history::HistorySink,
+    journal::JournalSink,
+    nvim::NvimSink,
+    org::OrgSink,
+    osc52::Osc52Sink,
+    stdout::StdoutSink,
+    tmux::TmuxSink,
+    typing::TypingSink,
+    OutputSink,
+};
+use crate::typer::{AppStyle, FocusTracking, PasteKey, Typer};
+use crate::typing_queue::TypingQueue;
+use crate::voice_commands::VoiceCommands;
+use crate::{command_exists, focus, DictationSession};
+
+/// How dictations detected to be in a language are handled instead of the
+/// defaults, with `language` set to `auto`
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Route {
+    /// Transcribe them again with these providers, told the language, as
+    /// for --provider
+    pub provider: Option<String>,
+    /// Apply these replacements instead, as for --replacements
+    pub replacements: Option<PathBuf>,
+}
+
+/// Everything the builders below look at. Each field does what the
+/// `rpdictation` flag of the same name does; only where they differ is it
+/// spelled out. The default has nothing turned on and no language, so set
+/// at least `language`.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Comma-separated provider chain; auto-detected when unset
+    pub provider: Option<String>,
+    /// Static API keys, wherever they came from
+    pub openai_api_key: Option<String>,
+    pub mistral_api_key: Option<String>,
+    pub groq_api_key: Option<String>,
+    pub google_api_key: Option<String>,
+    /// Commands printing the API key, by provider ("openai", "mistral",
+    /// "groq"), for those without a static key
+    pub key_commands: BTreeMap<String, String>,
+    pub keyring: bool,
+    pub token_endpoint: Option<String>,
+    pub wyoming_server: Option<String>,
+    pub language: String,
+    pub vocabulary: Vec<String>,
+
+    pub typer: Option<String>,
+    pub output: Option<String>,
+    pub paste: bool,
+    pub paste_key: PasteKey,
+    pub type_delay: Option<u64>,
+    pub enter: bool,
+    pub append: bool,
+    pub track_window: bool,
+    pub strict_focus: bool,
+    /// How text is typed into particular applications, by app-id or class
+    pub apps: BTreeMap<String, AppStyle>,
+
+    pub clipboard: bool,
+    pub primary: bool,
+    pub osc52: bool,
+    pub history: bool,
+    pub name: Option<String>,
+    pub tag: Vec<String>,
+    pub exec: Option<String>,
+    pub webhook: Option<String>,
+    pub mqtt: Option<String>,
+    pub append_file: Option<PathBuf>,
+    pub append_header: Option<String>,
+    pub journal: Option<String>,
+    pub journal_heading: Option<String>,
+    pub journal_template: Option<PathBuf>,
+    pub org: Option<PathBuf>,
+    pub org_headline: Option<String>,
+    pub nvim: Option<String>,
+    pub tmux: Option<String>,
+    pub quiet: bool,
+    pub otlp_endpoint: Option<String>,
+
+    /// Rules file, which has to exist; there is no default here
+    pub replacements: Option<PathBuf>,
+    pub spell: bool,
+    pub numbers: NumberStyle,
+    pub date_format: Option<String>,
+    pub case: Case,
+    pub strip_period: bool,
+    pub collapse_whitespace: bool,
+    pub profanity: Profanity,
+    pub voice_commands: bool,
+    /// Handling of detected languages, by language code
+    pub routes: BTreeMap<String, Route>,
+    pub summarize: Option<SummaryMode>,
+    pub translate_to: Option<String>,
+    pub llm: Option<String>,
+    pub llm_model: Option<String>,
+
+    pub confirm: bool,
+    pub edit: bool,
+    pub max_cost: Option<f64>,
+    pub timings: bool,
+    /// Nobody watches the terminal the session was started from, as with
+    /// the daemon or an interface of its own, so questions go elsewhere
+    pub background: bool,
+
+    pub delay: Option<u64>,
+    pub device: Option<String>,
+    pub replay: Option<PathBuf>,
+    pub duration: Option<std::time::Duration>,
+    pub continuous: bool,
+    pub vad_threshold: f64,
+    pub live: bool,
+    pub live_type: bool,
+    pub overlay: bool,
+}
+
+/// The key for `provider` ("openai", "mistral", or "groq"). A static key
+/// wins, then a key command, then the keyring; otherwise fall back to
+/// fetching a short-lived key from the token endpoint at transcription time.
+#[cfg(any(feature = "openai", feature = "groq", feature = "mistral"))]
+fn resolve_api_key(options: &Options, provider: &str) -> Option<ApiKey> {
+    let static_key = match provider {
+        "openai" => options.openai_api_key.as_ref(),
+        "mistral" => options.mistral_api_key.as_ref(),
+        "groq" => options.groq_api_key.as_ref(),
+        _ => None,
+    };
+    static_key
+        .filter(|k| !k.is_empty())
+        .map(|k| ApiKey::Static(k.clone()))
+        .or_else(|| {
+            options
+                .key_commands
+                .get(provider)
+                .filter(|c| !c.is_empty())
+                .map(|c| ApiKey::Command(c.clone()))
+        })
+        .or_else(|| {
+            (options.keyring && keyring_has(provider)).then(|| ApiKey::Keyring {
+                provider: provider.to_string(),
+            })
+        })
+        .or_else(|| {
+            options
+                .token_endpoint
+                .as_ref()
+                .map(|url| ApiKey::TokenEndpoint {
+                    url: url.clone(),
+                    provider: provider.to_string(),
+                })
+        })
+}
+
+/// Why there is no key for `provider`, with every place one can come from
+#[cfg(any(feature = "openai", feature = "groq", feature = "mistral"))]
+fn missing_key(provider: &str) -> String {
+    format!(
+        "No API key for {provider}: pass --{provider}-api-key, set {}_API_KEY, put \
+         {provider}_api_key or {provider}_key_command in the config file, store it in \
+         the keyring (with --keyring), or set --token-endpoint",
+        provider.to_uppercase()
+    )
+}
+
+/// Build the provider chain. A comma-separated list means "try these in order,
+/// falling back to the next on failure".
+// Which providers get pushed onto the auto-detected chain depends on the
+// enabled features
+#[allow(clippy::vec_init_then_push)]
+pub fn build_providers(options: &Options) -> Result<Vec<Box<dyn TranscriptionProvider>>> {
+    let build_provider = |name: &str| -> Result<Box<dyn TranscriptionProvider>> {
+        match name {
+            #[cfg(feature = "openai")]
+            "openai" => {
+                let api_key = resolve_api_key(options, "openai")
+                    .with_context(|| missing_key("openai"))?;
+                Ok(Box::new(
+                    OpenAIProvider::new(api_key).with_vocabulary(options.vocabulary.clone()),
+                ))
+            }
+            #[cfg(feature = "mistral")]
+            "mistral" => {
+                let api_key = resolve_api_key(options, "mistral")
+                    .with_context(|| missing_key("mistral"))?;
+                Ok(Box::new(MistralProvider::new(api_key)))
+            }
+            #[cfg(feature = "groq")]
+            "groq" => {
+                let api_key = resolve_api_key(options, "groq")
+                    .with_context(|| missing_key("groq"))?;
+                Ok(Box::new(
+                    GroqProvider::new(api_key).with_vocabulary(options.vocabulary.clone()),
+                ))
+            }
+            #[cfg(feature = "google")]
+            "google" => Ok(Box::new(
+                GoogleProvider::new(options.google_api_key.clone(), options.language.clone())
+                    .with_profanity_filter(options.profanity != Profanity::Keep),
+            )),
+            #[cfg(feature = "wyoming")]
+            "wyoming" => {
+                let server = options
+                    .wyoming_server
+                    .as_deref()
+                    .context("The wyoming provider needs --wyoming-server HOST:PORT")?;
+                Ok(Box::new(WyomingProvider::new(server, &options.language)))
+            }
+            "mock" => Ok(Box::new(MockProvider::new(
+                env::var("RPDICTATION_MOCK_TEXT")
+                    .unwrap_or_else(|_| "This is a mock transcription.".to_string()),
+            ))),
+            // Only reachable for providers whose feature is turned off
+            #[allow(unreachable_patterns)]
+            "openai" | "mistral" | "groq" | "google" | "wyoming" => anyhow::bail!(
+                "Provider '{}' is not available: rpdictation was built without the {} feature",
+                name,
+                name
+            ),
+            other => anyhow::bail!(
+                "Invalid provider '{}'. Valid options: openai, mistral, groq, google, wyoming, mock",
+                other
+            ),
+        }
+    };
+
+    let providers: Vec<Box<dyn TranscriptionProvider>> = match options.provider.as_deref() {
+        Some(list) => {
+            let mut providers: Vec<Box<dyn TranscriptionProvider>> = Vec::new();
+            for name in list.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                providers.push(build_provider(name)?);
+            }
+            if providers.is_empty() {
+                anyhow::bail!("--provider list is empty");
+            }
+            let names: Vec<&str> = providers.iter().map(|p| p.name()).collect();
+            eprintln!("Provider chain: {}", names.join(" -> "));
+            providers
+        }
+        None => {
+            // Auto-detect: build a best-effort fallback chain from every provider
+            // that has an API key available (or can get one from the token
+            // endpoint), ordered cheapest-first. Google is always appended last
+            // since it has a built-in default key.
+            #[allow(unused_mut)]
+            let mut providers: Vec<Box<dyn TranscriptionProvider>> = Vec::new();
+            #[cfg(feature = "wyoming")]
+            if let Some(ref server) = options.wyoming_server {
+                providers.push(Box::new(WyomingProvider::new(server, &options.language)));
+            }
+            #[cfg(feature = "groq")]
+            if let Some(api_key) = resolve_api_key(options, "groq") {
+                providers.push(Box::new(
+                    GroqProvider::new(api_key).with_vocabulary(options.vocabulary.clone()),
+                ));
+            }
+            #[cfg(feature = "openai")]
+            if let Some(api_key) = resolve_api_key(options, "openai") {
+                providers.push(Box::new(
+                    OpenAIProvider::new(api_key).with_vocabulary(options.vocabulary.clone()),
+                ));
+            }
+            #[cfg(feature = "mistral")]
+            if let Some(api_key) = resolve_api_key(options, "mistral") {
+                providers.push(Box::new(MistralProvider::new(api_key)));
+            }
+            #[cfg(feature = "google")]
+            providers.push(Box::new(
+                GoogleProvider::new(options.google_api_key.clone(), options.language.clone())
+                    .with_profanity_filter(options.profanity != Profanity::Keep),
+            ));
+            if providers.is_empty() {
+                anyhow::bail!("No provider available; pick one with --provider");
+            }
+            let names: Vec<&str> = providers.iter().map(|p| p.name()).collect();
+            eprintln!("Auto-detected provider chain: {}", names.join(" -> "));
+            providers
+        }
+    };
+    Ok(providers)
+}
+
+/// Remember the focused window so text can be typed back into it, if
+/// --track-window is set.
+pub async fn capture_focus(options: &Options) -> Option<FocusTracking> {
+    if !options.track_window && !options.strict_focus {
+        return None;
+    }
+
+    // Initialize focus provider
+    let Some(provider) = focus::detect_focus_provider().await else {
+        eprintln!(
+            "Warning: --track-window enabled but no compositor detected, focus tracking disabled"
+        );
+        return None;
+    };
+    eprintln!("Using focus provider: {}", provider.name());
+
+    match provider.get_focused_window().await {
+        Ok(Some(window)) => {
+            eprintln!("Captured window ID: {:?}", window);
+            Some(FocusTracking {
+                provider,
+                window,
+                strict: options.strict_focus,
+            })
+        }
+        Ok(None) => None,
+        Err(e) => {
+            eprintln!("Warning: Failed to capture focused window: {}", e);
+            None
+        }
+    }
+}
+
+/// The [`Options::apps`] style for the focused application, if there is
+/// one
+pub async fn app_style(options: &Options) -> Option<AppStyle> {
+    let apps = &options.apps;
+    if apps.is_empty() || !types_text(options) {
+        return None;
+    }
+    let Some(provider) = focus::detect_focus_provider().await else {
+        eprintln!("Warning: No compositor detected to tell the focused application, [app] settings unused");
+        return None;
+    };
+    let app = match provider.get_focused_app().await {
+        Ok(Some(app)) => app,
+        Ok(None) => return None,
+        Err(e) => {
+            eprintln!("Warning: Failed to get the focused application: {}", e);
+            return None;
+        }
+    };
+    // App-ids and classes differ in case between toolkits
+    let style = apps
+        .iter()
+        .find(|(id, _)| id.eq_ignore_ascii_case(&app))
+        .map(|(_, style)| style.clone());
+    match style {
+        Some(mut style) => {
+            eprintln!("Focused application: {} (using its [app] settings)", app);
+            style.paste_key.get_or_insert(options.paste_key);
+            Some(style)
+        }
+        None => {
+            eprintln!("Focused application: {}", app);
+            None
+        }
+    }
+}
+
+/// Whether text is inserted via the clipboard instead of typed directly.
+/// Non-English forces paste mode because ydotool's direct-type strips
+/// diacritics at the evdev level.
+/// See: https://github.com/ReimuNotMoe/ydotool/issues/249
+///
+/// The native typer builds its own keymap and types any character, and the
+/// input-method and atspi typers hand over whole strings, so they only
+/// paste when asked to.
+fn use_paste(options: &Options) -> bool {
+    options.paste
+        || output_names(options).iter().any(|n| n == "paste")
+        || (!options.language.starts_with("en")
+            && !matches!(
+                options.typer.as_deref(),
+                Some("native" | "input-method" | "atspi")
+            ))
+}
+
+/// Output sinks to run, in order: --output (or `type` when there's a
+/// typer), plus the ones turned on by --clipboard, --primary, --osc52,
+/// --history, --exec, --append-file, --journal, --org, --nvim, --tmux,
+/// --webhook, --mqtt and --quiet.
+fn output_names(options: &Options) -> Vec<String> {
+    let mut names: Vec<String> = match options.output.as_deref() {
+        Some(list) => list
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect(),
+        None => options.typer.iter().map(|_| "type".to_string()).collect(),
+    };
+    for (enabled, name) in [
+        (options.clipboard, "clipboard"),
+        (options.primary, "primary"),
+        (options.osc52, "osc52"),
+        (options.history, "history"),
+        (options.exec.is_some(), "exec"),
+        (options.append_file.is_some(), "file"),
+        (options.journal.is_some(), "journal"),
+        (options.org.is_some(), "org"),
+        (options.nvim.is_some(), "nvim"),
+        (options.tmux.is_some(), "tmux"),
+        (options.webhook.is_some(), "webhook"),
+        (options.mqtt.is_some(), "mqtt"),
+        (options.quiet, "stdout"),
+    ] {
+        if enabled && !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+fn types_text(options: &Options) -> bool {
+    output_names(options)
+        .iter()
+        .any(|n| n == "type" || n == "paste")
+}
+
+/// The output sinks, in order. `mqtt` is the one for --mqtt, if any, which
+/// is set up beforehand.
+pub fn build_sinks(
+    options: &Options,
+    mut mqtt: Option<Box<dyn OutputSink>>,
+) -> Result<Vec<Box<dyn OutputSink>>> {
+    let mut sinks: Vec<Box<dyn OutputSink>> = Vec::new();
+    for name in output_names(options) {
+        let sink: Box<dyn OutputSink> = match name.as_str() {
+            "type" | "paste" => {
+                let tool = options
+                    .typer
+                    .clone()
+                    .with_context(|| format!("Output '{}' requires --typer", name))?;
+                Box::new(TypingSink::new(tool, use_paste(options)))
+            }
+            "clipboard" => Box::new(ClipboardSink),
+            "primary" => Box::new(PrimarySelectionSink),
+            "osc52" => Box::new(Osc52Sink),
+            "history" => Box::new(HistorySink::new(options.name.clone(), options.tag.clone())),
+            "stdout" => Box::new(StdoutSink),
+            "exec" => Box::new(ExecSink::new(
+                options.exec.clone().context("Output 'exec' requires --exec")?,
+            )),
+            "file" => Box::new(FileSink::new(
+                options.append_file
+                    .clone()
+                    .context("Output 'file' requires --append-file")?,
+                options.append_header.clone(),
+            )),
+            "journal" => Box::new(JournalSink::new(
+                options.journal
+                    .clone()
+                    .context("Output 'journal' requires --journal")?,
+                options.journal_heading.clone(),
+                options.journal_template.clone(),
+            )),
+            "org" => Box::new(OrgSink::new(
+                options.org.clone().context("Output 'org' requires --org")?,
+                options.org_headline.clone(),
+            )),
+            "nvim" => Box::new(NvimSink::new(
+                options.nvim
+                    .clone()
+                    .or_else(|| env::var("NVIM").ok())
+                    .context("Output 'nvim' requires --nvim (or $NVIM)")?,
+            )),
+            "tmux" => Box::new(TmuxSink::new(
+                options.tmux.clone().context("Output 'tmux' requires --tmux")?,
+                options.enter,
+            )),
+            #[cfg(feature = "http")]
+            "webhook" => Box::new(WebhookSink::new(
+                options.webhook
+                    .clone()
+                    .context("Output 'webhook' requires --webhook")?,
+            )),
+            #[cfg(not(feature = "http"))]
+            "webhook" => anyhow::bail!(
+                "Output 'webhook' is not available: rpdictation was built without an HTTP provider feature"
+            ),
+            "mqtt" => mqtt.take().context("Output 'mqtt' requires --mqtt")?,
+            other => anyhow::bail!(
+                "Invalid output '{}'. Valid options: type, paste, clipboard, primary, osc52, history, stdout, exec, file, journal, org, nvim, tmux, webhook, mqtt",
+                other
+            ),
+        };
+        sinks.push(sink);
+    }
+    // Last, to see how long all the outputs took
+    if let Some(ref endpoint) = options.otlp_endpoint {
+        #[cfg(feature = "http")]
+        sinks.push(Box::new(OtlpSink::new(endpoint)));
+        #[cfg(not(feature = "http"))]
+        anyhow::bail!(
+            "--otlp-endpoint {} needs rpdictation built with an HTTP provider feature",
+            endpoint
+        );
+    }
+    Ok(sinks)
+}
+
+/// The transcription pipeline configured by `options`
+pub fn build_session(options: &Options) -> Result<DictationSession> {
+    let providers = build_providers(options)?;
+    // The broker also hears about the session's events, so the sink shares
+    // its connection
+    #[cfg(feature = "mqtt")]
+    let mqtt = options.mqtt.as_deref().map(Mqtt::connect).transpose()?;
+    #[cfg(feature = "mqtt")]
+    let mqtt_sink = mqtt
+        .clone()
+        .map(|mqtt| Box::new(MqttSink::new(mqtt)) as Box<dyn OutputSink>);
+    #[cfg(not(feature = "mqtt"))]
+    let mqtt_sink = match options.mqtt {
+        Some(_) => anyhow::bail!("--mqtt needs rpdictation built with the mqtt feature"),
+        None => None,
+    };
+    let sinks = build_sinks(options, mqtt_sink)?;
+    let session = DictationSession::new(providers, sinks, options.language.clone());
+    let replacements = match options.replacements {
+        Some(ref path) => Replacements::load(path)?,
+        None => Replacements::default(),
+    };
+    if let Some(ref format) = options.date_format {
+        numbers::check_date_format(format)?;
+    }
+    // Also for --language auto set later on, with `rpdictation lang auto`
+    let session = session
+        .with_postprocess(build_postprocess(options, replacements))
+        .with_rewriters(build_rewriters(options)?)
+        .with_language_routes(build_routes(options)?);
+    let session = if options.voice_commands {
+        let commands = VoiceCommands::for_language(&options.language).unwrap_or_else(|| {
+            // Detected languages get their own phrases
+            if options.language != providers::DETECT_LANGUAGE {
+                eprintln!(
+                    "Warning: No voice commands in '{}', listening for the English ones",
+                    options.language
+                );
+            }
+            VoiceCommands::english()
+        });
+        session.with_voice_commands(commands)
+    } else {
+        session
+    };
+    let session = if options.confirm || options.edit {
+        session.with_confirm(Confirm::new(
+            confirm_prompt(options)?,
+            options.edit,
+            options.confirm,
+        ))
+    } else {
+        session
+    };
+    let session = match options.max_cost {
+        Some(max) => session.with_cost_limit(CostLimit {
+            max,
+            prompt: if asks_on_terminal(options) {
+                Some(confirm::Prompt::Terminal)
+            } else {
+                notifier::get()
+                    .buttons()
+                    .then_some(confirm::Prompt::Notification)
+            },
+        }),
+        None => session,
+    };
+    let session = if options.timings {
+        session.with_timings_report()
+    } else {
+        session
+    };
+    #[cfg(feature = "mqtt")]
+    if let Some(mqtt) = mqtt {
+        tokio::spawn(mqtt.follow(session.subscribe()));
+    }
+    Ok(session)
+}
+
+fn build_postprocess(options: &Options, replacements: Replacements) -> Postprocess {
+    Postprocess {
+        spell: options.spell,
+        date_format: options.date_format.clone(),
+        numbers: options.numbers,
+        case: options.case,
+        profanity: options.profanity,
+        replacements,
+        strip_period: options.strip_period,
+        collapse_whitespace: options.collapse_whitespace,
+    }
+}
+
+/// The [`Options::routes`], for --language auto. Their providers are told
+/// the language as it is written there, e.g. "cs-CZ".
+fn build_routes(options: &Options) -> Result<BTreeMap<String, LanguageRoute>> {
+    let mut routes = BTreeMap::new();
+    for (language, route) in &options.routes {
+        let providers = match route.provider {
+            Some(ref list) => {
+                let mut route_options = options.clone();
+                route_options.language = language.clone();
+                route_options.provider = Some(list.clone());
+                build_providers(&route_options)
+                    .with_context(|| format!("Invalid [route.{}]", language))?
+            }
+            None => Vec::new(),
+        };
+        let postprocess = match route.replacements {
+            Some(ref path) => Some(build_postprocess(options, Replacements::load(path)?)),
+            None => None,
+        };
+        let primary = language.split(['-', '_']).next().unwrap_or_default();
+        routes.insert(
+            primary.to_lowercase(),
+            LanguageRoute {
+                providers,
+                postprocess,
+            },
+        );
+    }
+    Ok(routes)
+}
+
+/// Whether questions can go to the terminal: the one a recording was
+/// started from, unless [`Options::background`] says nobody watches it.
+fn asks_on_terminal(options: &Options) -> bool {
+    !options.background && std::io::stdin().is_terminal()
+}
+
+/// Where --confirm asks and --edit edits: on the terminal, otherwise in a
+/// notification and in an editor with a window of its own.
+fn confirm_prompt(options: &Options) -> Result<confirm::Prompt> {
+    if asks_on_terminal(options) {
+        Ok(confirm::Prompt::Terminal)
+    } else if options.edit && env::var_os("VISUAL").is_none_or(|v| v.is_empty()) {
+        anyhow::bail!("--edit without a terminal needs $VISUAL set to an editor that opens a window, e.g. \"gvim -f\"")
+    } else if !options.confirm || notifier::get().buttons() {
+        Ok(confirm::Prompt::Notification)
+    } else {
+        anyhow::bail!("--confirm needs a terminal or desktop notifications to ask in")
+    }
+}
+
+/// The steps that rewrite transcripts with a language model (--summarize,
+/// then --translate-to)
+pub fn build_rewriters(options: &Options) -> Result<Vec<Box<dyn Rewriter>>> {
+    #[allow(unused_mut)]
+    let mut rewriters: Vec<Box<dyn Rewriter>> = Vec::new();
+    #[cfg(not(any(feature = "openai", feature = "groq", feature = "mistral")))]
+    if options.summarize.is_some() || options.translate_to.is_some() {
+        anyhow::bail!(
+            "--summarize and --translate-to need a language model: rpdictation was built without the openai, groq, and mistral features"
+        );
+    }
+    #[cfg(any(feature = "openai", feature = "groq", feature = "mistral"))]
+    {
+        if let Some(mode) = options.summarize {
+            rewriters.push(Box::new(Summarizer::new(build_llm(options)?, mode)));
+        }
+        if let Some(ref language) = options.translate_to {
+            rewriters.push(Box::new(Translator::new(
+                build_llm(options)?,
+                language.clone(),
+            )));
+        }
+    }
+    Ok(rewriters)
+}
+
+/// The language model for rewriting: --llm, or else the first of the
+/// transcription providers, Groq, OpenAI, and Mistral that has a key
+#[cfg(any(feature = "openai", feature = "groq", feature = "mistral"))]
+fn build_llm(options: &Options) -> Result<Llm> {
+    const LLM_PROVIDERS: [&str; 3] = ["groq", "openai", "mistral"];
+    let candidates: Vec<String> = match options.llm {
+        Some(ref llm) => vec![llm.to_lowercase()],
+        None => options
+            .provider
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(|p| p.trim().to_lowercase())
+            .chain(LLM_PROVIDERS.map(String::from))
+            .filter(|p| LLM_PROVIDERS.contains(&p.as_str()))
+            .collect(),
+    };
+    for provider in &candidates {
+        if let Some(api_key) = resolve_api_key(options, provider) {
+            return Llm::new(provider, api_key, options.llm_model.clone());
+        }
+    }
+    match options.llm {
+        Some(ref llm) => anyhow::bail!("No API key for '{}', the --llm provider", llm),
+        None => {
+            anyhow::bail!("Summaries and translations need an OpenAI, Groq, or Mistral API key")
+        }
+    }
+}
+
+/// Start the typing worker, if there's a typer and the text is to be typed.
+pub async fn build_typing_queue(options: &Options) -> Result<Option<TypingQueue>> {
+    let Some(ref tool) = options.typer else {
+        return Ok(None);
+    };
+    if !types_text(options) {
+        return Ok(None);
+    }
+    // The other typers talk to the compositor or the application themselves
+    if matches!(tool.as_str(), "wtype" | "ydotool") && !command_exists(tool).await {
+        anyhow::bail!("{} command not found. Please install it.", tool);
+    }
+
+    let typer = Typer::new(
+        tool.clone(),
+        use_paste(options).then_some(options.paste_key),
+        options.enter,
+        options.type_delay.map(std::time::Duration::from_millis),
+    )?;
+    // Segments of a continuous session are pieces of the same text
+    Ok(Some(TypingQueue::spawn(
+        typer,
+        options.append || options.continuous,
+        CancellationToken::new(),
+    )))
+}
+
+/// Caption `session`'s dictations in the --overlay strip
+pub fn spawn_overlay(options: &Options, session: &DictationSession) {
+    if !options.overlay {
+        return;
+    }
+    #[cfg(feature = "overlay")]
+    match crate::overlay::Overlay::open() {
+        Ok(overlay) => {
+            tokio::spawn(overlay.follow(session.subscribe()));
+        }
+        Err(e) => eprintln!("Warning: Overlay unavailable: {:#}", e),
+    }
+    #[cfg(not(feature = "overlay"))]
+    {
+        let _ = session;
+        eprintln!("Warning: --overlay needs rpdictation built with the overlay feature");
+    }
+}
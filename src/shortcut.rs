@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use tokio::sync::{mpsc, oneshot};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+use crate::daemon::Request;
+
+const PORTAL_DEST: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const SHORTCUTS_IFACE: &str = "org.freedesktop.portal.GlobalShortcuts";
+const REQUEST_IFACE: &str = "org.freedesktop.portal.Request";
+
+/// Shortcut ID registered with the portal
+const TOGGLE_ID: &str = "toggle";
+
+/// Register a global "toggle dictation" shortcut with the desktop portal and
+/// turn its activations into `toggle` commands for the daemon loop.
+///
+/// `trigger` is only a preference (in the portal's syntax, e.g.
+/// `CTRL+ALT+d`); the desktop usually asks the user to confirm or pick a
+/// different key the first time.
+pub async fn run(
+    conn: zbus::Connection,
+    trigger: String,
+    requests: mpsc::Sender<Request>,
+) -> Result<()> {
+    let portal = zbus::Proxy::new(&conn, PORTAL_DEST, PORTAL_PATH, SHORTCUTS_IFACE)
+        .await
+        .context("GlobalShortcuts portal not available")?;
+
+    // Subscribe before binding so no activation is missed
+    let mut activated = portal.receive_signal("Activated").await?;
+
+    let session_token = new_token();
+    let results = portal_request(&conn, |token| {
+        let portal = &portal;
+        let session_token = &session_token;
+        async move {
+            let options: HashMap<&str, Value> = HashMap::from([
+                ("handle_token", Value::from(token)),
+                ("session_handle_token", Value::from(session_token.as_str())),
+            ]);
+            portal.call_method("CreateSession", &(options,)).await
+        }
+    })
+    .await
+    .context("Failed to create GlobalShortcuts session")?;
+    let session = session_handle(results)?;
+
+    let shortcuts = vec![(
+        TOGGLE_ID,
+        HashMap::from([
+            ("description", Value::from("Start or stop dictation")),
+            ("preferred_trigger", Value::from(trigger.as_str())),
+        ]),
+    )];
+    portal_request(&conn, |token| {
+        let portal = &portal;
+        let session = &session;
+        let shortcuts = &shortcuts;
+        async move {
+            let options: HashMap<&str, Value> =
+                HashMap::from([("handle_token", Value::from(token))]);
+            portal
+                .call_method("BindShortcuts", &(session, shortcuts, "", options))
+                .await
+        }
+    })
+    .await
+    .context("Failed to bind global shortcut")?;
+    println!("Global shortcut registered ({})", trigger);
+
+    while let Some(msg) = activated.next().await {
+        let (from, id, _timestamp, _options): (
+            OwnedObjectPath,
+            String,
+            u64,
+            HashMap<String, OwnedValue>,
+        ) = match msg.body().deserialize() {
+            Ok(args) => args,
+            Err(e) => {
+                eprintln!("Warning: Malformed Activated signal: {}", e);
+                continue;
+            }
+        };
+        if from != session || id != TOGGLE_ID {
+            continue;
+        }
+        let (reply_tx, reply_rx) = oneshot::channel();
+        if requests
+            .send(("toggle".to_string(), reply_tx))
+            .await
+            .is_err()
+        {
+            break;
+        }
+        if let Ok(Err(e)) = reply_rx.await {
+            eprintln!("{}", e);
+        }
+    }
+    Ok(())
+}
+
+fn new_token() -> String {
+    format!("rpdictation{}", uuid::Uuid::new_v4().simple())
+}
+
+/// Make a portal call that answers through an `org.freedesktop.portal.Request`
+/// object and return the results of its `Response` signal. `call` gets the
+/// handle token to pass in its options.
+async fn portal_request<F, Fut>(
+    conn: &zbus::Connection,
+    call: F,
+) -> Result<HashMap<String, OwnedValue>>
+where
+    F: FnOnce(String) -> Fut,
+    Fut: std::future::Future<Output = zbus::Result<zbus::Message>>,
+{
+    let token = new_token();
+    // The request path is predictable, so the Response signal can be
+    // subscribed to before the call that emits it
+    let sender = conn
+        .unique_name()
+        .context("D-Bus connection has no unique name")?
+        .trim_start_matches(':')
+        .replace('.', "_");
+    let path = format!("{}/request/{}/{}", PORTAL_PATH, sender, token);
+    let request = zbus::Proxy::new(conn, PORTAL_DEST, path.as_str(), REQUEST_IFACE).await?;
+    let mut responses = request.receive_signal("Response").await?;
+
+    call(token).await?;
+
+    let msg = responses
+        .next()
+        .await
+        .context("Portal closed the request without answering")?;
+    let (code, results): (u32, HashMap<String, OwnedValue>) = msg.body().deserialize()?;
+    match code {
+        0 => Ok(results),
+        1 => anyhow::bail!("Cancelled by the user"),
+        _ => anyhow::bail!("Portal request failed"),
+    }
+}
+
+/// `session_handle` from a CreateSession response. Older portals return it
+/// as a string, newer ones as an object path.
+fn session_handle(mut results: HashMap<String, OwnedValue>) -> Result<OwnedObjectPath> {
+    let value = results
+        .remove("session_handle")
+        .context("CreateSession response has no session_handle")?;
+    if let Ok(path) = OwnedObjectPath::try_from(value.try_clone()?) {
+        return Ok(path);
+    }
+    let handle = String::try_from(value).context("Invalid session_handle")?;
+    Ok(OwnedObjectPath::try_from(handle)?)
+}
@@ -0,0 +1,38 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::{OutputSink, Transcript};
+
+/// Leaves the text on the clipboard. Put it after `paste`, whose clipboard
+/// restore would undo it otherwise.
+pub struct ClipboardSink;
+
+#[async_trait]
+impl OutputSink for ClipboardSink {
+    fn name(&self) -> &str {
+        "clipboard"
+    }
+
+    async fn deliver(&self, transcript: &mut Transcript) -> Result<bool> {
+        crate::clipboard::copy(&transcript.text).await?;
+        say!("\nCopied to clipboard.");
+        Ok(true)
+    }
+}
+
+/// Leaves the text in the primary selection, for pasting with the middle
+/// mouse button. Like [`ClipboardSink`], it goes after `paste`.
+pub struct PrimarySelectionSink;
+
+#[async_trait]
+impl OutputSink for PrimarySelectionSink {
+    fn name(&self) -> &str {
+        "primary"
+    }
+
+    async fn deliver(&self, transcript: &mut Transcript) -> Result<bool> {
+        crate::clipboard::copy_primary(&transcript.text).await?;
+        say!("\nCopied to the primary selection.");
+        Ok(true)
+    }
+}
@@ -0,0 +1,66 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::os::fd::AsFd;
+use std::process::Stdio;
+
+use super::{OutputSink, Transcript};
+use crate::output;
+
+/// Runs a shell command for each transcript. `{text}` in the command is
+/// replaced with the shell-quoted text, and the details are passed in
+/// `RPD_*` environment variables.
+pub struct ExecSink {
+    command: String,
+}
+
+impl ExecSink {
+    pub fn new(command: String) -> Self {
+        Self { command }
+    }
+}
+
+/// Quote `s` as a single word for `sh`.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[async_trait]
+impl OutputSink for ExecSink {
+    fn name(&self) -> &str {
+        "exec"
+    }
+
+    async fn deliver(&self, transcript: &mut Transcript) -> Result<bool> {
+        let command = self
+            .command
+            .replace("{text}", &shell_quote(&transcript.text));
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c")
+            .arg(&command)
+            .stdin(Stdio::null())
+            .env("RPD_TEXT", &transcript.text)
+            .env("RPD_ID", transcript.id.to_string())
+            .env(
+                "RPD_DURATION",
+                format!("{:.1}", transcript.duration_seconds),
+            )
+            .env("RPD_PROVIDER", &transcript.provider)
+            .env("RPD_LANGUAGE", &transcript.language)
+            .env(
+                "RPD_COST",
+                transcript.cost.map(|c| c.to_string()).unwrap_or_default(),
+            );
+        // Keep stdout clean when it carries the transcript or JSON
+        if output::mode() != output::Mode::Human {
+            cmd.stdout(std::io::stderr().as_fd().try_clone_to_owned()?);
+        }
+        let status = cmd
+            .status()
+            .await
+            .with_context(|| format!("Failed to run '{}'", self.command))?;
+        if !status.success() {
+            anyhow::bail!("'{}' exited with {}", self.command, status);
+        }
+        Ok(true)
+    }
+}
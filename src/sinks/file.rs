@@ -0,0 +1,59 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::fmt::Write;
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+
+use super::{OutputSink, Transcript};
+
+/// Appends each transcript to a file, optionally under a header line
+/// formatted from the local time (strftime syntax, e.g. `## %Y-%m-%d %H:%M`).
+pub struct FileSink {
+    path: PathBuf,
+    header: Option<String>,
+}
+
+impl FileSink {
+    pub fn new(path: PathBuf, header: Option<String>) -> Self {
+        Self {
+            path: crate::expand_home(path),
+            header,
+        }
+    }
+}
+
+#[async_trait]
+impl OutputSink for FileSink {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    async fn deliver(&self, transcript: &mut Transcript) -> Result<bool> {
+        let mut entry = String::new();
+        match self.header {
+            Some(ref header) => {
+                writeln!(entry, "{}", chrono::Local::now().format(header))
+                    .map_err(|_| anyhow::anyhow!("Invalid header format '{}'", header))?;
+                entry.push_str(&transcript.text);
+                entry.push_str("\n\n");
+            }
+            None => {
+                entry.push_str(&transcript.text);
+                entry.push('\n');
+            }
+        }
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+        file.write_all(entry.as_bytes()).await?;
+        // tokio finishes the write in the background; make sure it's done
+        // before the process exits
+        file.flush().await?;
+        say!("\nAppended to {}", self.path.display());
+        Ok(true)
+    }
+}
@@ -0,0 +1,42 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::{OutputSink, Transcript};
+use crate::history::{self, Entry};
+
+/// Archives the audio and transcript in the history directory, along with
+/// the name and tags given for the session
+pub struct HistorySink {
+    name: Option<String>,
+    tags: Vec<String>,
+}
+
+impl HistorySink {
+    pub fn new(name: Option<String>, tags: Vec<String>) -> Self {
+        Self { name, tags }
+    }
+}
+
+#[async_trait]
+impl OutputSink for HistorySink {
+    fn name(&self) -> &str {
+        "history"
+    }
+
+    async fn deliver(&self, transcript: &mut Transcript) -> Result<bool> {
+        let entry = Entry {
+            id: transcript.id,
+            timestamp: history::now(),
+            duration_seconds: transcript.duration_seconds,
+            provider: transcript.provider.clone(),
+            language: transcript.language.clone(),
+            text: transcript.text.clone(),
+            cost: transcript.cost,
+            name: self.name.clone(),
+            tags: self.tags.clone(),
+            timings: Some(transcript.timings),
+        };
+        history::save(&entry, &transcript.wav).await?;
+        Ok(true)
+    }
+}
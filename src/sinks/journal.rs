@@ -0,0 +1,130 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use super::{OutputSink, Transcript};
+
+/// Heading used when none is configured
+pub const DEFAULT_HEADING: &str = "## %Y-%m-%d";
+
+/// Adds each transcript as a `- HH:MM text` item under today's heading in a
+/// daily note, as kept by Obsidian, Logseq, or plain Markdown journals. The
+/// note's path and the heading are strftime patterns (e.g.
+/// `~/Notes/%Y-%m-%d.md` and `## %Y-%m-%d`); a missing note is started
+/// from the template, if there is one.
+pub struct JournalSink {
+    path: String,
+    heading: String,
+    template: Option<PathBuf>,
+}
+
+impl JournalSink {
+    pub fn new(path: String, heading: Option<String>, template: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            heading: heading.unwrap_or_else(|| DEFAULT_HEADING.to_string()),
+            template: template.map(crate::expand_home),
+        }
+    }
+
+    /// A new note: the template with `{{date}}` and `{{title}}` filled in,
+    /// or nothing
+    async fn start_note(&self, path: &Path, now: DateTime<Local>) -> Result<String> {
+        let Some(ref template) = self.template else {
+            return Ok(String::new());
+        };
+        let text = tokio::fs::read_to_string(template)
+            .await
+            .with_context(|| format!("Failed to read template {}", template.display()))?;
+        let title = path
+            .file_stem()
+            .map(|s| s.to_string_lossy())
+            .unwrap_or_default();
+        Ok(text
+            .replace("{{date}}", &now.format("%Y-%m-%d").to_string())
+            .replace("{{title}}", &title))
+    }
+}
+
+#[async_trait]
+impl OutputSink for JournalSink {
+    fn name(&self) -> &str {
+        "journal"
+    }
+
+    async fn deliver(&self, transcript: &mut Transcript) -> Result<bool> {
+        let now = Local::now();
+        let path = crate::expand_home(PathBuf::from(strftime(&self.path, now)?));
+        let heading = strftime(&self.heading, now)?;
+        let entry = format!("- {} {}", now.format("%H:%M"), transcript.text);
+
+        let note = match tokio::fs::read_to_string(&path).await {
+            Ok(note) => note,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+                    tokio::fs::create_dir_all(dir)
+                        .await
+                        .with_context(|| format!("Failed to create {}", dir.display()))?;
+                }
+                self.start_note(&path, now).await?
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        tokio::fs::write(&path, insert_entry(&note, &heading, &entry))
+            .await
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        say!("\nAdded to {} under '{}'", path.display(), heading);
+        Ok(true)
+    }
+}
+
+fn strftime(format: &str, now: DateTime<Local>) -> Result<String> {
+    let mut s = String::new();
+    write!(s, "{}", now.format(format))
+        .map_err(|_| anyhow::anyhow!("Invalid date format '{}'", format))?;
+    Ok(s)
+}
+
+/// Level of a Markdown heading line (the number of leading `#`s), if it is
+/// one
+fn heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let rest = &line[level..];
+    (level > 0 && (rest.is_empty() || rest.starts_with(' '))).then_some(level)
+}
+
+/// `note` with `entry` added as the last line of the section under
+/// `heading`, which runs to the next heading of the same or a higher level.
+/// Without such a section, the heading and entry go at the end.
+fn insert_entry(note: &str, heading: &str, entry: &str) -> String {
+    let mut lines: Vec<&str> = note.lines().collect();
+    let level = heading_level(heading).unwrap_or(0);
+    match lines.iter().position(|l| l.trim_end() == heading) {
+        Some(start) => {
+            let mut end = lines[start + 1..]
+                .iter()
+                .position(|l| heading_level(l).is_some_and(|n| level == 0 || n <= level))
+                .map_or(lines.len(), |i| start + 1 + i);
+            // Keep the blank lines that separate the section from the next
+            while end > start + 1 && lines[end - 1].trim().is_empty() {
+                end -= 1;
+            }
+            lines.insert(end, entry);
+        }
+        None => {
+            while lines.last().is_some_and(|l| l.trim().is_empty()) {
+                lines.pop();
+            }
+            if !lines.is_empty() {
+                lines.push("");
+            }
+            lines.push(heading);
+            lines.push(entry);
+        }
+    }
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
@@ -0,0 +1,50 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::metrics::Timings;
+use crate::typing_queue::Ticket;
+
+/// A finished dictation on its way to the output sinks
+pub struct Transcript {
+    pub id: uuid::Uuid,
+    pub text: String,
+    pub duration_seconds: f64,
+    /// Name of the provider that transcribed it
+    pub provider: String,
+    pub language: String,
+    pub cost: Option<f64>,
+    /// The recording, WAV-encoded
+    pub wav: Vec<u8>,
+    /// Place in the typing queue reserved when recording started; taken by
+    /// the sink that types
+    pub ticket: Option<Ticket>,
+    /// How long it took to get here; `output` counts the sinks before
+    /// this one
+    pub timings: Timings,
+}
+
+#[async_trait]
+pub trait OutputSink: Send + Sync {
+    fn name(&self) -> &str;
+    /// Output `transcript`. Returns `false` when there was nothing to do for
+    /// this one, e.g. typing a file from the watch folder.
+    async fn deliver(&self, transcript: &mut Transcript) -> Result<bool>;
+}
+
+pub mod clipboard;
+pub mod exec;
+pub mod file;
+pub mod history;
+pub mod journal;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod nvim;
+pub mod org;
+pub mod osc52;
+#[cfg(feature = "http")]
+pub mod otlp;
+pub mod stdout;
+pub mod tmux;
+pub mod typing;
+#[cfg(feature = "http")]
+pub mod webhook;
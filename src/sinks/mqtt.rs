@@ -0,0 +1,211 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use rumqttc::{AsyncClient, Event as MqttEvent, MqttOptions, Packet, QoS};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+
+use super::{OutputSink, Transcript};
+use crate::session::Event;
+
+/// Port used when the URL has none
+const DEFAULT_PORT: u16 = 1883;
+
+/// Topic prefix used when the URL has no path
+const DEFAULT_PREFIX: &str = "rpdictation";
+
+/// How long to wait for the broker to confirm a transcript
+const ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Messages waiting for the connection before publishing blocks
+const QUEUE_LEN: usize = 64;
+
+/// Connection to an MQTT broker, shared by [`MqttSink`] and
+/// [`Mqtt::follow`]. Everything is published with QoS 1 under a common
+/// prefix: `<prefix>/transcript` (the text), `<prefix>/state` (`recording`
+/// or `idle`, retained), and `<prefix>/event` (every session [`Event`] as
+/// JSON).
+#[derive(Clone)]
+pub struct Mqtt {
+    client: AsyncClient,
+    prefix: String,
+    /// Messages handed to the client so far, and acknowledged by the broker
+    /// so far. The broker acknowledges in order, so a message is through
+    /// once as many acknowledgements as its number have arrived.
+    published: Arc<AtomicU64>,
+    acked: watch::Receiver<u64>,
+}
+
+impl Mqtt {
+    /// Connect to the broker at `url`,
+    /// `mqtt://[user[:password]@]host[:port][/prefix]`. The connection is
+    /// made (and remade, should it drop) in the background, so an
+    /// unreachable broker only shows when publishing.
+    pub fn connect(url: &str) -> Result<Self> {
+        let (options, prefix) = parse_url(url)?;
+        let (client, mut eventloop) = AsyncClient::new(options, QUEUE_LEN);
+        let (acked_tx, acked) = watch::channel(0);
+        let broker = url.to_string();
+        tokio::spawn(async move {
+            let mut failing = false;
+            loop {
+                match eventloop.poll().await {
+                    Ok(MqttEvent::Incoming(Packet::PubAck(_))) => {
+                        acked_tx.send_modify(|n| *n += 1);
+                    }
+                    Ok(MqttEvent::Incoming(Packet::ConnAck(_))) => failing = false,
+                    Ok(_) => {}
+                    Err(e) => {
+                        if !failing {
+                            eprintln!("Warning: MQTT broker {}: {}", redact(&broker), e);
+                            failing = true;
+                        }
+                        tokio::time::sleep(Duration::from_secs(2)).await;
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            client,
+            prefix,
+            published: Arc::new(AtomicU64::new(0)),
+            acked,
+        })
+    }
+
+    /// Queue `payload` for `<prefix>/<topic>`, returning its number for
+    /// [`Mqtt::confirmed`]
+    async fn publish(&self, topic: &str, payload: Vec<u8>, retain: bool) -> Result<u64> {
+        let topic = format!("{}/{}", self.prefix, topic);
+        self.client
+            .publish(&topic, QoS::AtLeastOnce, retain, payload)
+            .await
+            .with_context(|| format!("Failed to publish to {}", topic))?;
+        Ok(self.published.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+
+    /// Wait for the broker to acknowledge message number `n`
+    async fn confirmed(&self, n: u64) -> Result<()> {
+        let mut acked = self.acked.clone();
+        tokio::time::timeout(ACK_TIMEOUT, acked.wait_for(|&acked| acked >= n))
+            .await
+            .context("The MQTT broker didn't confirm the transcript in time")?
+            .context("MQTT connection gone")?;
+        Ok(())
+    }
+
+    /// Publish the recording state and every event `events` reports. Runs
+    /// until the session is gone.
+    pub async fn follow(self, mut events: broadcast::Receiver<Event>) {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let state = match event {
+                Event::Started { .. } => Some("recording"),
+                Event::Stopped { .. } | Event::Cancelled { .. } => Some("idle"),
+                _ => None,
+            };
+            if let Some(state) = state {
+                let _ = self.publish("state", state.into(), true).await;
+            }
+            if let Ok(json) = serde_json::to_vec(&event) {
+                let _ = self.publish("event", json, false).await;
+            }
+        }
+    }
+}
+
+/// Publishes each transcript to `<prefix>/transcript` and waits for the
+/// broker to take it
+pub struct MqttSink {
+    mqtt: Mqtt,
+}
+
+impl MqttSink {
+    pub fn new(mqtt: Mqtt) -> Self {
+        Self { mqtt }
+    }
+}
+
+#[async_trait]
+impl OutputSink for MqttSink {
+    fn name(&self) -> &str {
+        "mqtt"
+    }
+
+    async fn deliver(&self, transcript: &mut Transcript) -> Result<bool> {
+        let n = self
+            .mqtt
+            .publish("transcript", transcript.text.clone().into(), false)
+            .await?;
+        self.mqtt.confirmed(n).await?;
+        Ok(true)
+    }
+}
+
+/// Connection options and topic prefix for `url`
+fn parse_url(url: &str) -> Result<(MqttOptions, String)> {
+    let rest = match url.split_once("://") {
+        Some(("mqtt" | "tcp", rest)) => rest,
+        Some((scheme, _)) => anyhow::bail!(
+            "Unsupported MQTT URL scheme '{}' (only mqtt:// is supported)",
+            scheme
+        ),
+        None => url,
+    };
+    let (authority, prefix) = match rest.split_once('/') {
+        Some((authority, path)) => (authority, path.trim_matches('/')),
+        None => (rest, ""),
+    };
+    let (credentials, address) = match authority.rsplit_once('@') {
+        Some((credentials, address)) => (Some(credentials), address),
+        None => (None, authority),
+    };
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse()
+                .with_context(|| format!("Invalid port in MQTT URL '{}'", redact(url)))?,
+        ),
+        None => (address, DEFAULT_PORT),
+    };
+    if host.is_empty() {
+        anyhow::bail!("No host in MQTT URL '{}'", redact(url));
+    }
+
+    let client_id = format!(
+        "rpdictation-{}",
+        &uuid::Uuid::new_v4().simple().to_string()[..8]
+    );
+    let mut options = MqttOptions::new(client_id, host, port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if let Some(credentials) = credentials {
+        let (user, password) = credentials.split_once(':').unwrap_or((credentials, ""));
+        options.set_credentials(user, password);
+    }
+    let prefix = if prefix.is_empty() {
+        DEFAULT_PREFIX
+    } else {
+        prefix
+    };
+    Ok((options, prefix.to_string()))
+}
+
+/// `url` without the password, for messages
+fn redact(url: &str) -> String {
+    let (scheme, rest) = match url.split_once("://") {
+        Some((scheme, rest)) => (&url[..scheme.len() + 3], rest),
+        None => ("", url),
+    };
+    match rest.split_once('@') {
+        Some((credentials, after)) => match credentials.split_once(':') {
+            Some((user, _)) => format!("{}{}:***@{}", scheme, user, after),
+            None => url.to_string(),
+        },
+        None => url.to_string(),
+    }
+}
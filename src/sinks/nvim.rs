@@ -0,0 +1,60 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::process::Stdio;
+
+use super::{OutputSink, Transcript};
+
+/// Inserts each transcript at the cursor of a running Neovim, through its
+/// RPC server (`nvim --server ADDR --remote-expr`). Unlike typing, this
+/// doesn't depend on the focus or the editor's mode: the text lands after
+/// the cursor in normal mode and at it in insert mode, and the cursor
+/// moves past it.
+pub struct NvimSink {
+    /// Socket path or `host:port`, as in `$NVIM` or `nvim --listen`
+    server: String,
+}
+
+impl NvimSink {
+    pub fn new(server: String) -> Self {
+        Self { server }
+    }
+}
+
+/// `s` as a Vim script string literal; nothing is special between single
+/// quotes but the quote itself.
+fn vim_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+#[async_trait]
+impl OutputSink for NvimSink {
+    fn name(&self) -> &str {
+        "nvim"
+    }
+
+    async fn deliver(&self, transcript: &mut Transcript) -> Result<bool> {
+        let lines: Vec<String> = transcript.text.split('\n').map(vim_quote).collect();
+        let expr = format!(
+            "nvim_put([{}], 'c', mode() !~# '^[iR]', v:true)",
+            lines.join(", ")
+        );
+        let output = tokio::process::Command::new("nvim")
+            .args(["--server", &self.server, "--remote-expr", &expr])
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .context("Failed to run nvim (is Neovim installed?)")?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // nvim reports a server it can't reach on stderr, not always in its
+        // exit status
+        if !output.status.success() || !stderr.trim().is_empty() {
+            let reason = match stderr.trim() {
+                "" => output.status.to_string(),
+                message => message.to_string(),
+            };
+            anyhow::bail!("Neovim at {}: {}", self.server, reason);
+        }
+        say!("\nInserted into Neovim at {}", self.server);
+        Ok(true)
+    }
+}
@@ -0,0 +1,87 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+
+use super::{OutputSink, Transcript};
+
+/// Longest headline taken from the transcript, in characters
+const TITLE_LEN: usize = 60;
+
+/// Appends each transcript to an org file as a top-level capture entry: a
+/// headline, an inactive timestamp, and the text as body, like an
+/// `org-capture` template with `* %?\n%U` would. The headline is the fixed
+/// one given, or the start of the transcript.
+pub struct OrgSink {
+    path: PathBuf,
+    headline: Option<String>,
+}
+
+impl OrgSink {
+    pub fn new(path: PathBuf, headline: Option<String>) -> Self {
+        Self {
+            path: crate::expand_home(path),
+            headline,
+        }
+    }
+}
+
+#[async_trait]
+impl OutputSink for OrgSink {
+    fn name(&self) -> &str {
+        "org"
+    }
+
+    async fn deliver(&self, transcript: &mut Transcript) -> Result<bool> {
+        let headline = match self.headline {
+            Some(ref headline) => headline.clone(),
+            None => title(&transcript.text),
+        };
+        let mut entry = format!(
+            "* {}\n{}\n{}\n",
+            headline,
+            chrono::Local::now().format("[%Y-%m-%d %a %H:%M]"),
+            transcript.text
+        );
+        // The headline must start a line of its own
+        let ends_in_newline = match tokio::fs::read(&self.path).await {
+            Ok(existing) => existing.last().is_none_or(|&b| b == b'\n'),
+            Err(_) => true,
+        };
+        if !ends_in_newline {
+            entry.insert(0, '\n');
+        }
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+        file.write_all(entry.as_bytes()).await?;
+        // tokio finishes the write in the background; make sure it's done
+        // before the process exits
+        file.flush().await?;
+        say!("\nCaptured to {}", self.path.display());
+        Ok(true)
+    }
+}
+
+/// Headline for `text`: its first sentence, cut at a word boundary if
+/// that's long
+fn title(text: &str) -> String {
+    let first = text
+        .char_indices()
+        .find(|&(i, c)| matches!(c, '.' | '?' | '!') && text[i + 1..].starts_with(' '))
+        .map_or(text, |(i, _)| &text[..i]);
+    let first = first.trim_end_matches(['.', '?', '!']);
+    if first.chars().count() <= TITLE_LEN {
+        return first.to_string();
+    }
+    let cut: String = first.chars().take(TITLE_LEN).collect();
+    let cut = match cut.rfind(' ') {
+        Some(i) if i > 0 => &cut[..i],
+        _ => &cut,
+    };
+    format!("{}…", cut.trim_end_matches([',', ';', ':']))
+}
@@ -0,0 +1,36 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::Engine;
+use tokio::io::AsyncWriteExt;
+
+use super::{OutputSink, Transcript};
+
+/// Puts each transcript on the clipboard of the terminal rpdictation runs
+/// in, with an OSC 52 escape sequence. Over SSH, that's the clipboard of
+/// the machine in front of you, which no clipboard tool on the remote end
+/// can reach.
+pub struct Osc52Sink;
+
+#[async_trait]
+impl OutputSink for Osc52Sink {
+    fn name(&self) -> &str {
+        "osc52"
+    }
+
+    async fn deliver(&self, transcript: &mut Transcript) -> Result<bool> {
+        let sequence = format!(
+            "\x1b]52;c;{}\x07",
+            base64::engine::general_purpose::STANDARD.encode(&transcript.text)
+        );
+        // The terminal itself, whatever stdout is redirected to
+        let mut tty = tokio::fs::OpenOptions::new()
+            .write(true)
+            .open("/dev/tty")
+            .await
+            .context("No terminal to send the OSC 52 sequence to")?;
+        tty.write_all(sequence.as_bytes()).await?;
+        tty.flush().await?;
+        say!("\nSent to the terminal's clipboard.");
+        Ok(true)
+    }
+}
@@ -0,0 +1,201 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{OutputSink, Transcript};
+
+/// Sends the steps of each dictation as OpenTelemetry spans to an OTLP/HTTP
+/// collector (Jaeger, Tempo, the OpenTelemetry Collector, ...), in its JSON
+/// encoding.
+///
+/// It runs after the outputs, as the last sink, and outputs nothing itself.
+/// The spans are laid end to end backwards from when it runs, from the
+/// durations in [`Transcript::timings`]: the recording, capture, encode,
+/// transcribe (with its upload), rewrite, confirm, and output. The trace ID
+/// is the dictation's ID.
+pub struct OtlpSink {
+    /// The collector's traces endpoint, `<endpoint>/v1/traces`
+    url: String,
+    client: reqwest::Client,
+}
+
+impl OtlpSink {
+    /// `endpoint` is the collector's base URL, e.g. http://localhost:4318
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            url: format!("{}/v1/traces", endpoint.trim_end_matches('/')),
+            client: crate::providers::http_client(),
+        }
+    }
+}
+
+#[async_trait]
+impl OutputSink for OtlpSink {
+    fn name(&self) -> &str {
+        "otlp"
+    }
+
+    async fn deliver(&self, transcript: &mut Transcript) -> Result<bool> {
+        let payload = spans(transcript, SystemTime::now());
+        let client = self.client.clone();
+        let url = self.url.clone();
+        // A collector that is down mustn't hold up or fail the dictation
+        tokio::spawn(async move {
+            if let Err(e) = send(&client, &url, &payload).await {
+                eprintln!("Warning: Failed to export the trace: {:#}", e);
+            }
+        });
+        Ok(false)
+    }
+}
+
+async fn send(client: &reqwest::Client, url: &str, payload: &Value) -> Result<()> {
+    let response = client
+        .post(url)
+        .json(payload)
+        .timeout(crate::providers::timeout_for(0.0))
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach {}", url))?;
+    let status = response.status();
+    if !status.is_success() {
+        let message = response.text().await.unwrap_or_default();
+        anyhow::bail!("{} answered {}: {}", url, status, message.trim());
+    }
+    Ok(())
+}
+
+/// The OTLP request with the spans of `transcript`, which finished at `end`
+fn spans(transcript: &Transcript, end: SystemTime) -> Value {
+    let timings = &transcript.timings;
+    let trace_id = transcript.id.simple().to_string();
+    let root_id = span_id();
+    // Backwards from the end, one step after another
+    let mut cursor = end;
+    let mut back = |seconds: f64| {
+        let stop = cursor;
+        cursor = stop
+            .checked_sub(Duration::from_secs_f64(seconds.max(0.0)))
+            .unwrap_or(stop);
+        (cursor, stop)
+    };
+    let mut spans = Vec::new();
+    let mut step = |name: &str, (start, stop): (SystemTime, SystemTime), attributes: &[Value]| {
+        let id = span_id();
+        spans.push(span(
+            &trace_id, &id, &root_id, name, start, stop, attributes,
+        ));
+        id
+    };
+    step("output", back(timings.output), &[]);
+    if timings.confirm > 0.0 {
+        step("confirm", back(timings.confirm), &[]);
+    }
+    step("rewrite", back(timings.rewrite), &[]);
+    let transcribe = back(timings.upload + timings.provider);
+    let transcribe_id = step(
+        "transcribe",
+        transcribe,
+        &[attribute(
+            "rpdictation.provider",
+            json!({"stringValue": transcript.provider}),
+        )],
+    );
+    step("encode", back(timings.encode), &[]);
+    step("capture", back(timings.capture), &[]);
+    step("record", back(transcript.duration_seconds), &[]);
+    if timings.upload > 0.0 {
+        // The first part of transcribing
+        let (start, _) = transcribe;
+        let stop = start + Duration::from_secs_f64(timings.upload);
+        spans.push(span(
+            &trace_id,
+            &span_id(),
+            &transcribe_id,
+            "upload",
+            start,
+            stop,
+            &[],
+        ));
+    }
+
+    let mut attributes = vec![
+        attribute(
+            "rpdictation.id",
+            json!({"stringValue": transcript.id.to_string()}),
+        ),
+        attribute(
+            "rpdictation.language",
+            json!({"stringValue": transcript.language}),
+        ),
+        attribute(
+            "rpdictation.audio_seconds",
+            json!({"doubleValue": transcript.duration_seconds}),
+        ),
+    ];
+    if let Some(cost) = transcript.cost {
+        attributes.push(attribute("rpdictation.cost", json!({"doubleValue": cost})));
+    }
+    spans.push(span(
+        &trace_id,
+        &root_id,
+        "",
+        "dictation",
+        cursor,
+        end,
+        &attributes,
+    ));
+
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [attribute("service.name", json!({"stringValue": "rpdictation"}))],
+            },
+            "scopeSpans": [{
+                "scope": {"name": "rpdictation", "version": env!("CARGO_PKG_VERSION")},
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+/// A span of trace `trace_id`; `parent` is empty for the root
+fn span(
+    trace_id: &str,
+    id: &str,
+    parent: &str,
+    name: &str,
+    start: SystemTime,
+    end: SystemTime,
+    attributes: &[Value],
+) -> Value {
+    json!({
+        "traceId": trace_id,
+        "spanId": id,
+        "parentSpanId": parent,
+        "name": name,
+        // SPAN_KIND_INTERNAL
+        "kind": 1,
+        "startTimeUnixNano": unix_nanos(start),
+        "endTimeUnixNano": unix_nanos(end),
+        "attributes": attributes,
+    })
+}
+
+fn attribute(key: &str, value: Value) -> Value {
+    json!({"key": key, "value": value})
+}
+
+/// A random 8-byte span ID in hex
+fn span_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()[..16].to_string()
+}
+
+/// As a string, the way the JSON encoding of OTLP wants 64-bit integers
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0)
+        .to_string()
+}
@@ -0,0 +1,19 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::{OutputSink, Transcript};
+
+/// Prints just the text on stdout
+pub struct StdoutSink;
+
+#[async_trait]
+impl OutputSink for StdoutSink {
+    fn name(&self) -> &str {
+        "stdout"
+    }
+
+    async fn deliver(&self, transcript: &mut Transcript) -> Result<bool> {
+        println!("{}", transcript.text);
+        Ok(true)
+    }
+}
@@ -0,0 +1,55 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::process::Stdio;
+
+use super::{OutputSink, Transcript};
+
+/// Sends each transcript to a tmux pane as keystrokes (`tmux send-keys`),
+/// so it reaches a REPL or shell whether or not its window has the focus.
+pub struct TmuxSink {
+    /// Target pane, in any form `tmux -t` takes (`%3`, `work:1.0`, ...)
+    pane: String,
+    /// Press Enter afterwards
+    enter: bool,
+}
+
+impl TmuxSink {
+    pub fn new(pane: String, enter: bool) -> Self {
+        Self { pane, enter }
+    }
+
+    async fn send_keys(&self, keys: &[&str]) -> Result<()> {
+        let output = tokio::process::Command::new("tmux")
+            .args(["send-keys", "-t", &self.pane])
+            .args(keys)
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .context("Failed to run tmux (is it installed?)")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "tmux pane {}: {}",
+                self.pane,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl OutputSink for TmuxSink {
+    fn name(&self) -> &str {
+        "tmux"
+    }
+
+    async fn deliver(&self, transcript: &mut Transcript) -> Result<bool> {
+        // -l sends the text as is rather than looking up key names in it
+        self.send_keys(&["-l", "--", &transcript.text]).await?;
+        if self.enter {
+            self.send_keys(&["Enter"]).await?;
+        }
+        say!("\nSent to tmux pane {}", self.pane);
+        Ok(true)
+    }
+}
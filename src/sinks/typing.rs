@@ -0,0 +1,47 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::{OutputSink, Transcript};
+
+/// Types (or pastes) the text into the focused window through the typing
+/// queue. If typing fails, the text is left on the clipboard so it isn't
+/// lost.
+pub struct TypingSink {
+    tool: String,
+    paste: bool,
+}
+
+impl TypingSink {
+    pub fn new(tool: String, paste: bool) -> Self {
+        Self { tool, paste }
+    }
+}
+
+#[async_trait]
+impl OutputSink for TypingSink {
+    fn name(&self) -> &str {
+        if self.paste {
+            "paste"
+        } else {
+            "type"
+        }
+    }
+
+    async fn deliver(&self, transcript: &mut Transcript) -> Result<bool> {
+        let Some(ticket) = transcript.ticket.take() else {
+            return Ok(false);
+        };
+        crate::notifier::send_notification("Typing text...", false).await;
+        say!("\nTyping text using {}...", self.tool);
+        if let Err(e) = ticket.deliver(transcript.text.clone()).await {
+            return Err(match crate::clipboard::copy(&transcript.text).await {
+                Ok(()) => e.context("Typing failed; the text was copied to the clipboard"),
+                Err(copy_err) => {
+                    eprintln!("Warning: Clipboard fallback failed: {:#}", copy_err);
+                    e
+                }
+            });
+        }
+        Ok(true)
+    }
+}
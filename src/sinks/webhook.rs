@@ -0,0 +1,53 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+
+use super::{OutputSink, Transcript};
+
+/// POSTs each transcript as JSON to a URL, for automation services (n8n,
+/// Zapier, ...) and note-taking APIs
+pub struct WebhookSink {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl WebhookSink {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            client: crate::providers::http_client(),
+        }
+    }
+}
+
+#[async_trait]
+impl OutputSink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn deliver(&self, transcript: &mut Transcript) -> Result<bool> {
+        let payload = serde_json::json!({
+            "id": transcript.id,
+            "text": transcript.text,
+            "duration_seconds": transcript.duration_seconds,
+            "timestamp": chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+            "provider": transcript.provider,
+            "language": transcript.language,
+            "cost": transcript.cost,
+        });
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&payload)
+            .timeout(crate::providers::timeout_for(0.0))
+            .send()
+            .await
+            .with_context(|| format!("Failed to send the transcript to {}", self.url))?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            anyhow::bail!("{} answered {}: {}", self.url, status, message.trim());
+        }
+        Ok(true)
+    }
+}
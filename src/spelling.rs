@@ -0,0 +1,170 @@
+//! Spelled-out text in the NATO phonetic alphabet, for dictating
+//! identifiers, passwords, and serial numbers a provider would otherwise
+//! turn into words.
+//!
+//! Besides the 26 code words, a spelled sequence can contain the digits
+//! ("zero" to "nine", or "niner"), single letters and numbers as the
+//! provider wrote them, "capital" before a letter to uppercase it, and a few
+//! symbols ("dash", "underscore", "dot", "at", "slash", "space").
+
+use crate::PUNCTUATION;
+
+/// `text` with every "spell … stop" sequence replaced by what it spells:
+/// "the ID is spell alpha bravo seven stop, thanks" gives "the ID is ab7,
+/// thanks". "stop" can be left out after two or more words at the end of
+/// the text. Sequences with any word that isn't part of the alphabet are
+/// left alone, so "spell" in ordinary prose doesn't trigger it.
+pub fn spell_inline(text: &str) -> String {
+    let words = words(text);
+    let mut out = String::new();
+    let mut copied = 0;
+    let mut i = 0;
+    while i < words.len() {
+        if words[i].key != "spell" {
+            i += 1;
+            continue;
+        }
+        let mut speller = Speller::default();
+        let mut j = i + 1;
+        while j < words.len() && words[j].key != "stop" && speller.push(&words[j].key) {
+            j += 1;
+        }
+        let stopped = j < words.len() && words[j].key == "stop";
+        // Without "stop", one word ("a spell at") is too likely to be prose
+        let complete = stopped || (j == words.len() && j - i > 2);
+        if j == i + 1 || !complete {
+            i += 1;
+            continue;
+        }
+        out.push_str(&text[copied..words[i].start]);
+        out.push_str(&speller.text);
+        // Punctuation after the last word (or "stop") still ends the clause
+        let last = if stopped { &words[j] } else { &words[j - 1] };
+        copied = last.key_end;
+        i = j + 1;
+    }
+    out.push_str(&text[copied..]);
+    out
+}
+
+/// `text` read as one spelled sequence, for a dictation that is nothing
+/// but spelling (--spell). Words outside the alphabet are kept as they are,
+/// without the spaces around them.
+pub fn spell_all(text: &str) -> String {
+    let mut speller = Speller::default();
+    for word in words(text) {
+        if !speller.push(&word.key) {
+            speller.text.push_str(&word.key);
+        }
+    }
+    speller.text
+}
+
+/// A word of the text, lowercased and without surrounding punctuation
+struct Word {
+    key: String,
+    /// Byte offsets in the text: where the word starts, and where the
+    /// word ends but the punctuation after it doesn't
+    start: usize,
+    key_end: usize,
+}
+
+fn words(text: &str) -> Vec<Word> {
+    text.split_whitespace()
+        .map(|raw| {
+            let start = raw.as_ptr() as usize - text.as_ptr() as usize;
+            let trimmed_end = raw.trim_end_matches(PUNCTUATION);
+            Word {
+                key: trimmed_end.trim_start_matches(PUNCTUATION).to_lowercase(),
+                start,
+                key_end: start + trimmed_end.len(),
+            }
+        })
+        .collect()
+}
+
+/// Builds up the spelled text one word at a time
+#[derive(Default)]
+struct Speller {
+    text: String,
+    capital: bool,
+}
+
+impl Speller {
+    /// Add what `word` spells; false if it isn't part of the alphabet.
+    fn push(&mut self, word: &str) -> bool {
+        if matches!(word, "capital" | "uppercase") {
+            self.capital = true;
+            return true;
+        }
+        let spelled = match letter(word).or_else(|| symbol(word)) {
+            Some(c) => c.to_string(),
+            // Letters and numbers the provider already wrote as such
+            None if word.chars().count() == 1 && word.chars().all(char::is_alphanumeric) => {
+                word.to_string()
+            }
+            None if word.chars().all(|c| c.is_ascii_digit()) => word.to_string(),
+            None => return false,
+        };
+        if std::mem::take(&mut self.capital) {
+            self.text.push_str(&spelled.to_uppercase());
+        } else {
+            self.text.push_str(&spelled);
+        }
+        true
+    }
+}
+
+fn letter(word: &str) -> Option<char> {
+    Some(match word {
+        "alpha" | "alfa" => 'a',
+        "bravo" => 'b',
+        "charlie" => 'c',
+        "delta" => 'd',
+        "echo" => 'e',
+        "foxtrot" => 'f',
+        "golf" => 'g',
+        "hotel" => 'h',
+        "india" => 'i',
+        "juliet" | "juliett" => 'j',
+        "kilo" => 'k',
+        "lima" => 'l',
+        "mike" => 'm',
+        "november" => 'n',
+        "oscar" => 'o',
+        "papa" => 'p',
+        "quebec" => 'q',
+        "romeo" => 'r',
+        "sierra" => 's',
+        "tango" => 't',
+        "uniform" => 'u',
+        "victor" => 'v',
+        "whiskey" | "whisky" => 'w',
+        "x-ray" | "xray" => 'x',
+        "yankee" => 'y',
+        "zulu" => 'z',
+        "zero" => '0',
+        "one" => '1',
+        "two" => '2',
+        "three" => '3',
+        "four" => '4',
+        "five" => '5',
+        "six" => '6',
+        "seven" => '7',
+        "eight" => '8',
+        "nine" | "niner" => '9',
+        _ => return None,
+    })
+}
+
+fn symbol(word: &str) -> Option<char> {
+    Some(match word {
+        "dash" | "hyphen" | "minus" => '-',
+        "underscore" => '_',
+        "dot" | "period" | "point" => '.',
+        "at" => '@',
+        "slash" => '/',
+        "space" => ' ',
+        _ => return None,
+    })
+}
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use std::time::Duration;
+
+/// How `rpdictation status` prints the state
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusFormat {
+    /// `recording 00:42` or `idle`
+    Plain,
+    /// One JSON object per line, for a waybar `custom` module
+    Waybar,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Idle,
+    Recording { elapsed: Duration },
+}
+
+/// Ask the daemon or the one-shot recording process for its state.
+pub async fn query() -> State {
+    let Some(channel) = crate::control::connect().await else {
+        return State::Idle;
+    };
+    match channel
+        .send("status")
+        .await
+        .ok()
+        .as_deref()
+        .and_then(|r| r.strip_prefix("recording "))
+    {
+        Some(secs) => State::Recording {
+            elapsed: Duration::from_secs(secs.parse().unwrap_or(0)),
+        },
+        None => State::Idle,
+    }
+}
+
+/// The language the daemon transcribes, if one is running
+pub async fn language() -> Option<String> {
+    crate::daemon::request("lang").await?.ok()
+}
+
+/// The state as a line in `format`, with `language` after it when given
+fn render(state: State, format: StatusFormat, language: Option<&str>) -> String {
+    let suffix = language.map(|l| format!(" {}", l)).unwrap_or_default();
+    match (state, format) {
+        (State::Idle, StatusFormat::Plain) => format!("idle{}", suffix),
+        (State::Recording { elapsed }, StatusFormat::Plain) => {
+            format!("recording {}{}", format_elapsed(elapsed), suffix)
+        }
+        // Empty text hides the module while idle, unless it shows the
+        // language
+        (State::Idle, StatusFormat::Waybar) => serde_json::json!({
+            "text": language.unwrap_or_default(),
+            "tooltip": format!("rpdictation: idle{}", suffix),
+            "class": "idle",
+        })
+        .to_string(),
+        (State::Recording { elapsed }, StatusFormat::Waybar) => serde_json::json!({
+            "text": format!("● {}{}", format_elapsed(elapsed), suffix),
+            "tooltip": format!("rpdictation: recording{}", suffix),
+            "class": "recording",
+        })
+        .to_string(),
+    }
+}
+
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+/// The current state as a line in `format`, with the daemon's language if
+/// `show_language`
+async fn line(format: StatusFormat, show_language: bool) -> String {
+    let language = if show_language {
+        language().await
+    } else {
+        None
+    };
+    render(query().await, format, language.as_deref())
+}
+
+/// Print the current state once, or with `follow` keep printing a new line
+/// whenever it changes (at most once a second).
+pub async fn run(format: StatusFormat, follow: bool, show_language: bool) -> Result<()> {
+    if !follow {
+        println!("{}", line(format, show_language).await);
+        return Ok(());
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    let mut last = String::new();
+    loop {
+        interval.tick().await;
+        let line = line(format, show_language).await;
+        if line != last {
+            println!("{}", line);
+            last = line;
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Timed transcripts as subtitle files, and as text marked with the time
+//! each paragraph starts.
+
+use crate::providers::Segment;
+
+/// A silence at least this long between segments starts a new paragraph
+/// in [`timestamped`]
+const PARAGRAPH_PAUSE: f64 = 1.5;
+
+/// A paragraph longer than this (in seconds) ends with the segment that
+/// takes it over, so markers come often enough to find a passage by
+const PARAGRAPH_MAX: f64 = 60.0;
+
+/// Subtitle file formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// SubRip (`.srt`)
+    Srt,
+    /// WebVTT (`.vtt`), for browsers and video players on the web
+    Vtt,
+}
+
+/// `segments` as a subtitle file in `format`, one cue per segment. Empty
+/// segments are left out.
+pub fn render(segments: &[Segment], format: Format) -> String {
+    let mut out = String::new();
+    if format == Format::Vtt {
+        out.push_str("WEBVTT\n\n");
+    }
+    let cues = segments.iter().filter(|s| !s.text.trim().is_empty());
+    for (n, segment) in cues.enumerate() {
+        if format == Format::Srt {
+            out.push_str(&format!("{}\n", n + 1));
+        }
+        out.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            timestamp(segment.start, format),
+            timestamp(segment.end.max(segment.start), format),
+            segment.text.trim()
+        ));
+    }
+    out
+}
+
+/// `HH:MM:SS,mmm` (SubRip) or `HH:MM:SS.mmm` (WebVTT)
+fn timestamp(seconds: f64, format: Format) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    let separator = match format {
+        Format::Srt => ',',
+        Format::Vtt => '.',
+    };
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+/// `segments` as paragraphs, each starting with the time it begins as
+/// `[MM:SS]` and separated by blank lines. A paragraph ends at a pause, or
+/// once it has gone on for about a minute.
+pub fn timestamped(segments: &[Segment]) -> String {
+    let mut paragraphs: Vec<(f64, Vec<&str>)> = Vec::new();
+    let mut last_end: Option<f64> = None;
+    for segment in segments.iter().filter(|s| !s.text.trim().is_empty()) {
+        let new_paragraph = match (paragraphs.last(), last_end) {
+            (Some((start, _)), Some(end)) => {
+                segment.start - end >= PARAGRAPH_PAUSE || end - start >= PARAGRAPH_MAX
+            }
+            _ => true,
+        };
+        if new_paragraph {
+            paragraphs.push((segment.start, Vec::new()));
+        }
+        if let Some((_, texts)) = paragraphs.last_mut() {
+            texts.push(segment.text.trim());
+        }
+        last_end = Some(segment.end);
+    }
+    paragraphs
+        .into_iter()
+        .map(|(start, texts)| {
+            let seconds = start.max(0.0) as u64;
+            format!(
+                "[{:02}:{:02}] {}\n",
+                seconds / 60,
+                seconds % 60,
+                texts.join(" ")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,136 @@
+use anyhow::{Context, Result};
+use ksni::TrayMethods;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::status::{self, State};
+
+/// How often the tray refreshes the recording state
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+struct Indicator {
+    state: State,
+    /// A daemon is idle and can be told to start
+    can_start: bool,
+    /// Control commands picked from the menu
+    commands: mpsc::UnboundedSender<&'static str>,
+    /// --session, shown in the title
+    session: Option<String>,
+}
+
+impl Indicator {
+    fn send(&self, command: &'static str) {
+        let _ = self.commands.send(command);
+    }
+}
+
+impl ksni::Tray for Indicator {
+    fn id(&self) -> String {
+        "rpdictation".into()
+    }
+
+    fn title(&self) -> String {
+        match self.session {
+            Some(ref name) => format!("rpdictation ({})", name),
+            None => "rpdictation".into(),
+        }
+    }
+
+    fn icon_name(&self) -> String {
+        match self.state {
+            State::Idle => "audio-input-microphone".into(),
+            State::Recording { .. } => "media-record".into(),
+        }
+    }
+
+    fn tool_tip(&self) -> ksni::ToolTip {
+        let title = match self.state {
+            State::Idle => "rpdictation: idle".to_string(),
+            State::Recording { elapsed } => {
+                format!("rpdictation: recording {}", status::format_elapsed(elapsed))
+            }
+        };
+        ksni::ToolTip {
+            title,
+            ..Default::default()
+        }
+    }
+
+    /// Left click toggles
+    fn activate(&mut self, _x: i32, _y: i32) {
+        if self.can_start || matches!(self.state, State::Recording { .. }) {
+            self.send("toggle");
+        }
+    }
+
+    fn menu(&self) -> Vec<ksni::MenuItem<Self>> {
+        use ksni::menu::StandardItem;
+        let recording = matches!(self.state, State::Recording { .. });
+        vec![
+            StandardItem {
+                label: "Start dictation".into(),
+                enabled: self.can_start,
+                activate: Box::new(|this: &mut Self| this.send("start")),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "Stop and transcribe".into(),
+                enabled: recording,
+                activate: Box::new(|this: &mut Self| this.send("stop")),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "Cancel".into(),
+                enabled: recording,
+                activate: Box::new(|this: &mut Self| this.send("cancel")),
+                ..Default::default()
+            }
+            .into(),
+        ]
+    }
+}
+
+/// Show a tray icon for the daemon or a one-shot recording, whichever is
+/// running, until killed. Starting is only offered when a daemon is running;
+/// a one-shot recording has to be started the usual way. `session` is the
+/// --session it belongs to.
+pub async fn run(session: Option<String>) -> Result<()> {
+    let (commands, mut command_rx) = mpsc::unbounded_channel();
+    let handle = Indicator {
+        state: State::Idle,
+        can_start: false,
+        commands,
+        session,
+    }
+    .spawn()
+    .await
+    .context("Failed to show tray icon (is a StatusNotifier host running?)")?;
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            Some(command) = command_rx.recv() => {
+                match crate::control::connect().await {
+                    Some(channel) => {
+                        if let Err(e) = channel.send(command).await {
+                            eprintln!("{}", e);
+                        }
+                    }
+                    None => eprintln!("No recording in progress"),
+                }
+            }
+        }
+        let state = status::query().await;
+        // Idle but reachable means a daemon waiting for commands
+        let can_start = state == State::Idle && crate::control::connect().await.is_some();
+        handle
+            .update(|tray: &mut Indicator| {
+                tray.state = state;
+                tray.can_start = can_start;
+            })
+            .await;
+    }
+}
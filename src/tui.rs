@@ -0,0 +1,169 @@
+use anyhow::{Context, Result};
+use crossterm::event::{Event as TermEvent, EventStream, KeyCode, KeyEventKind, KeyModifiers};
+use futures_util::StreamExt;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Gauge, List, ListItem, Paragraph, Wrap};
+use ratatui::Frame;
+use std::os::fd::AsRawFd;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::frontend::Controller;
+use crate::{status, Args};
+use rpdictation::session::State;
+
+/// How often the screen is redrawn, for the level meter and the clock
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Dictate in a full-screen terminal interface: start, stop, cancel, and
+/// retry with single keys, with the input level, the text recognized so far,
+/// and the finished dictations on screen.
+pub async fn run(args: Args) -> Result<()> {
+    // Diagnostics would scribble over the screen
+    let log = rpdictation::get_runtime_dir().join("tui.log");
+    let _stderr = StderrRedirect::to(&log)?;
+
+    let mut app = Controller::open(&args).await?;
+    let mut terminal = ratatui::try_init().context("Failed to set up the terminal")?;
+    let result = event_loop(&mut app, &mut terminal).await;
+    ratatui::restore();
+    if let State::Recording { .. } = app.session.state() {
+        let _ = app.session.cancel();
+    }
+    result
+}
+
+async fn event_loop(app: &mut Controller, terminal: &mut ratatui::DefaultTerminal) -> Result<()> {
+    let mut keys = EventStream::new();
+    let mut events = app.session.subscribe();
+    let mut frame_tick = tokio::time::interval(FRAME_INTERVAL);
+
+    loop {
+        terminal.draw(|frame| draw(frame, app))?;
+        tokio::select! {
+            _ = frame_tick.tick() => app.update_preview(),
+            event = events.recv() => match event {
+                Ok(event) => app.follow(event),
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            key = keys.next() => {
+                let Some(key) = key else { break };
+                let TermEvent::Key(key) = key.context("Failed to read the terminal")? else {
+                    continue;
+                };
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                app.message = None;
+                match key.code {
+                    KeyCode::Char('q') => break,
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+                    KeyCode::Char(' ') | KeyCode::Enter => app.toggle(),
+                    KeyCode::Esc => app.cancel(),
+                    KeyCode::Char('r') => app.retry(),
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn draw(frame: &mut Frame, app: &Controller) {
+    let state = app.session.state();
+    let [header, meter, live, history, help] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(6),
+        Constraint::Min(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let mut spans = match state {
+        State::Recording { since, .. } => vec![
+            "● REC ".red().bold(),
+            Span::raw(status::format_elapsed(since.elapsed())),
+        ],
+        State::Idle => vec![Span::raw("Idle").dim()],
+    };
+    spans.push(Span::raw(format!(
+        "   Providers: {}",
+        app.session.provider_names().join(" → ")
+    )));
+    if !app.transcribing.is_empty() {
+        spans.push(format!("   Transcribing ({})…", app.transcribing.len()).yellow());
+    }
+    frame.render_widget(Line::from(spans), header);
+
+    let (db, ratio) = app.level();
+    frame.render_widget(
+        Gauge::default()
+            .gauge_style(Style::default().fg(Color::Green))
+            .ratio(f64::from(ratio))
+            .label(format!("Level {:.0} dB", db)),
+        meter,
+    );
+
+    let partial = match state {
+        State::Recording { .. } if app.partial.is_empty() => "Listening…",
+        _ => &app.partial,
+    };
+    frame.render_widget(
+        Paragraph::new(partial)
+            .wrap(Wrap { trim: true })
+            .block(Block::bordered().title(" Live ")),
+        live,
+    );
+
+    let items: Vec<ListItem> = app
+        .history
+        .iter()
+        .map(|entry| {
+            let time = Span::raw(format!("{} ", entry.time.format("%H:%M:%S"))).dim();
+            let line = match entry.result {
+                Ok(ref text) => Line::from(vec![time, Span::raw(text.clone())]),
+                Err(ref error) => Line::from(vec![time, format!("✗ {}", error).red()]),
+            };
+            ListItem::new(line)
+        })
+        .collect();
+    frame.render_widget(
+        List::new(items).block(Block::bordered().title(" History ")),
+        history,
+    );
+
+    let help_text = match app.message {
+        Some(ref message) => message.clone().red(),
+        None => Span::raw("Space start/stop · Esc cancel · r retry · q quit").dim(),
+    };
+    frame.render_widget(Line::from(help_text), help);
+}
+
+/// Sends everything written to stderr to a file until dropped
+struct StderrRedirect {
+    saved: std::os::fd::RawFd,
+}
+
+impl StderrRedirect {
+    fn to(path: &std::path::Path) -> Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let saved = nix::unistd::dup(std::io::stderr().as_raw_fd())?;
+        nix::unistd::dup2(file.as_raw_fd(), std::io::stderr().as_raw_fd())?;
+        Ok(Self { saved })
+    }
+}
+
+impl Drop for StderrRedirect {
+    fn drop(&mut self) {
+        let _ = nix::unistd::dup2(self.saved, std::io::stderr().as_raw_fd());
+        let _ = nix::unistd::close(self.saved);
+    }
+}
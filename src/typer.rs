@@ -0,0 +1,490 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use crate::clipboard::{restore_selection, save_selection};
+use crate::focus::{FocusProvider, WindowId};
+use crate::punctuation::{self, Case};
+#[cfg(feature = "native-typer")]
+use crate::virtual_keyboard::{self, Stroke, MOD_CTRL, MOD_SHIFT};
+
+/// Window that was focused when recording started, and the provider that can
+/// switch back to it.
+pub struct FocusTracking {
+    pub provider: Box<dyn FocusProvider>,
+    pub window: WindowId,
+    /// Refuse to type if focus has moved elsewhere, instead of switching back
+    pub strict: bool,
+}
+
+/// Longest piece of text handed to a single wtype/ydotool run
+const CHUNK_CHARS: usize = 200;
+
+/// Runs per chunk before giving up on it
+const CHUNK_ATTEMPTS: u32 = 3;
+
+/// Key combination sent to paste the transcription in paste mode
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PasteKey {
+    /// Pastes in most applications, including terminals
+    #[default]
+    ShiftInsert,
+    /// For applications that ignore Shift+Insert (e.g. some browsers and
+    /// Electron apps)
+    CtrlV,
+    /// Terminal emulators that paste on Ctrl+Shift+V
+    CtrlShiftV,
+}
+
+impl PasteKey {
+    fn name(self) -> &'static str {
+        match self {
+            PasteKey::ShiftInsert => "Shift+Insert",
+            PasteKey::CtrlV => "Ctrl+V",
+            PasteKey::CtrlShiftV => "Ctrl+Shift+V",
+        }
+    }
+
+    fn wtype_args(self) -> &'static [&'static str] {
+        match self {
+            PasteKey::ShiftInsert => &["-M", "shift", "-k", "Insert", "-m", "shift"],
+            PasteKey::CtrlV => &["-M", "ctrl", "-k", "v", "-m", "ctrl"],
+            PasteKey::CtrlShiftV => &[
+                "-M", "ctrl", "-M", "shift", "-k", "v", "-m", "shift", "-m", "ctrl",
+            ],
+        }
+    }
+
+    #[cfg(feature = "native-typer")]
+    fn stroke(self) -> Stroke {
+        use virtual_keyboard::{KEYSYM_INSERT, KEYSYM_V};
+        match self {
+            PasteKey::ShiftInsert => Stroke {
+                keysym: KEYSYM_INSERT,
+                modifiers: MOD_SHIFT,
+            },
+            PasteKey::CtrlV => Stroke {
+                keysym: KEYSYM_V,
+                modifiers: MOD_CTRL,
+            },
+            PasteKey::CtrlShiftV => Stroke {
+                keysym: KEYSYM_V,
+                modifiers: MOD_CTRL | MOD_SHIFT,
+            },
+        }
+    }
+
+    /// Key events for `ydotool key` (29=KEY_LEFTCTRL, 42=KEY_LEFTSHIFT,
+    /// 47=KEY_V, 110=KEY_INSERT)
+    fn ydotool_args(self) -> &'static [&'static str] {
+        match self {
+            PasteKey::ShiftInsert => &["42:1", "110:1", "110:0", "42:0"],
+            PasteKey::CtrlV => &["29:1", "47:1", "47:0", "29:0"],
+            PasteKey::CtrlShiftV => &["29:1", "42:1", "47:1", "47:0", "42:0", "29:0"],
+        }
+    }
+}
+
+/// How text typed into a particular application differs from the rest,
+/// from an `[app.ID]` table of the config file. Unset fields leave the
+/// general settings alone.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct AppStyle {
+    /// Applied on top of the general --case, so `preserve` changes nothing
+    pub case: Option<Case>,
+    pub strip_period: Option<bool>,
+    pub enter: Option<bool>,
+    pub paste: Option<bool>,
+    pub paste_key: Option<PasteKey>,
+}
+
+impl AppStyle {
+    /// `text` as it should be typed into the application
+    pub fn apply(&self, text: &str) -> String {
+        let text = punctuation::apply_case(text, self.case.unwrap_or_default());
+        if self.strip_period == Some(true) {
+            punctuation::strip_trailing_period(&text)
+        } else {
+            text
+        }
+    }
+}
+
+/// Inserts text into the focused window using an external typing tool, the
+/// compositor's virtual keyboard protocol for the `native` typer, its
+/// input method protocol for the `input-method` typer, or the accessibility
+/// interface for the `atspi` typer.
+#[derive(Clone)]
+pub struct Typer {
+    tool: String,
+    /// Paste through the clipboard with this key instead of typing
+    paste: Option<PasteKey>,
+    enter: bool,
+    /// Pause between keystrokes when typing directly
+    key_delay: Option<Duration>,
+}
+
+impl Typer {
+    pub fn new(
+        tool: String,
+        paste: Option<PasteKey>,
+        enter: bool,
+        key_delay: Option<Duration>,
+    ) -> Result<Self> {
+        if !matches!(
+            tool.as_str(),
+            "wtype" | "ydotool" | "native" | "input-method" | "atspi"
+        ) {
+            anyhow::bail!(
+                "Unknown typer '{}'. Supported: wtype, ydotool, native, input-method, atspi",
+                tool
+            );
+        }
+        if cfg!(not(feature = "dbus")) && tool == "atspi" {
+            anyhow::bail!("The atspi typer needs rpdictation built with the dbus feature");
+        }
+        if cfg!(not(feature = "native-typer")) && matches!(tool.as_str(), "native" | "input-method")
+        {
+            anyhow::bail!(
+                "The {} typer needs rpdictation built with the native-typer feature",
+                tool
+            );
+        }
+        Ok(Self {
+            tool,
+            paste,
+            enter,
+            key_delay,
+        })
+    }
+
+    /// This typer with the Enter and paste settings of `style` instead
+    pub fn styled(&self, style: &AppStyle) -> Typer {
+        let paste = match style.paste {
+            Some(false) => None,
+            Some(true) => Some(style.paste_key.or(self.paste).unwrap_or_default()),
+            None => self.paste.map(|key| style.paste_key.unwrap_or(key)),
+        };
+        Typer {
+            paste,
+            enter: style.enter.unwrap_or(self.enter),
+            ..self.clone()
+        }
+    }
+
+    /// Type `text` (and optionally press Enter). With `focus`, switch back to
+    /// the tracked window first and restore the current one afterwards.
+    /// Once `cancel` fires, typing stops after the piece being typed, and
+    /// Enter isn't pressed; pasting and committing text are too quick to
+    /// stop halfway.
+    pub async fn type_text(
+        &self,
+        text: &str,
+        focus: Option<&FocusTracking>,
+        cancel: &CancellationToken,
+    ) -> Result<()> {
+        // Handle focus tracking if enabled
+        let restore_window_id = if let Some(focus) = focus {
+            let fp = &focus.provider;
+            let saved_wid = &focus.window;
+
+            // Get current focused window
+            let current_wid = fp.get_focused_window().await.ok().flatten();
+
+            if current_wid.as_ref() != Some(saved_wid) && focus.strict {
+                anyhow::bail!(
+                    "Focus moved from {:?} to {:?} since recording started; not typing",
+                    saved_wid,
+                    current_wid
+                );
+            }
+            if current_wid.as_ref() != Some(saved_wid) {
+                // Focus changed, need to switch back
+                eprintln!(
+                    "Focus changed from {:?} to {:?}, switching back",
+                    saved_wid, current_wid
+                );
+
+                // Try to focus the original window
+                match fp.set_focused_window(saved_wid).await {
+                    Ok(true) => {
+                        eprintln!("Switched focus to original window");
+                        // Remember current window for restoration after typing
+                        current_wid
+                    }
+                    Ok(false) => {
+                        eprintln!(
+                            "Warning: Failed to switch to original window (may be closed), typing into current"
+                        );
+                        None
+                    }
+                    Err(e) => {
+                        eprintln!("Warning: Error switching focus: {}, typing into current", e);
+                        None
+                    }
+                }
+            } else {
+                // Focus unchanged, no need to restore
+                None
+            }
+        } else {
+            None
+        };
+
+        let result = match self.tool.as_str() {
+            "wtype" => self.type_wtype(text, cancel).await,
+            #[cfg(feature = "native-typer")]
+            "native" => self.type_native(text, cancel).await,
+            #[cfg(feature = "native-typer")]
+            "input-method" => self.type_input_method(text).await,
+            #[cfg(feature = "dbus")]
+            "atspi" => self.type_atspi(text).await,
+            _ => self.type_ydotool(text, cancel).await,
+        };
+
+        // Restore focus to the window that was focused before we switched
+        if let (Some(focus), Some(ref restore_wid)) = (focus, &restore_window_id) {
+            eprintln!("Restoring focus to {:?}", restore_wid);
+            if let Err(e) = focus.provider.set_focused_window(restore_wid).await {
+                eprintln!("Warning: Failed to restore focus: {}", e);
+            }
+        }
+
+        result
+    }
+
+    async fn type_wtype(&self, text: &str, cancel: &CancellationToken) -> Result<()> {
+        if let Some(key) = self.paste {
+            let saved_clipboard = save_selection(false).await;
+            let saved_primary = save_selection(true).await;
+
+            run(tokio::process::Command::new("wl-copy").args(["--", text]))
+                .await
+                .context("Failed to run wl-copy")?;
+            run(tokio::process::Command::new("wl-copy").args(["--primary", "--", text]))
+                .await
+                .context("Failed to run wl-copy --primary")?;
+
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+            run(tokio::process::Command::new("wtype").args(key.wtype_args()))
+                .await
+                .with_context(|| format!("Failed to run wtype for {} paste", key.name()))?;
+
+            if self.enter {
+                run(tokio::process::Command::new("wtype").args(["-k", "Return"]))
+                    .await
+                    .context("Failed to run wtype for Enter")?;
+            }
+
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+            restore_selection(false, saved_clipboard).await.ok();
+            restore_selection(true, saved_primary).await.ok();
+        } else {
+            type_chunked(text, cancel, |chunk| {
+                let mut cmd = tokio::process::Command::new("wtype");
+                if let Some(delay) = self.key_delay {
+                    cmd.arg("-d").arg(delay.as_millis().to_string());
+                }
+                cmd.arg(chunk);
+                cmd
+            })
+            .await?;
+            if self.enter {
+                run(tokio::process::Command::new("wtype").args(["-k", "Return"]))
+                    .await
+                    .context("Failed to run wtype for Enter")?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "native-typer")]
+    async fn type_native(&self, text: &str, cancel: &CancellationToken) -> Result<()> {
+        let mut strokes = Vec::new();
+        let pasted = match self.paste {
+            Some(key) => {
+                strokes.push(key.stroke());
+                Some((save_selection(false).await, save_selection(true).await))
+            }
+            None => {
+                strokes.extend(text.chars().filter_map(Stroke::char));
+                None
+            }
+        };
+        if self.enter {
+            strokes.push(Stroke::key(virtual_keyboard::KEYSYM_RETURN));
+        }
+
+        if pasted.is_some() {
+            run(tokio::process::Command::new("wl-copy").args(["--", text]))
+                .await
+                .context("Failed to run wl-copy")?;
+            run(tokio::process::Command::new("wl-copy").args(["--primary", "--", text]))
+                .await
+                .context("Failed to run wl-copy --primary")?;
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+
+        let key_delay = self.key_delay;
+        let cancel = cancel.clone();
+        tokio::task::spawn_blocking(move || {
+            virtual_keyboard::send(&strokes, key_delay, Some(&cancel))
+        })
+        .await
+        .context("Virtual keyboard task panicked")??;
+
+        if let Some((saved_clipboard, saved_primary)) = pasted {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            restore_selection(false, saved_clipboard).await.ok();
+            restore_selection(true, saved_primary).await.ok();
+        }
+        Ok(())
+    }
+
+    /// Commit the text as an input method would, instead of typing it.
+    /// There's nothing to paste, so paste mode changes nothing.
+    #[cfg(feature = "native-typer")]
+    async fn type_input_method(&self, text: &str) -> Result<()> {
+        let text = text.to_string();
+        let enter = self.enter;
+        tokio::task::spawn_blocking(move || {
+            crate::input_method::commit(&text)?;
+            if enter {
+                virtual_keyboard::send(
+                    &[Stroke::key(virtual_keyboard::KEYSYM_RETURN)],
+                    None,
+                    None,
+                )?;
+            }
+            Ok::<_, anyhow::Error>(())
+        })
+        .await
+        .context("Input method task panicked")?
+    }
+
+    /// Insert the text into the focused field's contents through AT-SPI.
+    /// Enter is a key press, which only the virtual keyboard can make.
+    #[cfg(feature = "dbus")]
+    async fn type_atspi(&self, text: &str) -> Result<()> {
+        crate::atspi::insert(text).await?;
+        if self.enter {
+            #[cfg(feature = "native-typer")]
+            tokio::task::spawn_blocking(|| {
+                virtual_keyboard::send(&[Stroke::key(virtual_keyboard::KEYSYM_RETURN)], None, None)
+            })
+            .await
+            .context("Virtual keyboard task panicked")??;
+            #[cfg(not(feature = "native-typer"))]
+            anyhow::bail!("Pressing Enter after atspi needs the native-typer feature");
+        }
+        Ok(())
+    }
+
+    async fn type_ydotool(&self, text: &str, cancel: &CancellationToken) -> Result<()> {
+        if let Some(key) = self.paste {
+            let saved_clipboard = save_selection(false).await;
+            let saved_primary = save_selection(true).await;
+
+            // Set both CLIPBOARD and PRIMARY selections — Shift+Insert
+            // pastes from PRIMARY in many apps (especially terminals),
+            // while others paste from CLIPBOARD.
+            run(tokio::process::Command::new("wl-copy").args(["--", text]))
+                .await
+                .context("Failed to run wl-copy")?;
+            run(tokio::process::Command::new("wl-copy").args(["--primary", "--", text]))
+                .await
+                .context("Failed to run wl-copy --primary")?;
+
+            // Small delay to ensure clipboard is ready
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+            run(tokio::process::Command::new("ydotool")
+                .arg("key")
+                .args(key.ydotool_args()))
+            .await
+            .with_context(|| format!("Failed to run ydotool key for {} paste", key.name()))?;
+
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+            restore_selection(false, saved_clipboard).await.ok();
+            restore_selection(true, saved_primary).await.ok();
+        } else {
+            let delay = self.key_delay.map_or(1, |d| d.as_millis()).to_string();
+            type_chunked(text, cancel, |chunk| {
+                let mut cmd = tokio::process::Command::new("ydotool");
+                cmd.args(["type", "-d", &delay, "--", chunk]);
+                cmd
+            })
+            .await?;
+        }
+        if self.enter {
+            run(tokio::process::Command::new("ydotool").args(["key", "28:1", "28:0"]))
+                .await
+                .context("Failed to run ydotool key")?;
+        }
+        Ok(())
+    }
+}
+
+/// Run `cmd` to its end, failing unless it exits successfully
+async fn run(cmd: &mut tokio::process::Command) -> Result<()> {
+    let status = cmd.status().await?;
+    if !status.success() {
+        let tool = cmd.as_std().get_program().to_string_lossy().into_owned();
+        anyhow::bail!("{} exited with {}", tool, status);
+    }
+    Ok(())
+}
+
+/// Type `text` in chunks of at most [`CHUNK_CHARS`], running `command` for
+/// each one. A chunk whose run fails is retried; if it keeps failing, or
+/// `cancel` fires, the error tells how much of the text was typed, instead
+/// of the tail going missing silently.
+async fn type_chunked<F>(text: &str, cancel: &CancellationToken, command: F) -> Result<()>
+where
+    F: Fn(&str) -> tokio::process::Command,
+{
+    let chars: Vec<char> = text.chars().collect();
+    let chunks: Vec<String> = chars
+        .chunks(CHUNK_CHARS)
+        .map(|c| c.iter().collect())
+        .collect();
+    let mut typed = 0;
+    for chunk in &chunks {
+        if cancel.is_cancelled() {
+            anyhow::bail!(
+                "Typing cancelled after {} of {} characters",
+                typed,
+                chars.len()
+            );
+        }
+        let mut attempt = 1;
+        loop {
+            let mut cmd = command(chunk);
+            let tool = cmd.as_std().get_program().to_string_lossy().into_owned();
+            let error = match cmd.status().await {
+                Ok(status) if status.success() => break,
+                Ok(status) => anyhow::anyhow!("{} exited with {}", tool, status),
+                Err(e) => anyhow::Error::new(e).context(format!("Failed to run {}", tool)),
+            };
+            if attempt == CHUNK_ATTEMPTS {
+                return Err(error.context(format!(
+                    "Typing stopped after {} of {} characters",
+                    typed,
+                    chars.len()
+                )));
+            }
+            eprintln!("Warning: {:#}, retrying", error);
+            attempt += 1;
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        typed += chunk.chars().count();
+    }
+    Ok(())
+}
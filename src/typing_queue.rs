@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::focus::WindowId;
+use crate::punctuation;
+use crate::typer::{AppStyle, FocusTracking, Typer};
+
+/// How many finished transcripts may wait for typing before producers block
+const QUEUE_CAPACITY: usize = 8;
+
+/// A transcript to type, the window it belongs to, and where to report the
+/// outcome.
+struct Delivery {
+    text: String,
+    focus: Option<Arc<FocusTracking>>,
+    style: Option<Arc<AppStyle>>,
+    /// More text for the same ticket follows; its slot stays open
+    partial: bool,
+    /// Continues the text typed for the same ticket so far
+    continues: bool,
+    /// Stops typing it, see [`Ticket::cancel_on`]
+    cancel: CancellationToken,
+    done: oneshot::Sender<Result<()>>,
+}
+
+/// A delivery (or `None` when the utterance produced nothing to type) tagged
+/// with the position it must be typed at.
+type Job = (u64, Option<Delivery>);
+
+/// Serializes delivery of transcripts into the focused window.
+///
+/// Each utterance reserves a [`Ticket`] when its recording starts, and its
+/// text is typed only after every earlier ticket has been delivered or
+/// dropped. Transcriptions that come back out of order are held until their
+/// turn, and a whole transcript is typed before the next one starts, so
+/// outputs never interleave mid-word. The channel is bounded: when typing
+/// falls behind, [`Ticket::deliver`] waits.
+pub struct TypingQueue {
+    tx: mpsc::Sender<Job>,
+    next_seq: u64,
+    worker: JoinHandle<()>,
+}
+
+impl TypingQueue {
+    /// Start the typing worker. With `append`, a transcript typed into the
+    /// same window as the previous one continues it (see
+    /// [`punctuation::join`]) instead of being typed verbatim. Cancelling
+    /// `cancel` drops everything still waiting to be typed; a transcript being
+    /// typed right now is finished first.
+    pub fn spawn(typer: Typer, append: bool, cancel: CancellationToken) -> Self {
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+        let worker = tokio::spawn(run(typer, append, rx, cancel));
+        Self {
+            tx,
+            next_seq: 0,
+            worker,
+        }
+    }
+
+    /// Reserve the next position in the output order. `focus` is the window
+    /// the text should be typed into, and `style` how the application that
+    /// had the focus wants it typed.
+    pub fn reserve(&mut self, focus: Option<FocusTracking>, style: Option<AppStyle>) -> Ticket {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        Ticket {
+            seq,
+            focus: focus.map(Arc::new),
+            style: style.map(Arc::new),
+            tx: Some(self.tx.clone()),
+            typed_words: 0,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Wait until every reserved ticket has been typed or skipped.
+    pub async fn finish(self) -> Result<()> {
+        drop(self.tx);
+        self.worker
+            .await
+            .map_err(|_| anyhow::anyhow!("Typing task panicked"))
+    }
+}
+
+/// A reserved slot in the typing order.
+///
+/// Dropping a ticket without delivering skips its slot, so a failed or
+/// cancelled dictation never stalls the ones behind it.
+pub struct Ticket {
+    seq: u64,
+    focus: Option<Arc<FocusTracking>>,
+    style: Option<Arc<AppStyle>>,
+    tx: Option<mpsc::Sender<Job>>,
+    /// Words typed ahead with [`Ticket::deliver_part`]
+    typed_words: usize,
+    cancel: CancellationToken,
+}
+
+impl Ticket {
+    /// Queue `text` and wait until it has been typed. If the start of the
+    /// transcript was already typed with [`Ticket::deliver_part`], only the
+    /// words after as many words as were typed then are typed now; words that
+    /// came out differently in the end are not corrected.
+    pub async fn deliver(mut self, text: String) -> Result<()> {
+        let text = skip_words(&text, self.typed_words);
+        if self.typed_words > 0 && text.is_empty() {
+            return Ok(());
+        }
+        let tx = self.tx.take().expect("ticket delivered twice");
+        self.send(&tx, text.to_string(), false).await
+    }
+
+    /// Press Enter once the transcript has been typed, whatever the typer
+    /// and the application's settings say
+    pub fn press_enter(&mut self) {
+        let mut style = self.style.as_deref().cloned().unwrap_or_default();
+        style.enter = Some(true);
+        self.style = Some(Arc::new(style));
+    }
+
+    /// Stop typing the text once `cancel` fires: what is still waiting isn't
+    /// typed, and text being typed stops after the current piece
+    pub fn cancel_on(&mut self, cancel: CancellationToken) {
+        self.cancel = cancel;
+    }
+
+    /// Type `text` as the start of a transcript that is still being
+    /// recorded, keeping the slot open for the rest. Later parts and the
+    /// final [`Ticket::deliver`] continue it as in append mode.
+    pub async fn deliver_part(&mut self, text: String) -> Result<()> {
+        let tx = self.tx.clone().expect("ticket delivered twice");
+        let words = text.split_whitespace().count();
+        self.send(&tx, text, true).await?;
+        self.typed_words += words;
+        Ok(())
+    }
+
+    async fn send(&self, tx: &mpsc::Sender<Job>, text: String, partial: bool) -> Result<()> {
+        let (done, result) = oneshot::channel();
+        let delivery = Delivery {
+            text,
+            focus: self.focus.clone(),
+            style: self.style.clone(),
+            partial,
+            continues: self.typed_words > 0,
+            cancel: self.cancel.clone(),
+            done,
+        };
+        tx.send((self.seq, Some(delivery)))
+            .await
+            .map_err(|_| anyhow::anyhow!("Typing queue closed"))?;
+        result
+            .await
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("Typing cancelled")))
+    }
+}
+
+/// `text` without its first `n` words and the whitespace after them
+fn skip_words(text: &str, n: usize) -> &str {
+    let mut rest = text.trim_start();
+    for _ in 0..n {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        rest = rest[end..].trim_start();
+    }
+    rest
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        if let Some(tx) = self.tx.take() {
+            let seq = self.seq;
+            tokio::spawn(async move {
+                let _ = tx.send((seq, None)).await;
+            });
+        }
+    }
+}
+
+async fn run(typer: Typer, append: bool, mut rx: mpsc::Receiver<Job>, cancel: CancellationToken) {
+    let mut pending: BTreeMap<u64, Option<Delivery>> = BTreeMap::new();
+    let mut next = 0;
+    // What was typed last and into which window, for joining continuations
+    let mut last: Option<(String, Option<WindowId>)> = None;
+    loop {
+        let (seq, delivery) = tokio::select! {
+            _ = cancel.cancelled() => return,
+            job = rx.recv() => match job {
+                Some(job) => job,
+                None => return,
+            },
+        };
+        pending.insert(seq, delivery);
+
+        while let Some(delivery) = pending.remove(&next) {
+            let partial = delivery.as_ref().is_some_and(|d| d.partial);
+            if !partial {
+                next += 1;
+            }
+            if cancel.is_cancelled() {
+                return;
+            }
+            if let Some(d) = delivery {
+                if d.cancel.is_cancelled() {
+                    let _ = d.done.send(Err(anyhow::anyhow!("Typing cancelled")));
+                    continue;
+                }
+                let window = d.focus.as_ref().map(|f| f.window.clone());
+                let own_text = match d.style {
+                    Some(ref style) => style.apply(&d.text),
+                    None => d.text,
+                };
+                let text = match last {
+                    Some((ref prev, ref prev_window))
+                        if (append || d.continues) && *prev_window == window =>
+                    {
+                        punctuation::join(prev, &own_text)
+                    }
+                    _ => own_text,
+                };
+                let styled = d.style.as_deref().map(|style| {
+                    // Enter goes after the whole transcript, not each part
+                    typer.styled(&AppStyle {
+                        enter: style.enter.filter(|_| !d.partial),
+                        ..style.clone()
+                    })
+                });
+                let result = styled
+                    .as_ref()
+                    .unwrap_or(&typer)
+                    .type_text(&text, d.focus.as_deref(), &d.cancel)
+                    .await;
+                if result.is_ok() {
+                    last = Some((text, window));
+                }
+                let _ = d.done.send(result);
+            }
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use crate::SAMPLE_RATE;
+
+/// Analysis frame: 20 ms
+const FRAME_SAMPLES: usize = SAMPLE_RATE as usize / 50;
+
+/// A pause this long after speech ends a segment
+const PAUSE_FRAMES: usize = 40; // 800 ms
+
+/// Audio kept from before speech starts, so the first syllable isn't cut
+const PREROLL_FRAMES: usize = 15; // 300 ms
+
+/// Cuts a continuous recording into utterances at pauses, using a plain
+/// energy threshold: a frame whose RMS reaches `threshold` counts as speech.
+pub struct Segmenter {
+    threshold: f64,
+    /// Whole frames of the segment being collected
+    segment: Vec<i16>,
+    /// Samples that don't fill a frame yet
+    partial: Vec<i16>,
+    has_speech: bool,
+    silent_frames: usize,
+}
+
+impl Segmenter {
+    pub fn new(threshold: f64) -> Self {
+        Self {
+            threshold,
+            segment: Vec::new(),
+            partial: Vec::new(),
+            has_speech: false,
+            silent_frames: 0,
+        }
+    }
+
+    /// Feed newly recorded samples; returns every segment they completed.
+    pub fn push(&mut self, samples: &[i16]) -> Vec<Vec<i16>> {
+        let mut done = Vec::new();
+        self.partial.extend_from_slice(samples);
+        let whole = self.partial.len() / FRAME_SAMPLES * FRAME_SAMPLES;
+        let frames: Vec<i16> = self.partial.drain(..whole).collect();
+
+        for frame in frames.chunks_exact(FRAME_SAMPLES) {
+            self.segment.extend_from_slice(frame);
+            if rms(frame) >= self.threshold {
+                self.has_speech = true;
+                self.silent_frames = 0;
+            } else {
+                self.silent_frames += 1;
+            }
+
+            if self.has_speech && self.silent_frames >= PAUSE_FRAMES {
+                done.push(std::mem::take(&mut self.segment));
+                self.has_speech = false;
+                self.silent_frames = 0;
+            } else if !self.has_speech && self.segment.len() > PREROLL_FRAMES * FRAME_SAMPLES {
+                let excess = self.segment.len() - PREROLL_FRAMES * FRAME_SAMPLES;
+                self.segment.drain(..excess);
+            }
+        }
+        done
+    }
+
+    /// The unfinished segment, if any speech was heard in it.
+    pub fn finish(mut self) -> Option<Vec<i16>> {
+        self.segment.append(&mut self.partial);
+        self.has_speech.then_some(self.segment)
+    }
+}
+
+fn rms(frame: &[i16]) -> f64 {
+    let sum: f64 = frame.iter().map(|&s| (s as f64) * (s as f64)).sum();
+    (sum / frame.len() as f64).sqrt()
+}
@@ -0,0 +1,178 @@
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::os::fd::AsFd;
+use tokio_util::sync::CancellationToken;
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::{wl_registry, wl_seat::WlSeat};
+use wayland_client::{delegate_noop, Connection, Dispatch, QueueHandle};
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::{
+    zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1,
+    zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1,
+};
+
+/// Real modifier masks, fixed by XKB
+pub const MOD_SHIFT: u32 = 1 << 0;
+pub const MOD_CTRL: u32 = 1 << 2;
+
+pub const KEYSYM_RETURN: u32 = 0xff0d;
+pub const KEYSYM_TAB: u32 = 0xff09;
+pub const KEYSYM_INSERT: u32 = 0xff63;
+pub const KEYSYM_V: u32 = 0x0076;
+
+/// wl_keyboard keymap format and key states
+const KEYMAP_FORMAT_XKB_V1: u32 = 1;
+const KEY_RELEASED: u32 = 0;
+const KEY_PRESSED: u32 = 1;
+
+/// One key press, with the modifiers held during it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stroke {
+    pub keysym: u32,
+    pub modifiers: u32,
+}
+
+impl Stroke {
+    pub fn key(keysym: u32) -> Self {
+        Self {
+            keysym,
+            modifiers: 0,
+        }
+    }
+
+    /// The stroke that enters `c`, or `None` for control characters that
+    /// have no key.
+    pub fn char(c: char) -> Option<Self> {
+        let keysym = match c {
+            '\n' => KEYSYM_RETURN,
+            '\t' => KEYSYM_TAB,
+            c if c.is_control() => return None,
+            // Latin-1 keysyms equal the code point, everything else lives in
+            // the Unicode keysym range
+            ' '..='~' | '\u{a0}'..='\u{ff}' => c as u32,
+            c => 0x0100_0000 | c as u32,
+        };
+        Some(Self::key(keysym))
+    }
+}
+
+struct State;
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+delegate_noop!(State: ignore WlSeat);
+delegate_noop!(State: ZwpVirtualKeyboardManagerV1);
+delegate_noop!(State: ZwpVirtualKeyboardV1);
+
+/// Send `strokes` to the focused window through the compositor's
+/// virtual-keyboard-unstable-v1 protocol. A keymap holding exactly the
+/// needed keysyms is uploaded first, so any character can be typed
+/// regardless of the user's layout. `delay` is slept between strokes, and
+/// once `cancel` fires the rest are left out. Blocking.
+pub fn send(
+    strokes: &[Stroke],
+    delay: Option<std::time::Duration>,
+    cancel: Option<&CancellationToken>,
+) -> Result<()> {
+    let conn = Connection::connect_to_env().context("Not running under a Wayland compositor")?;
+    let (globals, mut queue) = registry_queue_init::<State>(&conn)?;
+    let qh = queue.handle();
+    let seat: WlSeat = globals.bind(&qh, 1..=1, ()).context("No Wayland seat")?;
+    let manager: ZwpVirtualKeyboardManagerV1 = globals
+        .bind(&qh, 1..=1, ())
+        .context("Compositor doesn't support virtual-keyboard-unstable-v1")?;
+    let keyboard = manager.create_virtual_keyboard(&seat, &qh, ());
+
+    let mut keysyms: Vec<u32> = Vec::new();
+    for stroke in strokes {
+        if !keysyms.contains(&stroke.keysym) {
+            keysyms.push(stroke.keysym);
+        }
+    }
+    let keymap = keymap(&keysyms);
+    let file = keymap_file(&keymap)?;
+    keyboard.keymap(KEYMAP_FORMAT_XKB_V1, file.as_fd(), keymap.len() as u32);
+    queue.roundtrip(&mut State)?;
+
+    let mut time = 0;
+    let mut sent = strokes.len();
+    for (i, stroke) in strokes.iter().enumerate() {
+        if cancel.is_some_and(|c| c.is_cancelled()) {
+            sent = i;
+            break;
+        }
+        if let Some(delay) = delay.filter(|_| i > 0) {
+            std::thread::sleep(delay);
+        }
+        // Keymap keycodes start at 9, which is evdev code 1
+        let code = keysyms.iter().position(|&k| k == stroke.keysym).unwrap() as u32 + 1;
+        if stroke.modifiers != 0 {
+            keyboard.modifiers(stroke.modifiers, 0, 0, 0);
+        }
+        keyboard.key(time, code, KEY_PRESSED);
+        time += 1;
+        keyboard.key(time, code, KEY_RELEASED);
+        time += 1;
+        if stroke.modifiers != 0 {
+            keyboard.modifiers(0, 0, 0, 0);
+        }
+        // Let the compositor process each key before the next one
+        queue.roundtrip(&mut State)?;
+    }
+
+    keyboard.destroy();
+    queue.roundtrip(&mut State)?;
+    if sent < strokes.len() {
+        anyhow::bail!("Typing cancelled after {} of {} keys", sent, strokes.len());
+    }
+    Ok(())
+}
+
+/// XKB keymap with one keycode per keysym, NUL-terminated as the protocol
+/// expects
+fn keymap(keysyms: &[u32]) -> String {
+    let mut keycodes = String::new();
+    let mut symbols = String::new();
+    for (i, keysym) in keysyms.iter().enumerate() {
+        keycodes.push_str(&format!("    <K{}> = {};\n", i, i + 9));
+        symbols.push_str(&format!("    key <K{}> {{ [ {:#x} ] }};\n", i, keysym));
+    }
+    format!(
+        "xkb_keymap {{\n\
+         xkb_keycodes \"rpdictation\" {{\n    minimum = 8;\n    maximum = {};\n{}}};\n\
+         xkb_types \"rpdictation\" {{ include \"complete\" }};\n\
+         xkb_compatibility \"rpdictation\" {{ include \"complete\" }};\n\
+         xkb_symbols \"rpdictation\" {{\n{}}};\n\
+         }};\n\0",
+        keysyms.len() + 9,
+        keycodes,
+        symbols
+    )
+}
+
+/// The keymap has to be handed over as a file descriptor: write it to an
+/// unlinked file in the runtime directory.
+fn keymap_file(keymap: &str) -> Result<std::fs::File> {
+    let path = crate::get_runtime_dir().join(format!(
+        "rpdictation-keymap-{}",
+        uuid::Uuid::new_v4().simple()
+    ));
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    std::fs::remove_file(&path).ok();
+    file.write_all(keymap.as_bytes())?;
+    Ok(file)
+}
@@ -0,0 +1,105 @@
+//! Phrases at the end of a dictation that control what happens to it
+//! instead of being typed: "buy milk, send it" types "buy milk" and presses
+//! Enter.
+
+use crate::PUNCTUATION;
+
+/// What a dictation asked for with its last words
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceCommand {
+    /// Throw the whole dictation away ("scratch that")
+    ScratchThat,
+    /// Press Enter after typing it ("send it")
+    SendIt,
+    /// End it with a blank line, so the next dictation starts a new
+    /// paragraph ("new paragraph")
+    NewParagraph,
+    /// End it with a line break ("new line")
+    NewLine,
+}
+
+/// The phrases recognized at the end of a dictation, each a few words
+pub struct VoiceCommands {
+    phrases: Vec<(Vec<String>, VoiceCommand)>,
+}
+
+impl VoiceCommands {
+    /// The phrases in `language`, a code such as "cs" or "en-US", or `None`
+    /// when there are none in it
+    pub fn for_language(language: &str) -> Option<Self> {
+        let primary = language.split(['-', '_']).next().unwrap_or_default();
+        match primary.to_lowercase().as_str() {
+            "en" => Some(Self::english()),
+            "cs" => Some(Self::czech()),
+            "de" => Some(Self::german()),
+            _ => None,
+        }
+    }
+
+    pub fn english() -> Self {
+        Self::new(&[
+            ("scratch that", VoiceCommand::ScratchThat),
+            ("send it", VoiceCommand::SendIt),
+            ("new paragraph", VoiceCommand::NewParagraph),
+            ("new line", VoiceCommand::NewLine),
+        ])
+    }
+
+    pub fn czech() -> Self {
+        Self::new(&[
+            ("smaž to", VoiceCommand::ScratchThat),
+            ("pošli to", VoiceCommand::SendIt),
+            ("nový odstavec", VoiceCommand::NewParagraph),
+            ("nový řádek", VoiceCommand::NewLine),
+        ])
+    }
+
+    pub fn german() -> Self {
+        Self::new(&[
+            ("streich das", VoiceCommand::ScratchThat),
+            ("abschicken", VoiceCommand::SendIt),
+            ("neuer absatz", VoiceCommand::NewParagraph),
+            ("neue zeile", VoiceCommand::NewLine),
+        ])
+    }
+
+    pub fn new(phrases: &[(&str, VoiceCommand)]) -> Self {
+        Self {
+            phrases: phrases
+                .iter()
+                .map(|(phrase, command)| {
+                    (
+                        phrase.split_whitespace().map(str::to_lowercase).collect(),
+                        *command,
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Split the command off the end of `text`: the text before it, without
+    /// a comma leading up to it, and the command. A dictation that
+    /// is nothing but the command leaves an empty text.
+    pub fn parse(&self, text: &str) -> (String, Option<VoiceCommand>) {
+        let words: Vec<(usize, String)> = text
+            .split_whitespace()
+            .map(|w| {
+                let start = w.as_ptr() as usize - text.as_ptr() as usize;
+                (start, w.trim_matches(PUNCTUATION).to_lowercase())
+            })
+            .filter(|(_, key)| !key.is_empty())
+            .collect();
+        for (phrase, command) in &self.phrases {
+            if words.len() < phrase.len() {
+                continue;
+            }
+            let tail = &words[words.len() - phrase.len()..];
+            if tail.iter().map(|(_, key)| key).eq(phrase.iter()) {
+                let before = text[..tail[0].0].trim_end();
+                let before = before.trim_end_matches([',', ';', ':']).trim_end();
+                return (before.to_string(), Some(*command));
+            }
+        }
+        (text.to_string(), None)
+    }
+}
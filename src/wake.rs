@@ -0,0 +1,115 @@
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::daemon::Request;
+use crate::Args;
+#[cfg(feature = "wyoming")]
+use rpdictation::providers::wyoming::WyomingProvider;
+use rpdictation::{audio, vad, TranscriptionProvider, SAMPLE_RATE};
+
+/// Utterances longer than this can't be just the wake phrase
+const MAX_UTTERANCE_SECONDS: f64 = 4.0;
+
+/// Starts a dictation when `phrase` is heard while the daemon is idle.
+///
+/// Everything the microphone picks up meanwhile is cut into utterances at
+/// pauses, and each short one is transcribed by the Wyoming service. It has
+/// to be a local one: a cloud provider would get, and bill, every word
+/// said near the microphone.
+pub struct WakeWord {
+    phrase: String,
+    provider: Arc<dyn TranscriptionProvider>,
+    threshold: f64,
+    segmenter: vad::Segmenter,
+}
+
+impl WakeWord {
+    /// The --wake-word listener configured by `args`, if any
+    pub fn open(args: &Args) -> Result<Option<Self>> {
+        let Some(ref phrase) = args.wake_word else {
+            return Ok(None);
+        };
+        let phrase = normalize(phrase);
+        if phrase.is_empty() {
+            anyhow::bail!("--wake-word is empty");
+        }
+        Ok(Some(Self {
+            phrase,
+            provider: local_provider(args)?,
+            threshold: args.vad_threshold,
+            segmenter: vad::Segmenter::new(args.vad_threshold),
+        }))
+    }
+
+    /// Forget any utterance in progress, as recording has started
+    pub fn reset(&mut self) {
+        self.segmenter = vad::Segmenter::new(self.threshold);
+    }
+
+    /// Feed audio heard while idle. Every utterance it completes is checked
+    /// in the background, and `start` is sent to the daemon loop when one
+    /// holds the phrase.
+    pub fn push(&mut self, samples: &[i16], requests: &mpsc::Sender<Request>) {
+        for utterance in self.segmenter.push(samples) {
+            if utterance.len() as f64 / SAMPLE_RATE as f64 > MAX_UTTERANCE_SECONDS {
+                continue;
+            }
+            let phrase = self.phrase.clone();
+            let provider = Arc::clone(&self.provider);
+            let requests = requests.clone();
+            tokio::spawn(async move {
+                match heard(provider.as_ref(), &phrase, utterance).await {
+                    Ok(true) => {
+                        eprintln!("Wake word heard");
+                        let (reply_tx, _) = oneshot::channel();
+                        let _ = requests.send(("start".to_string(), reply_tx)).await;
+                    }
+                    Ok(false) => {}
+                    Err(e) => eprintln!("Warning: Wake word check failed: {:#}", e),
+                }
+            });
+        }
+    }
+}
+
+/// The Wyoming service given with --wyoming-server
+fn local_provider(args: &Args) -> Result<Arc<dyn TranscriptionProvider>> {
+    #[cfg(feature = "wyoming")]
+    {
+        let server = args
+            .wyoming_server
+            .as_deref()
+            .context("--wake-word needs a local speech-to-text service, see --wyoming-server")?;
+        Ok(Arc::new(WyomingProvider::new(server, &args.language)))
+    }
+    #[cfg(not(feature = "wyoming"))]
+    {
+        let _ = args;
+        anyhow::bail!("--wake-word needs rpdictation built with the wyoming feature")
+    }
+}
+
+/// Whether `phrase` was said in `samples`
+async fn heard(
+    provider: &dyn TranscriptionProvider,
+    phrase: &str,
+    samples: Vec<i16>,
+) -> Result<bool> {
+    let wav = tokio::task::spawn_blocking(move || audio::samples_to_wav(&samples, SAMPLE_RATE))
+        .await
+        .context("WAV encoding task panicked")??;
+    let text = provider.transcribe(&wav, SAMPLE_RATE).await?;
+    // Whole words only: "restart dictation" isn't "start dictation"
+    Ok(format!(" {} ", normalize(&text)).contains(&format!(" {} ", phrase)))
+}
+
+/// Lowercase words without punctuation, single-spaced, so that "Start
+/// dictation!" matches "start dictation"
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,96 @@
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rpdictation::DictationSession;
+
+/// How often the watched directory is rescanned
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+const AUDIO_EXTENSIONS: &[&str] = &["wav", "flac", "mp3", "m4a", "aac", "ogg", "opus", "webm"];
+
+/// Transcribe every audio file that shows up in `dir`, writing the
+/// transcript next to it as `<name>.txt`.
+///
+/// Files that already have a transcript are skipped, so restarting the
+/// daemon does not redo work. A file is only picked up once its size has
+/// stopped changing between two scans, so half-synced recordings are not
+/// transcribed early. Decoding goes through ffmpeg, which handles whatever
+/// format the recorder produces.
+pub async fn run(dir: PathBuf, session: Arc<DictationSession>) {
+    say!("Watching {} for audio files", dir.display());
+    // Size seen on the previous scan, for files not yet transcribed
+    let mut sizes: HashMap<PathBuf, u64> = HashMap::new();
+    // Files that failed; retried only if they change
+    let mut failed: HashSet<(PathBuf, u64)> = HashSet::new();
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        let candidates = match scan(&dir).await {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("Warning: Failed to scan {}: {:#}", dir.display(), e);
+                continue;
+            }
+        };
+        sizes.retain(|path, _| candidates.iter().any(|(p, _)| p == path));
+
+        for (path, size) in candidates {
+            let stable = sizes.insert(path.clone(), size) == Some(size);
+            if !stable || failed.contains(&(path.clone(), size)) {
+                continue;
+            }
+            sizes.remove(&path);
+            let utterance_id = uuid::Uuid::new_v4();
+            eprintln!("[{}] Transcribing {}", utterance_id, path.display());
+            if let Err(e) = transcribe_file(&path, &session, utterance_id).await {
+                eprintln!("Error: {}: {:#}", path.display(), e);
+                failed.insert((path, size));
+            }
+        }
+    }
+}
+
+/// Audio files in `dir` without a transcript, with their current sizes.
+async fn scan(dir: &Path) -> Result<Vec<(PathBuf, u64)>> {
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    let mut found = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let is_audio = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| AUDIO_EXTENSIONS.contains(&e.to_lowercase().as_str()));
+        if !is_audio || transcript_path(&path).exists() {
+            continue;
+        }
+        let meta = entry.metadata().await?;
+        if meta.is_file() {
+            found.push((path, meta.len()));
+        }
+    }
+    Ok(found)
+}
+
+fn transcript_path(audio: &Path) -> PathBuf {
+    audio.with_extension("txt")
+}
+
+async fn transcribe_file(
+    path: &Path,
+    session: &DictationSession,
+    utterance_id: uuid::Uuid,
+) -> Result<Option<String>> {
+    let samples = rpdictation::audio::decode_file(path).await?;
+    let Some(text) = session.process(samples, None, utterance_id).await? else {
+        return Ok(None);
+    };
+    let out = transcript_path(path);
+    tokio::fs::write(&out, format!("{}\n", text))
+        .await
+        .with_context(|| format!("Failed to write {}", out.display()))?;
+    say!("Transcript written to {}", out.display());
+    Ok(Some(text))
+}
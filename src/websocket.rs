@@ -0,0 +1,73 @@
+use anyhow::{Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::Message;
+
+use rpdictation::session::{Event, State};
+use rpdictation::DictationSession;
+
+/// Listen for WebSocket clients on `addr`
+pub async fn bind(addr: &str) -> Result<TcpListener> {
+    TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to listen for WebSocket clients on {}", addr))
+}
+
+/// Push every session event to every client connected to `listener`, one
+/// JSON object per message. A client connecting mid-recording first gets
+/// the `started` event it missed. Anything clients send is ignored.
+pub async fn serve(listener: TcpListener, session: Arc<DictationSession>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                eprintln!("Failed to accept WebSocket connection: {}", e);
+                continue;
+            }
+        };
+        let events = session.subscribe();
+        let recording = match session.state() {
+            State::Recording { id, .. } => Some(Event::Started { id }),
+            State::Idle => None,
+        };
+        tokio::spawn(async move {
+            if let Err(e) = feed(stream, recording, events).await {
+                eprintln!("WebSocket client dropped: {:#}", e);
+            }
+        });
+    }
+}
+
+async fn feed(
+    stream: TcpStream,
+    recording: Option<Event>,
+    mut events: broadcast::Receiver<Event>,
+) -> Result<()> {
+    let mut ws = tokio_tungstenite::accept_async(stream)
+        .await
+        .context("WebSocket handshake failed")?;
+    if let Some(event) = recording {
+        ws.send(Message::text(serde_json::to_string(&event)?))
+            .await?;
+    }
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => ws.send(Message::text(serde_json::to_string(&event)?)).await?,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    eprintln!("Warning: {} session events dropped for a WebSocket client", n);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            message = ws.next() => match message {
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.into()),
+            },
+        }
+    }
+    let _ = ws.close(None).await;
+    Ok(())
+}
@@ -0,0 +1,932 @@
+//! The record → encode → transcribe → output pipeline, driven through
+//! `DictationSession` with mock providers and an in-memory sink.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+
+use rpdictation::confirm::CostLimit;
+use rpdictation::metrics::Timings;
+use rpdictation::numbers::NumberStyle;
+use rpdictation::postprocess::Postprocess;
+use rpdictation::profanity::Profanity;
+use rpdictation::providers::mock::MockProvider;
+use rpdictation::punctuation::Case;
+use rpdictation::replacements::Replacements;
+use rpdictation::rewrite::Rewriter;
+use rpdictation::session::{Event, LanguageRoute, State};
+use rpdictation::setup::{self, Options};
+use rpdictation::voice_commands::VoiceCommands;
+use rpdictation::{
+    audio, DictationSession, OutputSink, Recorder, Transcript, TranscriptionProvider, SAMPLE_RATE,
+};
+
+/// Keeps every transcript it is given
+#[derive(Clone, Default)]
+struct MemorySink {
+    texts: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl OutputSink for MemorySink {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    async fn deliver(&self, transcript: &mut Transcript) -> Result<bool> {
+        self.texts.lock().unwrap().push(transcript.text.clone());
+        Ok(true)
+    }
+}
+
+struct FailingSink;
+
+#[async_trait]
+impl OutputSink for FailingSink {
+    fn name(&self) -> &str {
+        "failing"
+    }
+
+    async fn deliver(&self, _transcript: &mut Transcript) -> Result<bool> {
+        anyhow::bail!("sink failure")
+    }
+}
+
+/// `seconds` of a 440 Hz tone
+fn tone(seconds: f64) -> Vec<i16> {
+    let n = (seconds * SAMPLE_RATE as f64) as usize;
+    (0..n)
+        .map(|i| {
+            let t = i as f64 / SAMPLE_RATE as f64;
+            ((2.0 * std::f64::consts::PI * 440.0 * t).sin() * 8000.0) as i16
+        })
+        .collect()
+}
+
+fn session(
+    providers: Vec<Box<dyn TranscriptionProvider>>,
+    sinks: Vec<Box<dyn OutputSink>>,
+) -> DictationSession {
+    DictationSession::new(providers, sinks, "en-US".to_string())
+}
+
+#[tokio::test]
+async fn transcript_reaches_the_sinks() {
+    let sink = MemorySink::default();
+    let session = session(
+        vec![Box::new(MockProvider::new("hello world"))],
+        vec![Box::new(sink.clone())],
+    );
+    let mut events = session.subscribe();
+
+    let id = uuid::Uuid::new_v4();
+    let text = session.process(tone(2.0), None, id).await.unwrap();
+
+    assert_eq!(text.as_deref(), Some("hello world"));
+    assert_eq!(*sink.texts.lock().unwrap(), vec!["hello world"]);
+    match events.recv().await.unwrap() {
+        Event::Completed {
+            id: event_id,
+            text,
+            output,
+        } => {
+            assert_eq!(event_id, id);
+            assert_eq!(text, "hello world");
+            assert_eq!(output, "memory");
+        }
+        other => panic!("unexpected event {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn replayed_recording_goes_through_the_session() {
+    let sink = MemorySink::default();
+    let session = session(
+        vec![Box::new(MockProvider::new("replayed"))],
+        vec![Box::new(sink.clone())],
+    );
+    let recorder = Recorder::replay(tone(1.5));
+    let id = uuid::Uuid::new_v4();
+
+    recorder.start();
+    session.start(id).unwrap();
+    assert!(matches!(session.state(), State::Recording { id: r, .. } if r == id));
+    assert_eq!(session.stop().unwrap(), id);
+    assert_eq!(session.state(), State::Idle);
+
+    let text = session.process(recorder.stop(), None, id).await.unwrap();
+    assert_eq!(text.as_deref(), Some("replayed"));
+    assert_eq!(*sink.texts.lock().unwrap(), vec!["replayed"]);
+}
+
+#[tokio::test]
+async fn falls_back_to_the_next_provider() {
+    let sink = MemorySink::default();
+    let session = session(
+        vec![
+            Box::new(MockProvider::failing()),
+            Box::new(MockProvider::new("second")),
+        ],
+        vec![Box::new(sink.clone())],
+    );
+
+    let text = session
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(text.as_deref(), Some("second"));
+}
+
+#[tokio::test]
+async fn fails_when_every_provider_fails() {
+    let sink = MemorySink::default();
+    let session = session(
+        vec![Box::new(MockProvider::failing())],
+        vec![Box::new(sink.clone())],
+    );
+    let mut events = session.subscribe();
+
+    let result = session.process(tone(2.0), None, uuid::Uuid::new_v4()).await;
+    assert!(result.is_err());
+    assert!(sink.texts.lock().unwrap().is_empty());
+    assert!(matches!(events.recv().await.unwrap(), Event::Failed { .. }));
+}
+
+#[tokio::test]
+async fn short_recording_is_discarded() {
+    let sink = MemorySink::default();
+    let session = session(
+        vec![Box::new(MockProvider::new("unused"))],
+        vec![Box::new(sink.clone())],
+    );
+
+    let text = session
+        .process(tone(0.3), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(text, None);
+    assert!(sink.texts.lock().unwrap().is_empty());
+}
+
+/// Answers like the mock, but bills $0.10 per minute
+struct PricedProvider;
+
+#[async_trait]
+impl TranscriptionProvider for PricedProvider {
+    fn name(&self) -> &str {
+        "priced"
+    }
+
+    async fn transcribe(&self, _audio_data: &[u8], _sample_rate: u32) -> Result<String> {
+        Ok("expensive".to_string())
+    }
+
+    fn cost_per_minute(&self) -> Option<f64> {
+        Some(0.10)
+    }
+}
+
+#[tokio::test]
+async fn recording_over_the_cost_limit_is_dropped_with_nowhere_to_ask() {
+    let sink = MemorySink::default();
+    let over = session(vec![Box::new(PricedProvider)], vec![Box::new(sink.clone())])
+        .with_cost_limit(CostLimit {
+            max: 0.05,
+            prompt: None,
+        });
+    let mut events = over.subscribe();
+
+    let text = over
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(text, None);
+    assert!(sink.texts.lock().unwrap().is_empty());
+    assert!(matches!(events.recv().await.unwrap(), Event::Failed { .. }));
+
+    // At the limit, nobody is asked
+    let at_limit = session(vec![Box::new(PricedProvider)], vec![Box::new(sink.clone())])
+        .with_cost_limit(CostLimit {
+            max: 0.10,
+            prompt: None,
+        });
+    let text = at_limit
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(text.as_deref(), Some("expensive"));
+}
+
+#[tokio::test]
+async fn failed_output_has_its_exit_code_and_the_others_still_run() {
+    let sink = MemorySink::default();
+    let session = session(
+        vec![Box::new(MockProvider::new("text"))],
+        vec![Box::new(FailingSink), Box::new(sink.clone())],
+    );
+
+    let err = session
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap_err();
+    assert_eq!(rpdictation::error::exit_code(&err), 7);
+    assert_eq!(*sink.texts.lock().unwrap(), vec!["text"]);
+}
+
+/// Keeps the timings of every transcript, then takes a while
+#[derive(Clone, Default)]
+struct SlowSink {
+    timings: Arc<Mutex<Vec<Timings>>>,
+}
+
+#[async_trait]
+impl OutputSink for SlowSink {
+    fn name(&self) -> &str {
+        "slow"
+    }
+
+    async fn deliver(&self, transcript: &mut Transcript) -> Result<bool> {
+        self.timings.lock().unwrap().push(transcript.timings);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        Ok(true)
+    }
+}
+
+#[tokio::test]
+async fn timings_reach_the_sinks() {
+    let sink = SlowSink::default();
+    let session = session(
+        vec![Box::new(MockProvider::new("text"))],
+        vec![Box::new(sink.clone()), Box::new(sink.clone())],
+    );
+
+    let id = uuid::Uuid::new_v4();
+    session.start(id).unwrap();
+    session.stop().unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    session.process(tone(2.0), None, id).await.unwrap();
+
+    let timings = sink.timings.lock().unwrap();
+    assert!(timings[0].capture >= 0.05);
+    assert_eq!(timings[0].output, 0.0);
+    // The second sink waited for the first
+    assert!(timings[1].output >= 0.05);
+    assert_eq!(timings[1].upload, 0.0);
+    assert!(timings[1].total() >= timings[1].capture + timings[1].output);
+}
+
+/// Never answers, but lets `started` know it was asked
+#[derive(Clone, Default)]
+struct HangingProvider {
+    started: Arc<tokio::sync::Notify>,
+}
+
+#[async_trait]
+impl TranscriptionProvider for HangingProvider {
+    fn name(&self) -> &str {
+        "Hanging"
+    }
+
+    async fn transcribe(&self, _audio_data: &[u8], _sample_rate: u32) -> Result<String> {
+        self.started.notify_one();
+        std::future::pending().await
+    }
+
+    fn cost_per_minute(&self) -> Option<f64> {
+        None
+    }
+}
+
+#[tokio::test]
+async fn cancel_drops_the_request_in_flight() {
+    let sink = MemorySink::default();
+    let provider = HangingProvider::default();
+    let session = Arc::new(session(
+        vec![Box::new(provider.clone())],
+        vec![Box::new(sink.clone())],
+    ));
+    let mut events = session.subscribe();
+    assert_eq!(session.cancel_processing(), None);
+
+    let id = uuid::Uuid::new_v4();
+    let processing = tokio::spawn({
+        let session = Arc::clone(&session);
+        async move { session.process(tone(2.0), None, id).await }
+    });
+    provider.started.notified().await;
+    assert_eq!(session.cancel_processing(), Some(id));
+
+    assert_eq!(processing.await.unwrap().unwrap(), None);
+    assert!(matches!(events.recv().await.unwrap(), Event::Cancelled { id: c } if c == id));
+    assert!(sink.texts.lock().unwrap().is_empty());
+    assert_eq!(session.cancel_processing(), None);
+}
+
+#[tokio::test]
+async fn dictations_are_counted_for_prometheus() {
+    let session = session(
+        vec![
+            Box::new(MockProvider::failing()),
+            Box::new(MockProvider::new("text")),
+        ],
+        vec![],
+    );
+    session
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+
+    // Other tests count too, so only what this one adds is certain
+    let metrics = rpdictation::metrics::get().render();
+    assert!(metrics.contains("rpdictation_dictations_total{result=\"completed\"}"));
+    assert!(
+        metrics.contains("rpdictation_provider_requests_total{provider=\"Mock\",result=\"error\"}")
+    );
+    assert!(metrics
+        .contains("rpdictation_provider_latency_seconds_bucket{provider=\"Mock\",le=\"+Inf\"}"));
+    assert!(metrics.contains("# TYPE rpdictation_latency_seconds histogram"));
+}
+
+#[test]
+fn state_machine_rejects_invalid_transitions() {
+    let session = session(vec![Box::new(MockProvider::new("text"))], vec![]);
+
+    let err = session.stop().unwrap_err();
+    assert_eq!(rpdictation::error::exit_code(&err), 8);
+
+    session.start(uuid::Uuid::new_v4()).unwrap();
+    assert!(session.start(uuid::Uuid::new_v4()).is_err());
+    session.cancel().unwrap();
+    assert!(session.cancel().is_err());
+}
+
+#[tokio::test]
+async fn wav_round_trip() {
+    let samples = tone(1.0);
+    let wav = audio::samples_to_wav(&samples, SAMPLE_RATE).unwrap();
+    let path = std::env::temp_dir().join(format!("rpdictation-test-{}.wav", uuid::Uuid::new_v4()));
+    std::fs::write(&path, wav).unwrap();
+
+    let decoded = audio::decode_file(&path).await;
+    std::fs::remove_file(&path).ok();
+    assert_eq!(decoded.unwrap(), samples);
+}
+
+#[tokio::test]
+async fn replacements_apply_before_output() {
+    let sink = MemorySink::default();
+    let replacements = Replacements::parse(
+        "# jargon\n\
+         cube control => kubectl\n\
+         /\\bv(\\d+) dot (\\d+)/ => v$1.$2\n\
+         cat => dog\n",
+    )
+    .unwrap();
+    let session = session(
+        vec![Box::new(MockProvider::new(
+            "Run Cube Control on v1 dot 2, concatenate.",
+        ))],
+        vec![Box::new(sink.clone())],
+    )
+    .with_postprocess(Postprocess {
+        replacements,
+        ..Default::default()
+    });
+
+    let text = session
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(text.as_deref(), Some("Run kubectl on v1.2, concatenate."));
+    assert_eq!(
+        *sink.texts.lock().unwrap(),
+        vec!["Run kubectl on v1.2, concatenate."]
+    );
+    assert!(Replacements::parse("no arrow here").is_err());
+}
+
+#[tokio::test]
+async fn spelled_sequences_become_literal_text() {
+    let session = session(
+        vec![Box::new(MockProvider::new(
+            "My ID is spell Alpha, bravo, capital Charlie, 7, dash niner stop, thanks. \
+             Spell it out, please.",
+        ))],
+        vec![],
+    );
+
+    let text = session
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(
+        text.as_deref(),
+        Some("My ID is abC7-9, thanks. Spell it out, please.")
+    );
+}
+
+#[tokio::test]
+async fn spell_mode_reads_the_whole_dictation() {
+    let session = session(
+        vec![Box::new(MockProvider::new("Kilo, Echo, 4, x-ray."))],
+        vec![],
+    )
+    .with_postprocess(Postprocess {
+        spell: true,
+        ..Default::default()
+    });
+
+    let text = session
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(text.as_deref(), Some("ke4x"));
+}
+
+#[tokio::test]
+async fn numbers_and_dates_are_normalized() {
+    let dictated = "On March fifth, 2025 order twenty-three boxes, \
+                    one hundred and five crates, and 3 pallets for the one I liked.";
+    let normalize = |numbers, date_format: Option<&str>| {
+        let session = session(vec![Box::new(MockProvider::new(dictated))], vec![])
+            .with_postprocess(Postprocess {
+                numbers,
+                date_format: date_format.map(str::to_string),
+                ..Default::default()
+            });
+        async move {
+            session
+                .process(tone(2.0), None, uuid::Uuid::new_v4())
+                .await
+                .unwrap()
+                .unwrap()
+        }
+    };
+
+    assert_eq!(
+        normalize(NumberStyle::Digits, Some("%Y-%m-%d")).await,
+        "On 2025-03-05 order 23 boxes, 105 crates, and 3 pallets for the one I liked."
+    );
+    assert_eq!(
+        normalize(NumberStyle::Prose, None).await,
+        "On March fifth, 2025 order 23 boxes, 105 crates, and three pallets for the one I liked."
+    );
+    assert_eq!(normalize(NumberStyle::Keep, None).await, dictated);
+}
+
+#[tokio::test]
+async fn odd_numbers_and_dates_are_left_sensible() {
+    let normalize = |dictated: &str, numbers, date_format: Option<&str>| {
+        let session = session(vec![Box::new(MockProvider::new(dictated))], vec![])
+            .with_postprocess(Postprocess {
+                numbers,
+                date_format: date_format.map(str::to_string),
+                ..Default::default()
+            });
+        async move {
+            session
+                .process(tone(2.0), None, uuid::Uuid::new_v4())
+                .await
+                .unwrap()
+                .unwrap()
+        }
+    };
+
+    assert_eq!(
+        normalize(
+            "Due March twenty-first, 2025",
+            NumberStyle::Keep,
+            Some("%Y-%m-%d")
+        )
+        .await,
+        "Due 2025-03-21"
+    );
+    assert_eq!(
+        normalize(
+            "Due March twenty first, 2025",
+            NumberStyle::Keep,
+            Some("%Y-%m-%d")
+        )
+        .await,
+        "Due 2025-03-21"
+    );
+    assert_eq!(
+        normalize("Due march fifth, 2025", NumberStyle::Keep, Some("%Y-%m-%d")).await,
+        "Due 2025-03-05"
+    );
+    assert_eq!(
+        normalize("Due May 5 or 5 May, with 3 boxes", NumberStyle::Prose, None).await,
+        "Due May 5 or 5 May, with three boxes"
+    );
+    assert_eq!(
+        normalize("meet at eleven thirty", NumberStyle::Digits, None).await,
+        "meet at 11 30"
+    );
+    assert_eq!(
+        normalize("a fifty fifty chance", NumberStyle::Prose, None).await,
+        "a 50 50 chance"
+    );
+    assert_eq!(
+        normalize("ten fifteen", NumberStyle::Digits, None).await,
+        "10 15"
+    );
+    assert_eq!(
+        normalize("back in nineteen ninety five", NumberStyle::Digits, None).await,
+        "back in 1995"
+    );
+    assert_eq!(
+        normalize("I took the first one", NumberStyle::Digits, None).await,
+        "I took the first one"
+    );
+    assert_eq!(
+        normalize("twenty twenty twenty", NumberStyle::Digits, None).await,
+        "2020 20"
+    );
+    assert_eq!(
+        normalize(&["twenty"; 12].join(" "), NumberStyle::Digits, None).await,
+        "2020 2020 2020 2020 2020 2020"
+    );
+    assert_eq!(
+        normalize("I have ٣ apples", NumberStyle::Prose, None).await,
+        "I have ٣ apples"
+    );
+}
+
+#[tokio::test]
+async fn terminal_commands_are_lowercased_without_a_full_stop() {
+    let session = session(
+        vec![Box::new(MockProvider::new("Git, um, Status  --short."))],
+        vec![],
+    )
+    .with_postprocess(Postprocess {
+        case: Case::Lower,
+        replacements: Replacements::parse("/um,/ =>\n/,/ =>").unwrap(),
+        strip_period: true,
+        collapse_whitespace: true,
+        ..Default::default()
+    });
+
+    let text = session
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(text.as_deref(), Some("git status --short"));
+}
+
+#[tokio::test]
+async fn voice_commands_at_the_end_control_the_dictation() {
+    let sink = MemorySink::default();
+    let session = |text: &str| {
+        DictationSession::new(
+            vec![Box::new(MockProvider::new(text))],
+            vec![Box::new(sink.clone())],
+            "en-US".to_string(),
+        )
+        .with_voice_commands(VoiceCommands::english())
+    };
+
+    let text = session("First point. New paragraph.")
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(text.as_deref(), Some("First point.\n\n"));
+
+    let text = session("Buy milk, send it.")
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(text.as_deref(), Some("Buy milk"));
+
+    // Not at the end, so just words
+    let text = session("Send it to Bob.")
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(text.as_deref(), Some("Send it to Bob."));
+
+    let scratched = session("Something embarrassing. Scratch that!");
+    let mut events = scratched.subscribe();
+    let text = scratched
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(text, None);
+    assert!(matches!(events.recv().await.unwrap(), Event::Failed { .. }));
+    assert_eq!(
+        *sink.texts.lock().unwrap(),
+        vec!["First point.\n\n", "Buy milk", "Send it to Bob."]
+    );
+}
+
+#[tokio::test]
+async fn voice_commands_follow_the_language() {
+    let session = |text: &str, language: &str| {
+        DictationSession::new(
+            vec![Box::new(MockProvider::new(text))],
+            vec![],
+            language.to_string(),
+        )
+        .with_voice_commands(VoiceCommands::for_language(language).unwrap())
+    };
+
+    let text = session("Ahoj, nový řádek.", "cs-CZ")
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(text.as_deref(), Some("Ahoj\n"));
+
+    // English phrases are just words in Czech
+    let text = session("Ahoj, new line.", "cs")
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(text.as_deref(), Some("Ahoj, new line."));
+
+    let text = session("Hallo. Neuer Absatz!", "de-DE")
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(text.as_deref(), Some("Hallo.\n\n"));
+
+    assert!(VoiceCommands::for_language("xx").is_none());
+}
+
+#[tokio::test]
+async fn switching_the_language_switches_voice_commands() {
+    let session = DictationSession::new(
+        vec![Box::new(MockProvider::new("Ahoj, nový řádek."))],
+        vec![],
+        "en-us".to_string(),
+    )
+    .with_voice_commands(VoiceCommands::english());
+
+    session.set_language("cs");
+    assert_eq!(session.language(), "cs");
+    let text = session
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(text.as_deref(), Some("Ahoj\n"));
+}
+
+#[tokio::test]
+async fn detected_language_picks_the_route() {
+    let sink = MemorySink::default();
+    let routes = [(
+        "cs".to_string(),
+        LanguageRoute {
+            providers: vec![Box::new(MockProvider::new("Ahoj Petře, nový řádek."))],
+            postprocess: Some(Postprocess {
+                replacements: Replacements::parse("Petře => Pavle\n").unwrap(),
+                ..Default::default()
+            }),
+        },
+    )]
+    .into_iter()
+    .collect();
+    let session = DictationSession::new(
+        vec![Box::new(
+            MockProvider::new("A hoi Peter, no very.").detecting("cs"),
+        )],
+        vec![Box::new(sink.clone())],
+        "auto".to_string(),
+    )
+    .with_voice_commands(VoiceCommands::english())
+    .with_language_routes(routes);
+
+    let text = session
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    // Transcribed again by the Czech route, with its replacements and the
+    // Czech voice commands
+    assert_eq!(text.as_deref(), Some("Ahoj Pavle\n"));
+
+    // Nothing detected: the defaults
+    let session = DictationSession::new(
+        vec![Box::new(MockProvider::new("Hello, new line."))],
+        vec![],
+        "auto".to_string(),
+    )
+    .with_voice_commands(VoiceCommands::english());
+    let text = session
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(text.as_deref(), Some("Hello\n"));
+}
+
+#[tokio::test]
+async fn profanity_is_masked_or_removed() {
+    let filtered = |profanity| async move {
+        session(
+            vec![Box::new(MockProvider::new(
+                "Shit, the fucking build broke. Scunthorpe is fine.",
+            ))],
+            vec![],
+        )
+        .with_postprocess(Postprocess {
+            profanity,
+            ..Default::default()
+        })
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap()
+        .unwrap()
+    };
+
+    assert_eq!(
+        filtered(Profanity::Mask).await,
+        "S***, the f****** build broke. Scunthorpe is fine."
+    );
+    assert_eq!(
+        filtered(Profanity::Remove).await,
+        "the build broke. Scunthorpe is fine."
+    );
+}
+
+/// Appends its tag to the text, or fails without one
+struct TagRewriter(Option<&'static str>);
+
+#[async_trait]
+impl Rewriter for TagRewriter {
+    fn describe(&self) -> String {
+        "Tagging".to_string()
+    }
+
+    async fn rewrite(&self, text: &str) -> Result<String> {
+        match self.0 {
+            Some(tag) => Ok(format!("{} [{}]", text, tag)),
+            None => anyhow::bail!("rewriter failure"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn rewriters_run_in_order_and_failures_keep_the_text() {
+    let sink = MemorySink::default();
+    let session = session(
+        vec![Box::new(MockProvider::new("memo"))],
+        vec![Box::new(sink.clone())],
+    )
+    .with_rewriters(vec![
+        Box::new(TagRewriter(Some("summary"))),
+        Box::new(TagRewriter(None)),
+        Box::new(TagRewriter(Some("translation"))),
+    ]);
+
+    let text = session
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(text.as_deref(), Some("memo [summary] [translation]"));
+    assert_eq!(
+        *sink.texts.lock().unwrap(),
+        vec!["memo [summary] [translation]"]
+    );
+}
+
+#[tokio::test]
+async fn preview_reports_a_partial_without_output() {
+    let sink = MemorySink::default();
+    let session = session(
+        vec![Box::new(MockProvider::new("so far"))],
+        vec![Box::new(sink.clone())],
+    );
+    let mut events = session.subscribe();
+    let id = uuid::Uuid::new_v4();
+
+    assert_eq!(session.preview(id, tone(0.5)).await.unwrap(), None);
+    let text = session.preview(id, tone(2.0)).await.unwrap();
+    assert_eq!(text.as_deref(), Some("so far"));
+    assert!(sink.texts.lock().unwrap().is_empty());
+    match events.recv().await.unwrap() {
+        Event::Partial { id: event_id, text } => {
+            assert_eq!(event_id, id);
+            assert_eq!(text, "so far");
+        }
+        other => panic!("unexpected event {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn segments_become_subtitles() {
+    use rpdictation::subtitles::{self, Format};
+
+    let session = session(
+        vec![
+            Box::new(MockProvider::failing()),
+            Box::new(MockProvider::new("One. Two.")),
+        ],
+        vec![],
+    );
+    let segments = session.segments(tone(2.0)).await.unwrap();
+    assert_eq!(segments.len(), 2);
+    assert_eq!(segments[0].text, "One.");
+    assert!((segments[1].end - 2.0).abs() < 0.01);
+
+    assert_eq!(
+        subtitles::render(&segments, Format::Srt),
+        "1\n00:00:00,000 --> 00:00:01,000\nOne.\n\n2\n00:00:01,000 --> 00:00:02,000\nTwo.\n\n"
+    );
+    assert!(subtitles::render(&segments, Format::Vtt)
+        .starts_with("WEBVTT\n\n00:00:00.000 --> 00:00:01.000\nOne.\n"));
+    assert_eq!(subtitles::timestamped(&segments), "[00:00] One. Two.\n");
+}
+
+#[test]
+fn pauses_start_timestamped_paragraphs() {
+    use rpdictation::providers::Segment;
+    use rpdictation::subtitles;
+
+    let segment = |start: f64, end: f64, text: &str| Segment {
+        start,
+        end,
+        text: text.to_string(),
+    };
+    let segments = [
+        segment(0.0, 4.0, "Welcome."),
+        segment(4.2, 9.0, "Today we talk about bees."),
+        segment(75.5, 80.0, "Questions?"),
+    ];
+    assert_eq!(
+        subtitles::timestamped(&segments),
+        "[00:00] Welcome. Today we talk about bees.\n\n[01:15] Questions?\n"
+    );
+}
+
+#[tokio::test]
+async fn journal_entries_go_under_the_heading() {
+    use rpdictation::sinks::journal::JournalSink;
+
+    let dir = std::env::temp_dir().join(format!("rpdictation-test-{}", uuid::Uuid::new_v4()));
+    let note = dir.join("today.md");
+    let template = dir.join("template.md");
+    let session = session(
+        vec![Box::new(MockProvider::new("Buy milk."))],
+        vec![Box::new(JournalSink::new(
+            note.to_string_lossy().into_owned(),
+            Some("## Log".to_string()),
+            Some(template.clone()),
+        ))],
+    );
+
+    // A missing note starts from the template
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(&template, "# {{title}}\n\n## Log\n\n## Tasks\n").unwrap();
+    session
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    let text = std::fs::read_to_string(&note).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[..3], ["# today", "", "## Log"]);
+    assert!(lines[3].starts_with("- ") && lines[3].ends_with(" Buy milk."));
+    assert_eq!(lines[4..], ["", "## Tasks"]);
+
+    // Later entries follow the earlier ones; a note without the heading
+    // gets it at the end
+    session
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    let text = std::fs::read_to_string(&note).unwrap();
+    assert_eq!(
+        text.lines().nth(4).map(|l| l.ends_with(" Buy milk.")),
+        Some(true)
+    );
+    assert!(text.ends_with("\n\n## Tasks\n"));
+    std::fs::write(&note, "Notes\n\n").unwrap();
+    session
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    let text = std::fs::read_to_string(&note).unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+    assert!(text.starts_with("Notes\n\n## Log\n- "));
+}
+
+#[tokio::test]
+async fn options_build_the_session_the_command_line_would() {
+    let options = Options {
+        provider: Some("mock".to_string()),
+        language: "en-us".to_string(),
+        output: Some(String::new()),
+        case: Case::Lower,
+        strip_period: true,
+        ..Default::default()
+    };
+    let session = setup::build_session(&options).unwrap();
+
+    let text = session
+        .process(tone(2.0), None, uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(text.as_deref(), Some("this is a mock transcription"));
+
+    let options = Options {
+        provider: Some("mock,nonsense".to_string()),
+        ..options
+    };
+    assert!(setup::build_providers(&options).is_err());
+}
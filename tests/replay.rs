@@ -0,0 +1,251 @@
+//! The `rpdictation` binary end to end: a WAV file replayed as the recording
+//! and transcribed by the mock provider, with no audio hardware, network,
+//! or desktop involved.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A scratch directory, removed on drop
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("rpdictation-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        Self(dir)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+fn write_tone(path: &Path, seconds: f64) {
+    let samples: Vec<i16> = (0..(seconds * 16000.0) as usize)
+        .map(|i| ((i as f64 * 0.17).sin() * 8000.0) as i16)
+        .collect();
+    std::fs::write(
+        path,
+        rpdictation::audio::samples_to_wav(&samples, 16000).unwrap(),
+    )
+    .unwrap();
+}
+
+/// Run rpdictation in `dir`, isolated from the user's config and runtime
+/// directory
+fn rpdictation(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rpdictation"))
+        .args(args)
+        .current_dir(dir)
+        .env("XDG_RUNTIME_DIR", dir.join("run"))
+        .env("XDG_CONFIG_HOME", dir.join("config"))
+        .env("XDG_DATA_HOME", dir.join("data"))
+        .env("RPDICTATION_MOCK_TEXT", "Replayed dictation.")
+        .stdin(std::process::Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn replay_prints_the_transcript() {
+    let dir = TempDir::new();
+    let wav = dir.0.join("speech.wav");
+    write_tone(&wav, 2.0);
+
+    let output = rpdictation(
+        &dir.0,
+        &[
+            "--replay",
+            wav.to_str().unwrap(),
+            "--provider",
+            "mock",
+            "--quiet",
+        ],
+    );
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Replayed dictation.\n"
+    );
+}
+
+#[test]
+fn replay_json_describes_the_dictation() {
+    let dir = TempDir::new();
+    let wav = dir.0.join("speech.wav");
+    write_tone(&wav, 3.0);
+
+    let output = rpdictation(
+        &dir.0,
+        &[
+            "--replay",
+            wav.to_str().unwrap(),
+            "--provider",
+            "mock",
+            "--json",
+            "--notifier",
+            "none",
+        ],
+    );
+
+    assert!(output.status.success(), "{:?}", output);
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["transcript"], "Replayed dictation.");
+    assert_eq!(result["provider"], "Mock");
+    assert_eq!(result["duration_seconds"], 3.0);
+    assert_eq!(result["output"], "none");
+}
+
+#[test]
+fn replay_appends_to_a_file() {
+    let dir = TempDir::new();
+    let wav = dir.0.join("speech.wav");
+    let notes = dir.0.join("notes.md");
+    write_tone(&wav, 2.0);
+
+    for _ in 0..2 {
+        let output = rpdictation(
+            &dir.0,
+            &[
+                "--replay",
+                wav.to_str().unwrap(),
+                "--provider",
+                "mock",
+                "--notifier",
+                "none",
+                "--append-file",
+                notes.to_str().unwrap(),
+            ],
+        );
+        assert!(output.status.success(), "{:?}", output);
+    }
+
+    let contents = std::fs::read_to_string(&notes).unwrap();
+    assert_eq!(contents.matches("Replayed dictation.").count(), 2);
+}
+
+#[test]
+fn stop_without_a_recording_exits_with_8() {
+    let dir = TempDir::new();
+    let output = rpdictation(&dir.0, &["stop"]);
+    assert_eq!(output.status.code(), Some(8));
+}
+
+#[test]
+fn history_lists_dictations_by_tag() {
+    let dir = TempDir::new();
+    let wav = dir.0.join("speech.wav");
+    write_tone(&wav, 2.0);
+
+    for tags in [&["--tag", "project-x", "--name", "standup"][..], &[]] {
+        let mut args = vec![
+            "--replay",
+            wav.to_str().unwrap(),
+            "--provider",
+            "mock",
+            "--notifier",
+            "none",
+            "--history",
+        ];
+        args.extend(tags);
+        let output = rpdictation(&dir.0, &args);
+        assert!(output.status.success(), "{:?}", output);
+    }
+
+    let output = rpdictation(&dir.0, &["--json", "history", "--tag", "project-x"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let entries: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["name"], "standup");
+    assert_eq!(entries[0]["tags"], serde_json::json!(["project-x"]));
+    assert_eq!(entries[0]["text"], "Replayed dictation.");
+}
+
+#[test]
+fn history_exports_csv() {
+    let dir = TempDir::new();
+    let wav = dir.0.join("speech.wav");
+    write_tone(&wav, 2.0);
+
+    let output = rpdictation(
+        &dir.0,
+        &[
+            "--replay",
+            wav.to_str().unwrap(),
+            "--provider",
+            "mock",
+            "--notifier",
+            "none",
+            "--history",
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = rpdictation(&dir.0, &["history", "export", "--format", "csv"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("id,time,duration_seconds,"));
+    assert!(lines[1].contains(",Mock,en-us,"));
+    assert!(lines[1].ends_with(",Replayed dictation."));
+
+    let output = rpdictation(
+        &dir.0,
+        &[
+            "history",
+            "export",
+            "--format",
+            "json",
+            "--since",
+            "2999-01-01",
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "[]\n");
+}
+
+#[test]
+fn gc_deletes_old_history() {
+    let dir = TempDir::new();
+    let history = dir.0.join("data/rpdictation/history");
+    std::fs::create_dir_all(&history).unwrap();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    // A year old, ten days old, and from today
+    let ages = [365, 10, 0];
+    let ids: Vec<uuid::Uuid> = ages.iter().map(|_| uuid::Uuid::new_v4()).collect();
+    for (id, days) in ids.iter().zip(ages) {
+        let entry = serde_json::json!({
+            "id": id,
+            "timestamp": now - days * 86400,
+            "duration_seconds": 1.0,
+            "provider": "Mock",
+            "language": "en-us",
+            "text": "Old dictation.",
+            "cost": null,
+        });
+        std::fs::write(history.join(format!("{}.json", id)), entry.to_string()).unwrap();
+        write_tone(&history.join(format!("{}.wav", id)), 0.1);
+    }
+
+    let output = rpdictation(
+        &dir.0,
+        &["--keep-transcripts", "90d", "--keep-audio", "7d", "gc"],
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    let exists = |id: &uuid::Uuid, ext: &str| history.join(format!("{}.{}", id, ext)).exists();
+    assert!(!exists(&ids[0], "json") && !exists(&ids[0], "wav"));
+    assert!(exists(&ids[1], "json") && !exists(&ids[1], "wav"));
+    assert!(exists(&ids[2], "json") && exists(&ids[2], "wav"));
+}